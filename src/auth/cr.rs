@@ -0,0 +1,59 @@
+//! The legacy MONGODB-CR challenge-response mechanism, used by servers before MongoDB 3.0.
+use bson::Bson;
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+use error::Error::ResponseError;
+use error::Result;
+
+use super::Authenticator;
+
+// Computes the hex-encoded MD5 digest of a string.
+fn md5_hex(input: &str) -> String {
+    let mut md5 = Md5::new();
+    md5.input_str(input);
+    md5.result_str()
+}
+
+// Computes the authenticate key, md5(nonce + user + md5("<user>:mongo:<password>")).
+fn digest_key(nonce: &str, user: &str, password: &str) -> String {
+    let password_digest = md5_hex(&format!("{}:mongo:{}", user, password));
+    md5_hex(&format!("{}{}{}", nonce, user, password_digest))
+}
+
+/// Authenticates a user-password pair with a getnonce/authenticate exchange.
+pub fn auth(authenticator: &Authenticator, user: &str, password: &str) -> Result<()> {
+    let reply = try!(authenticator.command(doc! { "getnonce" => 1 }));
+
+    let nonce = match reply.get("nonce") {
+        Some(&Bson::String(ref nonce)) => nonce.to_owned(),
+        _ => return Err(ResponseError("No nonce returned".to_owned())),
+    };
+
+    let key = digest_key(&nonce, user, password);
+
+    let authenticate = doc! {
+        "authenticate" => 1,
+        "user" => user,
+        "nonce" => nonce,
+        "key" => key
+    };
+
+    try!(authenticator.command(authenticate));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digest_key, md5_hex};
+
+    #[test]
+    fn password_digest() {
+        assert_eq!("1c33006ec1ffd90f9cadcbcc0e118200", md5_hex("user:mongo:pencil"));
+    }
+
+    #[test]
+    fn authenticate_key() {
+        assert_eq!("21742f26431831d5cfca035a08c5bdf6",
+                   digest_key("2375531c32080ae8", "user", "pencil"));
+    }
+}
@@ -0,0 +1,406 @@
+//! Authentication schemes.
+mod cr;
+mod plain;
+mod saslprep;
+mod scram;
+mod x509;
+
+use bson::Bson::{self, Binary};
+use bson::Document;
+use bson::spec::BinarySubtype::Generic;
+use {Client, CommandType, ThreadedClient};
+use CommandType::{IsMaster, Suppressed};
+use connstring::ConnectionString;
+use db::{Database, ThreadedDatabase};
+use error::Error::{self, ArgumentError, AuthenticationError, OperationError, ResponseError};
+use error::Result;
+use self::scram::ScramVersion;
+use stream::Stream;
+use wire_protocol::flags::OpQueryFlags;
+use wire_protocol::operations::Message;
+
+pub use self::scram::ScramCache;
+
+use std::str::FromStr;
+
+/// The database that holds users whose credentials are managed outside of MongoDB.
+pub const EXTERNAL_DB: &'static str = "$external";
+
+/// The minimum wire version of servers that support SCRAM-SHA-1 (MongoDB 3.0).
+const SCRAM_SHA_1_WIRE_VERSION: i64 = 3;
+
+/// Authentication mechanisms supported by the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuthMechanism {
+    /// SCRAM using SHA-1 over the MD5-hashed password; the default before MongoDB 4.0.
+    ScramSha1,
+    /// SCRAM using SHA-256 over the SASLprep-normalized password.
+    ScramSha256,
+    /// The legacy challenge-response mechanism; the default before MongoDB 3.0.
+    MongoDbCr,
+    /// Client certificate authentication against the '$external' database.
+    MongoDbX509,
+    /// SASL PLAIN against the '$external' database, typically backed by LDAP.
+    Plain,
+}
+
+impl FromStr for AuthMechanism {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "SCRAM-SHA-1" => AuthMechanism::ScramSha1,
+            "SCRAM-SHA-256" => AuthMechanism::ScramSha256,
+            "MONGODB-CR" => AuthMechanism::MongoDbCr,
+            "MONGODB-X509" => AuthMechanism::MongoDbX509,
+            "PLAIN" => AuthMechanism::Plain,
+            _ => return Err(ArgumentError(format!("Unsupported authMechanism '{}'.", s))),
+        })
+    }
+}
+
+impl AuthMechanism {
+    /// Returns the mechanism name, as used by the authMechanism connection string option.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            &AuthMechanism::ScramSha1 => "SCRAM-SHA-1",
+            &AuthMechanism::ScramSha256 => "SCRAM-SHA-256",
+            &AuthMechanism::MongoDbCr => "MONGODB-CR",
+            &AuthMechanism::MongoDbX509 => "MONGODB-X509",
+            &AuthMechanism::Plain => "PLAIN",
+        }
+    }
+}
+
+/// Credentials used to authenticate each new connection made by a client.
+#[derive(Clone)]
+pub struct Credential {
+    /// The user name, or the certificate subject for MONGODB-X509.
+    pub user: String,
+    /// The user's password.
+    pub password: String,
+    /// The database that holds the user's credentials.
+    pub source: String,
+    /// The authentication mechanism, or None to negotiate one with the server.
+    pub mechanism: Option<AuthMechanism>,
+}
+
+impl Credential {
+    /// Creates a new credential that negotiates its mechanism with the server.
+    pub fn new(user: &str, password: &str, source: &str) -> Credential {
+        Credential {
+            user: user.to_owned(),
+            password: password.to_owned(),
+            source: source.to_owned(),
+            mechanism: None,
+        }
+    }
+
+    /// Reads the credential from a connection string, if it contains a user or requests
+    /// MONGODB-X509, whose user defaults to the subject of the client certificate. The
+    /// credential source is the authSource option, '$external' for MONGODB-X509 and PLAIN,
+    /// the connection string database, or 'admin', in that order.
+    pub fn from_config(config: &ConnectionString) -> Result<Option<Credential>> {
+        let option = |name: &str| config.options.as_ref().and_then(|options| {
+            options.get(name).cloned()
+        });
+
+        let mechanism = match option("authMechanism") {
+            Some(name) => Some(try!(AuthMechanism::from_str(&name))),
+            None => None,
+        };
+
+        let user = match (config.user.as_ref(), mechanism) {
+            (Some(user), _) => user.to_owned(),
+            (None, Some(AuthMechanism::MongoDbX509)) => String::new(),
+            (None, _) => return Ok(None),
+        };
+
+        let source = match (option("authSource"), mechanism) {
+            (Some(source), _) => source,
+            (None, Some(AuthMechanism::MongoDbX509)) |
+            (None, Some(AuthMechanism::Plain)) => EXTERNAL_DB.to_owned(),
+            (None, _) => config.database.clone().unwrap_or("admin".to_owned()),
+        };
+
+        Ok(Some(Credential {
+            user: user,
+            password: config.password.clone().unwrap_or(String::new()),
+            source: source,
+            mechanism: mechanism,
+        }))
+    }
+
+    /// Returns the '<source>.<user>' value for the isMaster saslSupportedMechs field.
+    pub fn namespace(&self) -> String {
+        format!("{}.{}", self.source, self.user)
+    }
+}
+
+/// The client's credential and the mechanisms the server monitors reported for it, read by
+/// an operation before server selection and used to authenticate any connection it opens.
+#[derive(Clone)]
+pub struct ConnectionAuth {
+    /// The credential to authenticate new connections with, if any.
+    pub credential: Option<Credential>,
+    /// The mechanisms reported for the credential in a monitor's isMaster reply, if any.
+    pub mechanisms: Option<Vec<String>>,
+}
+
+impl ConnectionAuth {
+    /// Returns an empty snapshot, with which new connections are not authenticated.
+    pub fn none() -> ConnectionAuth {
+        ConnectionAuth { credential: None, mechanisms: None }
+    }
+
+    /// Reads the client's current credential and the mechanisms reported for it.
+    /// Must not be called while holding a topology lock.
+    pub fn read(client: &Client) -> Result<ConnectionAuth> {
+        let credential = try!(client.credential.read()).clone();
+        let mechanisms = match credential {
+            Some(ref credential) => {
+                try!(client.topology.sasl_supported_mechs(&credential.namespace()))
+            },
+            None => None,
+        };
+
+        Ok(ConnectionAuth { credential: credential, mechanisms: mechanisms })
+    }
+}
+
+/// Authenticates a new connection with the credential read by the operation, if any.
+pub fn authenticate_socket(client: &Client, socket: &Stream,
+                           auth: &ConnectionAuth) -> Result<()> {
+    let credential = match auth.credential {
+        Some(ref credential) => credential,
+        None => return Ok(()),
+    };
+
+    let authenticator = Authenticator::with_socket(client.db(&credential.source),
+                                                   try!(socket.try_clone()));
+
+    // Network failures are reported as-is, so that server selection can try another server;
+    // any other failure will recur on every connection and is reported to the operation.
+    authenticator.auth_credential(credential, auth.mechanisms.as_ref()).map_err(|err| match err {
+        Error::IoError(_) => err,
+        _ => AuthenticationError(Box::new(err)),
+    })
+}
+
+/// Handles authentication logic.
+pub struct Authenticator {
+    db: Database,
+    // If set, commands are sent over this connection instead of through the connection pool.
+    socket: Option<Stream>,
+}
+
+impl Authenticator {
+    /// Creates a new authenticator.
+    pub fn new(db: Database) -> Authenticator {
+        Authenticator { db: db, socket: None }
+    }
+
+    /// Creates a new authenticator that authenticates a specific connection.
+    pub fn with_socket(db: Database, socket: Stream) -> Authenticator {
+        Authenticator { db: db, socket: Some(socket) }
+    }
+
+    /// Authenticates a credential, using its mechanism, the strongest of the mechanisms
+    /// already reported for it, or one negotiated with the server.
+    pub fn auth_credential(self, credential: &Credential,
+                           reported: Option<&Vec<String>>) -> Result<()> {
+        let mechanism = match credential.mechanism {
+            Some(mechanism) => mechanism,
+            None => try!(self.select_mechanism(&credential.user, reported)),
+        };
+
+        self.auth_with_mechanism(&credential.user, &credential.password, mechanism)
+    }
+
+    /// Authenticates a user-password pair against a database, using the mechanism given
+    /// by the authMechanism connection string option or the strongest one the server supports.
+    pub fn auth(self, user: &str, password: &str) -> Result<()> {
+        let mechanism = try!(self.select_mechanism(user, None));
+        self.auth_with_mechanism(user, password, mechanism)
+    }
+
+    /// Authenticates a user-password pair against a database using a specific mechanism.
+    /// MONGODB-X509 takes the certificate subject as the user, or derives it from the client's
+    /// certificate if empty, and ignores the password.
+    /// MONGODB-X509 and PLAIN always authenticate against the '$external' database.
+    pub fn auth_with_mechanism(self, user: &str, password: &str,
+                               mechanism: AuthMechanism) -> Result<()> {
+        match mechanism {
+            AuthMechanism::ScramSha1 => scram::auth(&self, user, password, ScramVersion::Sha1),
+            AuthMechanism::ScramSha256 => scram::auth(&self, user, password, ScramVersion::Sha256),
+            AuthMechanism::MongoDbCr => cr::auth(&self, user, password),
+            AuthMechanism::MongoDbX509 => x509::auth(&self, user),
+            AuthMechanism::Plain => plain::auth(&self, user, password),
+        }
+    }
+
+    /// Logs out the users authenticated on the authenticator's database.
+    pub fn logout(self) -> Result<()> {
+        try!(self.run(doc! { "logout" => 1 }, CommandType::Logout));
+        Ok(())
+    }
+
+    // Reads the authMechanism connection string option, or negotiates a mechanism with the
+    // server if the option is absent. Mechanisms already reported by a server monitor for
+    // the user are used without sending another isMaster.
+    fn select_mechanism(&self, user: &str,
+                        reported: Option<&Vec<String>>) -> Result<AuthMechanism> {
+        if let Some(ref options) = self.db.client.topology.config.options {
+            if let Some(name) = options.get("authMechanism") {
+                return AuthMechanism::from_str(name);
+            }
+        }
+
+        if let Some(mechs) = reported {
+            if !mechs.is_empty() {
+                return Ok(strongest_mechanism(mechs));
+            }
+        }
+
+        let is_master = doc! {
+            "isMaster" => 1,
+            "saslSupportedMechs" => (format!("{}.{}", self.db.name, user))
+        };
+
+        let reply = try!(self.run(is_master, IsMaster));
+        let mechs = match reply.get("saslSupportedMechs") {
+            Some(&Bson::Array(ref mechs)) => mechs.iter().filter_map(|mech| match mech {
+                &Bson::String(ref mech) => Some(mech.to_owned()),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+
+        if !mechs.is_empty() {
+            return Ok(strongest_mechanism(&mechs));
+        }
+
+        // Servers before MongoDB 3.0 neither report mechanisms nor support SCRAM.
+        let max_wire_version = match reply.get("maxWireVersion") {
+            Some(&Bson::I32(v)) => v as i64,
+            Some(&Bson::I64(v)) => v,
+            _ => 0,
+        };
+
+        if max_wire_version >= SCRAM_SHA_1_WIRE_VERSION {
+            Ok(AuthMechanism::ScramSha1)
+        } else {
+            Ok(AuthMechanism::MongoDbCr)
+        }
+    }
+
+    // Returns an authenticator for another database, on the same connection if there is one.
+    fn for_db(&self, name: &str) -> Result<Authenticator> {
+        let socket = match self.socket {
+            Some(ref socket) => Some(try!(socket.try_clone())),
+            None => None,
+        };
+
+        Ok(Authenticator { db: self.db.client.db(name), socket: socket })
+    }
+
+    // Sends an authentication command to the database.
+    fn command(&self, doc: Document) -> Result<Document> {
+        self.run(doc, Suppressed)
+    }
+
+    fn run(&self, doc: Document, cmd_type: CommandType) -> Result<Document> {
+        match self.socket {
+            Some(ref socket) => self.socket_command(socket, doc),
+            None => self.db.command(doc, cmd_type, None),
+        }
+    }
+
+    // Sends a command over the authenticator's connection and reads the reply.
+    fn socket_command(&self, mut socket: &Stream, doc: Document) -> Result<Document> {
+        let req_id = self.db.client.get_req_id();
+        let message = try!(Message::new_query(req_id, OpQueryFlags::no_flags(),
+                                              format!("{}.$cmd", self.db.name), 0, 1, doc, None));
+
+        try!(message.write(&mut socket));
+
+        let reply = match try!(Message::read(&mut socket)) {
+            Message::OpReply { documents, .. } => documents.into_iter().next(),
+            _ => None,
+        };
+
+        let reply = match reply {
+            Some(reply) => reply,
+            None => return Err(ResponseError("No reply returned".to_owned())),
+        };
+
+        let ok = match reply.get("ok") {
+            Some(&Bson::FloatingPoint(ok)) => ok == 1.0,
+            Some(&Bson::I32(ok)) => ok == 1,
+            Some(&Bson::I64(ok)) => ok == 1,
+            _ => false,
+        };
+
+        if ok {
+            return Ok(reply);
+        }
+
+        match reply.get("errmsg") {
+            Some(&Bson::String(ref msg)) => Err(OperationError(msg.to_owned())),
+            _ => Err(OperationError(format!("Authentication command failed: {:?}", reply))),
+        }
+    }
+
+    // Starts a SASL conversation, returning the server reply.
+    fn sasl_start(&self, mechanism: &str, payload: Vec<u8>) -> Result<Document> {
+        let start_doc = doc! {
+            "saslStart" => 1,
+            "autoAuthorize" => 1,
+            "payload" => (Binary(Generic, payload)),
+            "mechanism" => mechanism
+        };
+
+        self.command(start_doc)
+    }
+
+    // Continues a SASL conversation, returning the server reply.
+    fn sasl_continue(&self, conversation_id: &Bson, payload: Vec<u8>) -> Result<Document> {
+        let next_doc = doc! {
+            "saslContinue" => 1,
+            "payload" => (Binary(Generic, payload)),
+            "conversationId" => (conversation_id.clone())
+        };
+
+        self.command(next_doc)
+    }
+}
+
+/// Returns the strongest supported mechanism among those reported in an isMaster
+/// saslSupportedMechs field, defaulting to SCRAM-SHA-1.
+pub fn strongest_mechanism(mechs: &[String]) -> AuthMechanism {
+    if mechs.iter().any(|mech| mech == AuthMechanism::ScramSha256.as_str()) {
+        AuthMechanism::ScramSha256
+    } else {
+        AuthMechanism::ScramSha1
+    }
+}
+
+// Extracts the conversation id from a SASL reply.
+fn conversation_id(doc: &Document) -> Result<Bson> {
+    match doc.get("conversationId") {
+        Some(bson) => Ok(bson.clone()),
+        None => Err(ResponseError("No conversationId returned".to_owned()))
+    }
+}
+
+// Extracts the UTF-8 payload from a SASL reply.
+fn payload(doc: &Document) -> Result<String> {
+    let data = match doc.get("payload") {
+        Some(&Binary(_, ref payload)) => payload.to_owned(),
+        _ => return Err(ResponseError("Invalid payload returned".to_owned()))
+    };
+
+    match String::from_utf8(data) {
+        Ok(string) => Ok(string),
+        Err(_) => Err(ResponseError("Invalid UTF-8 payload returned".to_owned()))
+    }
+}
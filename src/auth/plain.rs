@@ -0,0 +1,32 @@
+//! The SASL PLAIN mechanism (RFC 4616), used for users managed by an external LDAP service.
+//!
+//! PLAIN sends the password in cleartext, so it only runs if the client connects over TLS,
+//! or was created with `allow_insecure_auth`.
+use bson::Bson;
+use error::Error::ArgumentError;
+use error::Result;
+
+use super::{Authenticator, EXTERNAL_DB};
+
+/// Authenticates a user-password pair against the '$external' database.
+pub fn auth(authenticator: &Authenticator, user: &str, password: &str) -> Result<()> {
+    let client = &authenticator.db.client;
+    if !client.stream_connector.is_tls() && !client.allow_insecure_auth {
+        return Err(ArgumentError("PLAIN authentication sends the password in cleartext and \
+                                  requires TLS; set allow_insecure_auth to use it without TLS."
+                                 .to_owned()));
+    }
+
+    let external = try!(authenticator.for_db(EXTERNAL_DB));
+    let payload = format!("\0{}\0{}", user, password).into_bytes();
+    let mut doc = try!(external.sasl_start("PLAIN", payload));
+
+    loop {
+        if let Some(&Bson::Boolean(true)) = doc.get("done") {
+            return Ok(())
+        }
+
+        let conversation_id = try!(super::conversation_id(&doc));
+        doc = try!(external.sasl_continue(&conversation_id, vec![]));
+    }
+}
@@ -0,0 +1,319 @@
+//! The SCRAM-SHA-1 and SCRAM-SHA-256 SASL mechanisms.
+use bson::Bson;
+use crypto::digest::Digest;
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::md5::Md5;
+use crypto::pbkdf2;
+use crypto::sha1::Sha1;
+use crypto::sha2::Sha256;
+use error::Error::{DefaultError, MaliciousServerError, ResponseError};
+use error::MaliciousServerErrorType;
+use error::Result;
+use rustc_serialize::base64::{self, FromBase64, ToBase64};
+use textnonce::TextNonce;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{Authenticator, conversation_id, payload};
+use super::saslprep::saslprep;
+
+const B64_CONFIG : base64::Config = base64::Config { char_set: base64::CharacterSet::Standard,
+                                                     newline: base64::Newline::LF,
+                                                     pad: true, line_length: None };
+
+/// The minimum iteration count accepted from the server.
+const MIN_ITERATION_COUNT: u32 = 4096;
+
+/// The hash function underlying a SCRAM conversation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScramVersion {
+    Sha1,
+    Sha256,
+}
+
+struct InitialData {
+    message: String,
+    response: String,
+    nonce: String,
+    conversation_id: Bson,
+}
+
+struct AuthData {
+    server_key: Vec<u8>,
+    message: String,
+    response: ::bson::Document,
+}
+
+// Identifies the inputs to PBKDF2. The prepared password is stored as a digest.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    user: String,
+    password_digest: Vec<u8>,
+    salt: Vec<u8>,
+    iterations: u32,
+    version: ScramVersion,
+}
+
+#[derive(Clone)]
+struct DerivedKeys {
+    client_key: Vec<u8>,
+    server_key: Vec<u8>,
+}
+
+/// Caches the keys derived from SCRAM passwords, so that repeated authentications with the
+/// same credentials, salt, and iteration count skip PBKDF2.
+pub struct ScramCache {
+    keys: Mutex<HashMap<CacheKey, DerivedKeys>>,
+}
+
+impl ScramCache {
+    /// Creates an empty cache.
+    pub fn new() -> ScramCache {
+        ScramCache { keys: Mutex::new(HashMap::new()) }
+    }
+
+    /// Removes all cached keys.
+    pub fn clear(&self) {
+        if let Ok(mut keys) = self.keys.lock() {
+            keys.clear();
+        }
+    }
+
+    // Returns the cached keys for the inputs, deriving and caching them on a miss.
+    fn derive(&self, key: CacheKey, password: &str) -> DerivedKeys {
+        if let Ok(keys) = self.keys.lock() {
+            if let Some(derived) = keys.get(&key) {
+                return derived.clone();
+            }
+        }
+
+        let version = key.version;
+        let salted_password = version.hi(password.as_bytes(), &key.salt[..], key.iterations);
+        let client_key = version.hmac(&salted_password[..], "Client Key".as_bytes());
+        let server_key = version.hmac(&salted_password[..], "Server Key".as_bytes());
+
+        let derived = DerivedKeys {
+            client_key: client_key,
+            server_key: server_key,
+        };
+
+        if let Ok(mut keys) = self.keys.lock() {
+            keys.insert(key, derived.clone());
+        }
+
+        derived
+    }
+}
+
+impl ScramVersion {
+    /// Returns the SASL mechanism name.
+    pub fn mechanism(&self) -> &'static str {
+        match self {
+            &ScramVersion::Sha1 => "SCRAM-SHA-1",
+            &ScramVersion::Sha256 => "SCRAM-SHA-256",
+        }
+    }
+
+    // Prepares the password for salting. SCRAM-SHA-1 uses the MD5 digest of
+    // 'user:mongo:password', while SCRAM-SHA-256 uses the SASLprep-normalized password.
+    fn prepare_password(&self, user: &str, password: &str) -> Result<String> {
+        match self {
+            &ScramVersion::Sha1 => {
+                let mut md5 = Md5::new();
+                md5.input_str(&format!("{}:mongo:{}", user, password)[..]);
+                Ok(md5.result_str())
+            },
+            &ScramVersion::Sha256 => saslprep(password),
+        }
+    }
+
+    // HMAC(key, input)
+    fn hmac(&self, key: &[u8], input: &[u8]) -> Vec<u8> {
+        match self {
+            &ScramVersion::Sha1 => hmac(Sha1::new(), key, input),
+            &ScramVersion::Sha256 => hmac(Sha256::new(), key, input),
+        }
+    }
+
+    // H(input)
+    fn hash(&self, input: &[u8]) -> Vec<u8> {
+        match self {
+            &ScramVersion::Sha1 => hash(Sha1::new(), input),
+            &ScramVersion::Sha256 => hash(Sha256::new(), input),
+        }
+    }
+
+    // Hi(password, salt, i)
+    fn hi(&self, password: &[u8], salt: &[u8], i: u32) -> Vec<u8> {
+        match self {
+            &ScramVersion::Sha1 => hi(Sha1::new(), password, salt, i),
+            &ScramVersion::Sha256 => hi(Sha256::new(), password, salt, i),
+        }
+    }
+}
+
+fn hmac<D: Digest>(digest: D, key: &[u8], input: &[u8]) -> Vec<u8> {
+    let mut hmac = Hmac::new(digest, key);
+    hmac.input(input);
+    hmac.result().code().to_owned()
+}
+
+fn hash<D: Digest>(mut digest: D, input: &[u8]) -> Vec<u8> {
+    digest.input(input);
+    let mut output : Vec<_> = (0..digest.output_bytes()).map(|_| 0).collect();
+    digest.result(&mut output);
+    output
+}
+
+fn hi<D: Digest>(digest: D, password: &[u8], salt: &[u8], i: u32) -> Vec<u8> {
+    let mut hmac = Hmac::new(digest, password);
+    let mut output : Vec<_> = (0..hmac.output_bytes()).map(|_| 0).collect();
+    pbkdf2::pbkdf2(&mut hmac, salt, i, &mut output);
+    output
+}
+
+// Escapes a username for use in a SCRAM message.
+fn escape_user(user: &str) -> String {
+    user.replace("=", "=3D").replace(",", "=2C")
+}
+
+/// Authenticates a user-password pair over a SCRAM conversation.
+pub fn auth(authenticator: &Authenticator, user: &str, password: &str,
+            version: ScramVersion) -> Result<()> {
+    let prepared_password = try!(version.prepare_password(user, password));
+    let initial_data = try!(start(authenticator, user, version));
+    let conversation_id = initial_data.conversation_id.clone();
+    let auth_data = try!(next(authenticator, user, prepared_password, initial_data, version));
+
+    finish(authenticator, conversation_id, auth_data, version)
+}
+
+fn start(authenticator: &Authenticator, user: &str, version: ScramVersion) -> Result<InitialData> {
+    let text_nonce = match TextNonce::sized(64) {
+        Ok(text_nonce) => text_nonce,
+        Err(string) => return Err(DefaultError(string))
+    };
+
+    let nonce = format!("{}", text_nonce);
+    let message = format!("n={},r={}", escape_user(user), nonce);
+    let bytes = format!("n,,{}", message).into_bytes();
+
+    let doc = try!(authenticator.sasl_start(version.mechanism(), bytes));
+    let response = try!(payload(&doc));
+    let id = try!(conversation_id(&doc));
+
+    Ok(InitialData { message: message, response: response, nonce: nonce,
+                     conversation_id: id })
+}
+
+fn next(authenticator: &Authenticator, user: &str, password: String, initial_data: InitialData,
+        version: ScramVersion) -> Result<AuthData> {
+    // Parse out rnonce, salt, and iteration count
+    let (rnonce_opt, salt_opt, i_opt) = scan_fmt!(&initial_data.response[..], "r={},s={},i={}", String, String, u32);
+
+    let rnonce_b64 = match rnonce_opt {
+        Some(val) => val,
+        None => return Err(ResponseError("Invalid rnonce returned".to_owned()))
+    };
+
+    // Validate rnonce to make sure server isn't malicious
+    if !rnonce_b64.starts_with(&initial_data.nonce[..]) {
+        return Err(MaliciousServerError(MaliciousServerErrorType::InvalidRnonce))
+    }
+
+    let salt_b64 = match salt_opt {
+        Some(val) => val,
+        None => return Err(ResponseError("Invalid salt returned".to_owned()))
+    };
+
+    let salt = match salt_b64.from_base64() {
+        Ok(val) => val,
+        Err(_) => return Err(ResponseError("Invalid base64 salt returned".to_owned()))
+    };
+
+    let i = match i_opt {
+        Some(val) => val,
+        None => return Err(ResponseError("Invalid iteration count returned".to_owned()))
+    };
+
+    // A low iteration count would weaken the salted password
+    if i < MIN_ITERATION_COUNT {
+        return Err(MaliciousServerError(MaliciousServerErrorType::InsufficientIterations));
+    }
+
+    // Salt password and compute the client and server keys, or reuse them from the cache
+    let cache_key = CacheKey {
+        user: user.to_owned(),
+        password_digest: version.hash(password.as_bytes()),
+        salt: salt,
+        iterations: i,
+        version: version,
+    };
+
+    let keys = authenticator.db.client.scram_cache.derive(cache_key, &password);
+    let client_key = keys.client_key;
+
+    // Hash into stored key
+    let stored_key = version.hash(&client_key[..]);
+
+    // Create auth message
+    let without_proof = format!("c=biws,r={}", rnonce_b64);
+    let auth_message = format!("{},{},{}", initial_data.message, initial_data.response, without_proof);
+
+    // Compute client signature
+    let client_signature = version.hmac(&stored_key[..], auth_message.as_bytes());
+
+    // Sanity check
+    if client_key.len() != client_signature.len() {
+        return Err(DefaultError("Generated client key and/or client signature is invalid".to_owned()));
+    }
+
+    // Compute proof by xor'ing key and signature
+    let mut proof = vec![];
+    for i in 0..client_key.len() {
+        proof.push(client_key[i] ^ client_signature[i]);
+    }
+
+    // Encode proof and produce the message to send to the server
+    let b64_proof = proof.to_base64(B64_CONFIG);
+    let final_message = format!("{},p={}", without_proof, b64_proof);
+
+    let response = try!(authenticator.sasl_continue(&initial_data.conversation_id,
+                                                    final_message.into_bytes()));
+
+    Ok(AuthData { server_key: keys.server_key, message: auth_message,
+                  response: response })
+}
+
+fn finish(authenticator: &Authenticator, conversation_id: Bson, auth_data: AuthData,
+          version: ScramVersion) -> Result<()> {
+    // Compute server signature
+    let server_signature = version.hmac(&auth_data.server_key[..], auth_data.message.as_bytes());
+
+    let mut doc = auth_data.response;
+
+    // Verify server signature
+    let payload_str = try!(payload(&doc));
+
+    // Check that the signature exists
+    let verifier = match scan_fmt!(&payload_str[..], "v={}", String) {
+        Some(string) => string,
+        None => return Err(MaliciousServerError(MaliciousServerErrorType::NoServerSignature)),
+    };
+
+    // Check that the signature is valid
+    if verifier.ne(&server_signature.to_base64(B64_CONFIG)[..]) {
+        return Err(MaliciousServerError(MaliciousServerErrorType::InvalidServerSignature));
+    }
+
+    // Complete the conversation with empty messages
+    loop {
+        if let Some(&Bson::Boolean(true)) = doc.get("done") {
+            return Ok(())
+        }
+
+        doc = try!(authenticator.sasl_continue(&conversation_id, vec![]));
+    }
+}
@@ -0,0 +1,40 @@
+//! The MONGODB-X509 mechanism, which authenticates the subject of a client certificate.
+//!
+//! The server only accepts this mechanism over a TLS connection presenting the certificate,
+//! so clients authenticate each new connection once its TLS handshake has completed.
+use bson::Bson;
+use error::Error::ArgumentError;
+use error::Result;
+
+use super::{Authenticator, EXTERNAL_DB};
+
+/// Authenticates a certificate subject against the '$external' database. An empty subject
+/// is derived from the client certificate configured for TLS connections; without one,
+/// the subject is omitted so that servers from MongoDB 3.4 derive it from the connection.
+pub fn auth(authenticator: &Authenticator, subject: &str) -> Result<()> {
+    let connector = &authenticator.db.client.stream_connector;
+
+    let subject = if subject.is_empty() {
+        if !connector.is_tls() {
+            return Err(ArgumentError("MONGODB-X509 authentication requires a TLS connection \
+                                      presenting a client certificate.".to_owned()));
+        }
+
+        try!(connector.certificate_subject()).unwrap_or(String::new())
+    } else {
+        subject.to_owned()
+    };
+
+    let mut authenticate = doc! {
+        "authenticate" => 1,
+        "mechanism" => "MONGODB-X509"
+    };
+
+    if !subject.is_empty() {
+        authenticate.insert("user".to_owned(), Bson::String(subject));
+    }
+
+    let external = try!(authenticator.for_db(EXTERNAL_DB));
+    try!(external.command(authenticate));
+    Ok(())
+}
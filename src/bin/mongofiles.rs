@@ -0,0 +1,340 @@
+//! A command-line tool for inspecting and managing GridFS stores, modeled after `mongofiles`.
+//!
+//! ```text
+//! mongofiles [--uri <uri>] [--db <database>] [--prefix <prefix>] <command> [arguments]
+//!
+//! Commands:
+//!     list [prefix]                  Lists files whose filenames start with the prefix.
+//!     search <string>                Lists files whose filenames contain the string.
+//!     put <filename> [local_path]    Uploads a local file, read from local_path or filename.
+//!     get <filename> [local_path]    Downloads the latest revision of a file, written to
+//!                                    local_path or filename.
+//!     get_id <id> [local_path]       Downloads a file by object ID.
+//!     delete <filename>              Deletes all files with the filename.
+//!     delete_id <id>                 Deletes a file by object ID.
+//!     stat <filename>                Describes the latest revision of a file and verifies
+//!                                    its chunks.
+//! ```
+//!
+//! Output is written to stdout as one JSON object per line. Errors are written to stderr as a
+//! JSON object with an "error" field, and the process exits with a non-zero status.
+#[macro_use(bson, doc)]
+extern crate bson;
+extern crate mongodb;
+extern crate rustc_serialize;
+
+use bson::{Bson, oid};
+use mongodb::{Client, Error, Result, ThreadedClient};
+use mongodb::coll::options::FindOptions;
+use mongodb::db::ThreadedDatabase;
+use mongodb::gridfs::{FileSelector, Store, ThreadedStore};
+use mongodb::gridfs::bucket::GridFsBucket;
+use mongodb::gridfs::file::File;
+use mongodb::gridfs::options::GridFsUploadOptions;
+use rustc_serialize::json::{Json, Object};
+
+use std::{env, fs, process};
+use std::io::{self, Write};
+
+const USAGE: &'static str = "usage: mongofiles [--uri <uri>] [--db <database>] \
+                             [--prefix <prefix>] <command> [arguments]
+
+commands:
+    list [prefix]                  list files whose filenames start with the prefix
+    search <string>                list files whose filenames contain the string
+    put <filename> [local_path]    upload a local file
+    get <filename> [local_path]    download the latest revision of a file
+    get_id <id> [local_path]       download a file by object ID
+    delete <filename>              delete all files with the filename
+    delete_id <id>                 delete a file by object ID
+    stat <filename>                describe and verify the latest revision of a file";
+
+struct Config {
+    uri: String,
+    db: String,
+    prefix: String,
+    command: String,
+    args: Vec<String>,
+}
+
+fn parse_args<I: Iterator<Item=String>>(mut args: I) -> std::result::Result<Config, String> {
+    let mut config = Config {
+        uri: "mongodb://localhost:27017".to_owned(),
+        db: "test".to_owned(),
+        prefix: "fs".to_owned(),
+        command: String::new(),
+        args: Vec::new(),
+    };
+
+    while let Some(arg) = args.next() {
+        let target = match &arg[..] {
+            "--uri" => &mut config.uri,
+            "--db" => &mut config.db,
+            "--prefix" => &mut config.prefix,
+            "-h" | "--help" => return Err(USAGE.to_owned()),
+            _ => {
+                if config.command.is_empty() {
+                    config.command = arg.clone();
+                } else {
+                    config.args.push(arg.clone());
+                }
+                continue;
+            }
+        };
+
+        match args.next() {
+            Some(value) => *target = value,
+            None => return Err(format!("Missing value for {}.\n{}", arg, USAGE)),
+        }
+    }
+
+    if config.command.is_empty() {
+        return Err(USAGE.to_owned());
+    }
+
+    Ok(config)
+}
+
+// Escapes regular expression metacharacters in a filename fragment.
+fn escape_regex(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Formats a file id, which other drivers may have stored as a value other than an ObjectId.
+fn id_to_json(id: &Bson) -> Json {
+    match id {
+        &Bson::ObjectId(ref oid) => Json::String(oid.to_hex()),
+        &Bson::String(ref s) => Json::String(s.to_owned()),
+        other => Json::String(format!("{}", other)),
+    }
+}
+
+fn file_to_json(file: &File) -> Json {
+    let mut obj = Object::new();
+    obj.insert("_id".to_owned(), id_to_json(&file.id));
+
+    if let Some(ref name) = file.name {
+        obj.insert("filename".to_owned(), Json::String(name.to_owned()));
+    }
+
+    obj.insert("length".to_owned(), Json::I64(file.len()));
+    obj.insert("chunkSize".to_owned(), Json::I64(file.chunk_size as i64));
+    obj.insert("md5".to_owned(), Json::String(file.md5().to_owned()));
+
+    if let Some(ref date) = file.upload_date {
+        obj.insert("uploadDate".to_owned(), Json::String(date.to_rfc3339()));
+    }
+
+    if let Some(ref content_type) = file.content_type {
+        obj.insert("contentType".to_owned(), Json::String(content_type.to_owned()));
+    }
+
+    if !file.aliases.is_empty() {
+        obj.insert("aliases".to_owned(), Json::Array(file.aliases.iter().map(|alias| {
+            Json::String(alias.to_owned())
+        }).collect()));
+    }
+
+    Json::Object(obj)
+}
+
+fn print_json(json: Json) {
+    println!("{}", json);
+}
+
+fn status(command: &str, fields: Vec<(&str, Json)>) -> Json {
+    let mut obj = Object::new();
+    obj.insert("command".to_owned(), Json::String(command.to_owned()));
+    obj.insert("ok".to_owned(), Json::Boolean(true));
+    for (key, value) in fields {
+        obj.insert(key.to_owned(), value);
+    }
+    Json::Object(obj)
+}
+
+fn arg<'a>(args: &'a [String], i: usize, name: &str) -> Result<&'a str> {
+    match args.get(i) {
+        Some(arg) => Ok(&arg[..]),
+        None => Err(Error::ArgumentError(format!("Missing argument <{}>.", name))),
+    }
+}
+
+fn parse_id(s: &str) -> Result<oid::ObjectId> {
+    Ok(try!(oid::ObjectId::with_string(s)))
+}
+
+fn list(store: &Store, filter: Option<bson::Document>) -> Result<()> {
+    let mut options = FindOptions::new();
+    options.sort = Some(doc!{ "filename" => 1, "uploadDate" => 1 });
+
+    for file in try!(store.find(filter, Some(options))) {
+        print_json(file_to_json(&file));
+    }
+    Ok(())
+}
+
+// Opens the most recent revision of the named file.
+fn open_latest(store: &Store, name: &str) -> Result<File> {
+    GridFsBucket::from(store.clone()).open_download_stream_by_name(name, None)
+}
+
+fn download(store: &Store, selector: FileSelector, path: &str) -> Result<()> {
+    // Look up the stored file first, so that a missing file leaves the local path untouched.
+    let mut file = match selector {
+        FileSelector::Name(name) => try!(open_latest(store, &name)),
+        FileSelector::Id(id) => try!(store.open_id(id)),
+    };
+
+    let mut local = try!(fs::File::create(path));
+    let written = try!(io::copy(&mut file, &mut local));
+    try!(file.close());
+
+    print_json(status("get", vec![("local", Json::String(path.to_owned())),
+                                  ("length", Json::U64(written))]));
+    Ok(())
+}
+
+fn run(config: Config) -> Result<()> {
+    let client = try!(Client::with_uri(&config.uri));
+    let store = Store::with_prefix(client.db(&config.db), config.prefix.to_owned());
+    let args = &config.args[..];
+
+    match &config.command[..] {
+        "list" => {
+            let filter = args.get(0).map(|prefix| {
+                doc!{ "filename" => (Bson::RegExp(format!("^{}", escape_regex(prefix)),
+                                                  String::new())) }
+            });
+            list(&store, filter)
+        },
+        "search" => {
+            let fragment = try!(arg(args, 0, "string"));
+            list(&store, Some(doc!{ "filename" => (Bson::RegExp(escape_regex(fragment),
+                                                             String::new())) }))
+        },
+        "put" => {
+            let name = try!(arg(args, 0, "filename"));
+            let path = args.get(1).map(|path| &path[..]).unwrap_or(name);
+            let local = try!(fs::File::open(path));
+            let id = try!(store.put_reader(name.to_owned(), local,
+                                        Some(GridFsUploadOptions::new()), None));
+            print_json(status("put", vec![("_id", id_to_json(&id)),
+                                          ("filename", Json::String(name.to_owned()))]));
+            Ok(())
+        },
+        "get" => {
+            let name = try!(arg(args, 0, "filename"));
+            let path = args.get(1).map(|path| &path[..]).unwrap_or(name);
+            download(&store, FileSelector::Name(name.to_owned()), path)
+        },
+        "get_id" => {
+            let id = try!(parse_id(try!(arg(args, 0, "id"))));
+            let default_path = id.to_hex();
+            let path = args.get(1).map(|path| &path[..]).unwrap_or(&default_path[..]);
+            download(&store, FileSelector::Id(Bson::ObjectId(id.clone())), path)
+        },
+        "delete" => {
+            let name = try!(arg(args, 0, "filename"));
+            try!(store.remove(name.to_owned()));
+            print_json(status("delete", vec![("filename", Json::String(name.to_owned()))]));
+            Ok(())
+        },
+        "delete_id" => {
+            let id = try!(parse_id(try!(arg(args, 0, "id"))));
+            try!(store.open_id(Bson::ObjectId(id.clone())));
+            try!(store.remove_id(Bson::ObjectId(id.clone())));
+            print_json(status("delete_id", vec![("_id", Json::String(id.to_hex()))]));
+            Ok(())
+        },
+        "stat" => {
+            let name = try!(arg(args, 0, "filename"));
+            let file = try!(open_latest(&store, name));
+            let report = try!(store.verify(file.id.clone()));
+
+            let mut json = match file_to_json(&file) {
+                Json::Object(obj) => obj,
+                _ => Object::new(),
+            };
+
+            json.insert("chunks".to_owned(), Json::I64(report.found_chunks));
+            json.insert("expectedChunks".to_owned(), Json::I64(report.expected_chunks));
+            json.insert("missingChunks".to_owned(), Json::Array(
+                report.missing_chunks.iter().map(|&n| Json::I64(n as i64)).collect()));
+            json.insert("valid".to_owned(), Json::Boolean(report.is_valid()));
+            print_json(Json::Object(json));
+            Ok(())
+        },
+        command => Err(Error::ArgumentError(format!("Unknown command '{}'.\n{}", command, USAGE))),
+    }
+}
+
+fn main() {
+    let config = match parse_args(env::args().skip(1)) {
+        Ok(config) => config,
+        Err(usage) => {
+            let _ = writeln!(io::stderr(), "{}", usage);
+            process::exit(2);
+        }
+    };
+
+    if let Err(err) = run(config) {
+        let mut obj = Object::new();
+        obj.insert("error".to_owned(), Json::String(format!("{}", err)));
+        let _ = writeln!(io::stderr(), "{}", Json::Object(obj));
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape_regex, parse_args};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let config = parse_args(args(&["list"]).into_iter()).unwrap();
+        assert_eq!("mongodb://localhost:27017", config.uri);
+        assert_eq!("test", config.db);
+        assert_eq!("fs", config.prefix);
+        assert_eq!("list", config.command);
+        assert!(config.args.is_empty());
+    }
+
+    #[test]
+    fn parse_options_and_arguments() {
+        let config = parse_args(args(&["--db", "media", "get", "--uri", "mongodb://db:27018",
+                                       "movie.mp4", "copy.mp4", "--prefix", "videos"])
+                                .into_iter()).unwrap();
+        assert_eq!("mongodb://db:27018", config.uri);
+        assert_eq!("media", config.db);
+        assert_eq!("videos", config.prefix);
+        assert_eq!("get", config.command);
+        assert_eq!(args(&["movie.mp4", "copy.mp4"]), config.args);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse_args(args(&[]).into_iter()).is_err());
+        assert!(parse_args(args(&["--help"]).into_iter()).is_err());
+        assert!(parse_args(args(&["--db"]).into_iter()).is_err());
+        assert!(parse_args(args(&["--db", "media"]).into_iter()).is_err());
+    }
+
+    #[test]
+    fn escape_metacharacters() {
+        assert_eq!("movie", escape_regex("movie"));
+        assert_eq!(r"movie\.mp4", escape_regex("movie.mp4"));
+        assert_eq!(r"a\+b\(1\)\[2\]\{3\}", escape_regex("a+b(1)[2]{3}"));
+        assert_eq!(r"\^\$\|\?\*\\", escape_regex(r"^$|?*\"));
+    }
+}
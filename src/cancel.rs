@@ -0,0 +1,172 @@
+//! Cancellation of in-flight operations from another thread.
+//!
+//! ```no_run
+//! # use mongodb::{Client, ThreadedClient};
+//! # use mongodb::cancel::CancellationToken;
+//! # use mongodb::coll::options::FindOptions;
+//! # use mongodb::db::ThreadedDatabase;
+//! # use std::thread;
+//! # let client = Client::connect("localhost", 27017).unwrap();
+//! # let coll = client.db("test").collection("logs");
+//! #
+//! let token = CancellationToken::new();
+//! let mut options = FindOptions::new();
+//! options.cancellation_token = Some(token.clone());
+//!
+//! let killer = client.clone();
+//! thread::spawn(move || {
+//!     thread::sleep_ms(1000);
+//!     token.cancel_and_kill(killer).unwrap();
+//! });
+//!
+//! for result in coll.find(None, Some(options)).unwrap() {
+//!     // Iteration returns a CancelledError once the token is cancelled.
+//!     if result.is_err() { break; }
+//! }
+//! ```
+use {Client, Result, ThreadedClient};
+use Error::CancelledError;
+
+use bson::{self, Bson};
+use pool::PooledStream;
+
+use rand::{thread_rng, Rng};
+
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+
+/// A shareable handle for cancelling the operations it is passed to.
+///
+/// Cancelling a token shuts down the sockets of all operations currently using it, and causes
+/// further operations and cursor iterations to fail with a `CancelledError`. Operations are
+/// tagged with a unique comment, allowing the server-side operation to be killed as well.
+#[derive(Clone)]
+pub struct CancellationToken {
+    inner: Arc<TokenInner>,
+}
+
+struct TokenInner {
+    // The comment attached to operations using this token.
+    tag: String,
+    cancelled: AtomicBool,
+    // Handles to the sockets currently in use by operations, keyed by registration.
+    sockets: Mutex<HashMap<usize, TcpStream>>,
+    // The key assigned to the next registration.
+    next_id: AtomicUsize,
+    // Whether any operation has run with this token.
+    registered: AtomicBool,
+}
+
+/// Associates a token with a borrowed stream for the duration of a network round trip.
+/// If the token is cancelled, the stream is discarded instead of returning to its pool.
+pub struct Registration<'a> {
+    token: &'a CancellationToken,
+    stream: &'a PooledStream,
+    id: usize,
+}
+
+impl<'a> Drop for Registration<'a> {
+    fn drop(&mut self) {
+        if let Ok(mut sockets) = self.token.inner.sockets.lock() {
+            sockets.remove(&self.id);
+        }
+
+        if self.token.is_cancelled() {
+            self.stream.discard();
+        }
+    }
+}
+
+impl CancellationToken {
+    /// Creates a new token with a unique operation tag.
+    pub fn new() -> CancellationToken {
+        let tag = format!("mongodb-rust-cancel-{:x}", thread_rng().gen::<u64>());
+        CancellationToken {
+            inner: Arc::new(TokenInner {
+                tag: tag,
+                cancelled: AtomicBool::new(false),
+                sockets: Mutex::new(HashMap::new()),
+                next_id: ATOMIC_USIZE_INIT,
+                registered: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// Returns the comment used to tag operations associated with this token.
+    pub fn tag(&self) -> &str {
+        &self.inner.tag
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns a CancelledError if the token has been cancelled.
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(CancelledError)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Cancels all current and future operations using this token. The sockets of all
+    /// in-flight operations are shut down, interrupting blocked reads and writes.
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+
+        if let Ok(mut sockets) = self.inner.sockets.lock() {
+            for (_, socket) in sockets.drain() {
+                let _ = socket.shutdown(Shutdown::Both);
+            }
+        }
+    }
+
+    /// Cancels the token, and then kills any server-side operations tagged with this
+    /// token's comment. The operations are located with `current_op` and killed with
+    /// `kill_op` through the client, so the commands are authenticated and monitored.
+    pub fn cancel_and_kill(&self, client: Client) -> Result<()> {
+        self.cancel();
+
+        // No operation has run with this token, so there is nothing to kill.
+        if !self.inner.registered.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        // Servers before MongoDB 3.2 report the comment as part of the query.
+        let clauses: Vec<_> = ["query.$comment", "query.comment", "command.comment",
+                               "originatingCommand.comment"].iter().map(|field| {
+            let mut clause = bson::Document::new();
+            clause.insert(field.to_string(), Bson::String(self.tag().to_owned()));
+            Bson::Document(clause)
+        }).collect();
+
+        let mut filter = bson::Document::new();
+        filter.insert("$or".to_owned(), Bson::Array(clauses));
+
+        for op in try!(client.current_op(Some(filter))) {
+            try!(client.kill_op(op.opid));
+        }
+
+        Ok(())
+    }
+
+    /// Associates the token with a stream for the duration of an operation's network
+    /// round trip. Returns a CancelledError if the token has already been cancelled.
+    pub fn register<'a>(&'a self, stream: &'a PooledStream) -> Result<Registration<'a>> {
+        // Shutting down the underlying socket also interrupts reads on a TLS session.
+        let handle = try!(stream.get_socket().tcp_stream().try_clone());
+
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let mut locked = try!(self.inner.sockets.lock());
+        try!(self.check());
+        locked.insert(id, handle);
+        self.inner.registered.store(true, Ordering::SeqCst);
+
+        Ok(Registration { token: self, stream: stream, id: id })
+    }
+}
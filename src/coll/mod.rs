@@ -4,6 +4,8 @@ pub mod error;
 pub mod options;
 pub mod results;
 
+use apm::{CommandStarted, CommandResult, EventRunner};
+use cancel::CancellationToken;
 use bson::{self, Bson, oid};
 use command_type::CommandType;
 
@@ -13,16 +15,21 @@ use self::options::*;
 use self::results::*;
 
 use ThreadedClient;
-use common::{ReadPreference, WriteConcern};
+use common::{Deadline, ReadPreference, WriteConcern};
 use cursor::Cursor;
 use db::{Database, ThreadedDatabase};
+use stats::CollectionStats;
+use topology::server::ServerDescription;
 
 use Result;
-use Error::{ArgumentError, ResponseError,OperationError, BulkWriteError};
+use Error::{ArgumentError, ResponseError,OperationError, BulkWriteError, EventListenerError};
 
-use wire_protocol::flags::OpQueryFlags;
+use time;
+use wire_protocol::flags::{OpDeleteFlags, OpInsertFlags, OpQueryFlags, OpUpdateFlags};
+use wire_protocol::operations::Message;
 use std::collections::{BTreeMap, VecDeque};
 use std::iter::FromIterator;
+use std::mem;
 
 /// Interfaces with a MongoDB collection.
 pub struct Collection {
@@ -32,6 +39,7 @@ pub struct Collection {
     pub namespace: String,
     read_preference: ReadPreference,
     write_concern: WriteConcern,
+    timeout_ms: Option<i64>,
 }
 
 impl Collection {
@@ -55,9 +63,52 @@ impl Collection {
             namespace: format!("{}.{}", db.name, name),
             read_preference: rp,
             write_concern: wc,
+            timeout_ms: db.timeout_ms,
         }
     }
 
+    /// Creates a copy of the collection representation with a different operation time limit.
+    /// A timeout of zero places no limit on operations.
+    pub fn with_timeout(&self, timeout_ms: Option<i64>) -> Collection {
+        Collection {
+            db: self.db.clone(),
+            namespace: self.namespace.to_owned(),
+            read_preference: self.read_preference.to_owned(),
+            write_concern: self.write_concern.to_owned(),
+            timeout_ms: timeout_ms,
+        }
+    }
+
+    // Computes the deadline for an operation, favoring an operation-level timeout
+    // over the collection-level timeout.
+    fn deadline(&self, timeout_ms: Option<i64>) -> Deadline {
+        Deadline::new(timeout_ms.or(self.timeout_ms))
+    }
+
+    // Runs a command against the collection's database, bounded by the operation timeout.
+    fn command(&self, spec: bson::Document, cmd_type: CommandType,
+               read_preference: Option<ReadPreference>,
+               timeout_ms: Option<i64>) -> Result<bson::Document> {
+
+        let mut options = FindOptions::new();
+        options.batch_size = 1;
+        options.read_preference = read_preference;
+        options.timeout_ms = timeout_ms.or(self.timeout_ms);
+
+        let coll = self.db.collection("$cmd");
+        let res = try!(coll.find_one_with_command_type(Some(spec.clone()), Some(options),
+                                                       cmd_type));
+        res.ok_or(OperationError(format!("Failed to execute command with spec {:?}.", spec)))
+    }
+
+    // Generates a command cursor, bounded by the operation timeout.
+    fn command_cursor(&self, spec: bson::Document, cmd_type: CommandType,
+                      read_pref: ReadPreference, timeout_ms: Option<i64>,
+                      cancellation: Option<CancellationToken>) -> Result<Cursor> {
+        Cursor::command_cursor(self.db.client.clone(), &self.db.name[..], spec, cmd_type,
+                               read_pref, self.deadline(timeout_ms), cancellation)
+    }
+
     /// Returns a unique operational request id.
     pub fn get_req_id(&self) -> i32 {
         self.db.client.get_req_id()
@@ -82,6 +133,51 @@ impl Collection {
         self.db.drop_collection(&self.name()[..])
     }
 
+    /// Renames the collection within its database, returning a representation of the renamed
+    /// collection. If `drop_target` is set, an existing collection with the new name is dropped.
+    pub fn rename(&self, new_name: &str, drop_target: bool) -> Result<Collection> {
+        let doc = doc! {
+            "renameCollection" => (self.namespace.to_owned()),
+            "to" => (format!("{}.{}", self.db.name, new_name)),
+            "dropTarget" => drop_target
+        };
+
+        // renameCollection must run against the admin database.
+        let admin = self.db.client.db("admin");
+        try!(admin.command(doc, CommandType::RenameCollection, None));
+
+        Ok(Collection::new(self.db.clone(), new_name, false,
+                           Some(self.read_preference.to_owned()),
+                           Some(self.write_concern.to_owned())))
+    }
+
+    /// Returns storage statistics for the collection, as reported by collStats.
+    pub fn stats(&self) -> Result<CollectionStats> {
+        let doc = doc! { "collStats" => (self.name()) };
+        let res = try!(self.command(doc, CommandType::CollStats, None, None));
+        Ok(CollectionStats::with_doc(res))
+    }
+
+    /// Modifies the collection's validation rules or the TTL of an existing index.
+    pub fn coll_mod(&self, options: CollModOptions) -> Result<()> {
+        let doc = options.to_command(&self.name()[..]);
+        self.command(doc, CommandType::CollMod, None, None).map(|_| ())
+    }
+
+    /// Converts the collection into a capped collection with a maximum size in bytes.
+    pub fn convert_to_capped(&self, size: i64) -> Result<()> {
+        if size <= 0 {
+            return Err(ArgumentError("The size of a capped collection must be positive.".to_owned()));
+        }
+
+        let doc = doc! {
+            "convertToCapped" => (self.name()),
+            "size" => size
+        };
+
+        self.command(doc, CommandType::ConvertToCapped, None, None).map(|_| ())
+    }
+
     /// Runs an aggregation framework pipeline.
     pub fn aggregate(&self, pipeline: Vec<bson::Document>,
                      options: Option<AggregateOptions>) -> Result<Cursor> {
@@ -102,7 +198,8 @@ impl Collection {
         }
 
         let read_pref = opts.read_preference.unwrap_or(self.read_preference.to_owned());
-        self.db.command_cursor(spec, CommandType::Aggregate, read_pref)
+        self.command_cursor(spec, CommandType::Aggregate, read_pref, opts.timeout_ms,
+                            opts.cancellation_token)
     }
 
     /// Gets the number of documents matching the filter.
@@ -126,7 +223,7 @@ impl Collection {
         }
 
         let read_pref = opts.read_preference.unwrap_or(self.read_preference.to_owned());
-        let result = try!(self.db.command(spec, CommandType::Count, Some(read_pref)));
+        let result = try!(self.command(spec, CommandType::Count, Some(read_pref), opts.timeout_ms));
         match result.get("n") {
             Some(&Bson::I32(ref n)) => Ok(*n as i64),
             Some(&Bson::I64(ref n)) => Ok(*n),
@@ -148,7 +245,7 @@ impl Collection {
         }
 
         let read_pref = opts.read_preference.unwrap_or(self.read_preference.to_owned());
-        let result = try!(self.db.command(spec, CommandType::Distinct, Some(read_pref)));
+        let result = try!(self.command(spec, CommandType::Distinct, Some(read_pref), opts.timeout_ms));
         match result.get("values") {
             Some(&Bson::Array(ref vals)) => Ok(vals.to_owned()),
             _ => Err(ResponseError("No values received from server.".to_owned()))
@@ -167,13 +264,19 @@ impl Collection {
         let options = options.unwrap_or(FindOptions::new());
         let flags = OpQueryFlags::with_find_options(&options);
 
-        let doc = if options.sort.is_some() {
+        let doc = if options.sort.is_some() || options.max_time_ms.is_some() {
             let mut doc = bson::Document::new();
             doc.insert("$query".to_owned(),
                        Bson::Document(filter.unwrap_or(bson::Document::new())));
 
-            doc.insert("$orderby".to_owned(),
-                       Bson::Document(options.sort.as_ref().unwrap().clone()));
+            if let Some(ref sort) = options.sort {
+                doc.insert("$orderby".to_owned(), Bson::Document(sort.clone()));
+            }
+
+            // Bounded further by the time remaining before the operation's deadline.
+            if let Some(max_time_ms) = options.max_time_ms {
+                doc.insert("$maxTimeMS".to_owned(), Bson::I64(max_time_ms));
+            }
 
             doc
         } else {
@@ -181,10 +284,12 @@ impl Collection {
         };
 
         let read_pref = options.read_preference.unwrap_or(self.read_preference.to_owned());
+        let deadline = self.deadline(options.timeout_ms);
 
         Cursor::query(self.db.client.clone(), self.namespace.to_owned(), options.batch_size,
                       flags, options.skip as i32, options.limit, doc,
-                      options.projection.clone(), cmd_type, false, read_pref)
+                      options.projection.clone(), cmd_type, false, read_pref, deadline,
+                      options.cancellation_token)
     }
 
     /// Returns the first document within the collection that matches the filter, or None.
@@ -208,13 +313,15 @@ impl Collection {
 
     // Helper method for all findAndModify commands.
     fn find_and_modify(&self, cmd: &mut bson::Document,
-                       filter: bson::Document, _max_time_ms: Option<i64>,
+                       filter: bson::Document, max_time_ms: Option<i64>,
+                       timeout_ms: Option<i64>,
                        projection: Option<bson::Document>,
                        sort: Option<bson::Document>,
                        write_concern: Option<WriteConcern>, cmd_type: CommandType)
                        -> Result<Option<bson::Document>> {
 
         let wc = write_concern.unwrap_or(self.write_concern.clone());
+        try!(wc.validate());
 
         let mut new_cmd = bson::Document::new();
         new_cmd.insert("findAndModify".to_owned(), Bson::String(self.name()));
@@ -226,12 +333,15 @@ impl Collection {
         if projection.is_some() {
             new_cmd.insert("fields".to_owned(), Bson::Document(projection.unwrap()));
         }
+        if let Some(max_time_ms) = max_time_ms {
+            new_cmd.insert("maxTimeMS".to_owned(), Bson::I64(max_time_ms));
+        }
 
         for (key, val) in cmd.iter() {
             new_cmd.insert(key.to_owned(), val.to_owned());
         }
 
-        let res = try!(self.db.command(new_cmd, cmd_type, None));
+        let res = try!(self.command(new_cmd, cmd_type, None, timeout_ms));
         try!(WriteException::validate_write_result(res.clone(), wc));
         let doc = match res.get("value") {
             Some(&Bson::Document(ref nested_doc)) => Some(nested_doc.to_owned()),
@@ -244,6 +354,7 @@ impl Collection {
     // Helper method for validated replace and update commands.
     fn find_one_and_replace_or_update(&self, filter: bson::Document, update: bson::Document,
                                       after: bool, max_time_ms: Option<i64>,
+                                      timeout_ms: Option<i64>,
                                       projection: Option<bson::Document>,
                                       sort: Option<bson::Document>, upsert: bool, write_concern:
                                       Option<WriteConcern>, cmd_type: CommandType) -> Result<Option<bson::Document>> {
@@ -257,8 +368,8 @@ impl Collection {
             cmd.insert("upsert".to_owned(), Bson::Boolean(true));
         }
 
-        self.find_and_modify(&mut cmd, filter, max_time_ms, projection, sort, write_concern,
-                             cmd_type)
+        self.find_and_modify(&mut cmd, filter, max_time_ms, timeout_ms, projection, sort,
+                             write_concern, cmd_type)
     }
 
     /// Finds a single document and deletes it, returning the original.
@@ -268,7 +379,7 @@ impl Collection {
         let opts = options.unwrap_or(FindOneAndDeleteOptions::new());
         let mut cmd = bson::Document::new();
         cmd.insert("remove".to_owned(), Bson::Boolean(true));
-        self.find_and_modify(&mut cmd, filter, opts.max_time_ms,
+        self.find_and_modify(&mut cmd, filter, opts.max_time_ms, opts.timeout_ms,
                              opts.projection, opts.sort, opts.write_concern,
                              CommandType::FindOneAndDelete)
     }
@@ -280,7 +391,7 @@ impl Collection {
         let opts = options.unwrap_or(FindOneAndUpdateOptions::new());
         try!(Collection::validate_replace(&replacement));
         self.find_one_and_replace_or_update(filter, replacement, opts.return_document.to_bool(),
-                                            opts.max_time_ms, opts.projection, opts.sort,
+                                            opts.max_time_ms, opts.timeout_ms, opts.projection, opts.sort,
                                             opts.upsert, opts.write_concern,
                                              CommandType::FindOneAndReplace)
     }
@@ -292,7 +403,7 @@ impl Collection {
         let opts = options.unwrap_or(FindOneAndUpdateOptions::new());
         try!(Collection::validate_update(&update));
         self.find_one_and_replace_or_update(filter, update, opts.return_document.to_bool(),
-                                            opts.max_time_ms, opts.projection, opts.sort,
+                                            opts.max_time_ms, opts.timeout_ms, opts.projection, opts.sort,
                                             opts.upsert, opts.write_concern,
                                             CommandType::FindOneAndUpdate)
     }
@@ -353,14 +464,14 @@ impl Collection {
     }
 
     fn execute_insert_batch(&self, documents: Vec<bson::Document>,
-                            start_index: i64, ordered: bool,
+                            start_index: i64, ordered: bool, write_concern: &WriteConcern,
                             result: &mut BulkWriteResult,
                             exception: &mut BulkWriteException) -> bool {
         let models = documents.iter().map(|doc| {
             WriteModel::InsertOne { document: doc.clone() }
         }).collect();
 
-        let options = Some(InsertManyOptions::new(ordered, None));
+        let options = Some(InsertManyOptions::new(ordered, Some(write_concern.clone())));
 
         match self.insert_many(documents, options) {
             Ok(insert_result) =>
@@ -374,7 +485,7 @@ impl Collection {
     }
 
     fn execute_delete_batch(&self, models: Vec<DeleteModel>, ordered: bool,
-                            result: &mut BulkWriteResult,
+                            write_concern: &WriteConcern, result: &mut BulkWriteResult,
                             exception: &mut BulkWriteException) -> bool {
         let original_models = models.iter().map(|model| {
             if model.multi {
@@ -384,7 +495,8 @@ impl Collection {
             }
         }).collect();
 
-        match self.bulk_delete(models, ordered, None, CommandType::DeleteMany) {
+        match self.bulk_delete(models, ordered, Some(write_concern.clone()), None,
+                               CommandType::DeleteMany) {
             Ok(bulk_delete_result) =>
                 result.process_bulk_delete_result(bulk_delete_result,
                                                   original_models, exception),
@@ -396,7 +508,8 @@ impl Collection {
     }
 
     fn execute_update_batch(&self, models: Vec<UpdateModel>, start_index: i64,
-                            ordered: bool, result: &mut BulkWriteResult,
+                            ordered: bool, write_concern: &WriteConcern,
+                            result: &mut BulkWriteResult,
                             exception: &mut BulkWriteException) -> bool{
         let original_models = models.iter().map(|model| {
             if model.multi {
@@ -406,7 +519,8 @@ impl Collection {
             }
         }).collect();
 
-        match self.bulk_update(models, ordered, None, CommandType::UpdateMany) {
+        match self.bulk_update(models, ordered, Some(write_concern.clone()), None,
+                               CommandType::UpdateMany) {
             Ok(bulk_update_result) =>
                 result.process_bulk_update_result(bulk_update_result,
                                                   original_models, start_index,
@@ -419,24 +533,27 @@ impl Collection {
     }
 
     fn execute_batch(&self, batch: Batch, start_index: i64, ordered: bool,
-                     result: &mut BulkWriteResult,
+                     write_concern: &WriteConcern, result: &mut BulkWriteResult,
                      exception: &mut BulkWriteException) -> bool {
         match batch {
             Batch::Insert(docs) =>
-                self.execute_insert_batch(docs, start_index, ordered, result,
+                self.execute_insert_batch(docs, start_index, ordered, write_concern, result,
                                           exception),
             Batch::Delete(models) =>
-                self.execute_delete_batch(models, ordered, result,
+                self.execute_delete_batch(models, ordered, write_concern, result,
                                           exception),
             Batch::Update(models) =>
-                self.execute_update_batch(models, start_index, ordered, result,
+                self.execute_update_batch(models, start_index, ordered, write_concern, result,
                                           exception),
         }
     }
 
-    /// Sends a batch of writes to the server at the same time.
-    pub fn bulk_write(&self, requests: Vec<WriteModel>,
-                      ordered: bool) -> BulkWriteResult {
+    /// Sends a batch of writes to the server at the same time, using the collection's
+    /// write concern unless another is provided.
+    pub fn bulk_write(&self, requests: Vec<WriteModel>, ordered: bool,
+                      write_concern: Option<WriteConcern>) -> BulkWriteResult {
+        let wc = write_concern.unwrap_or(self.write_concern.clone());
+
         let batches = if ordered {
             Collection::get_ordered_batches(VecDeque::from_iter(requests.into_iter()))
         } else {
@@ -444,13 +561,14 @@ impl Collection {
         };
 
         let mut result = BulkWriteResult::new();
+        result.acknowledged = wc.is_acknowledged();
         let mut exception = BulkWriteException::new(vec![], vec![], vec![], None);
 
         let mut start_index = 0;
 
         for batch in batches {
             let length = batch.len();
-            let success = self.execute_batch(batch, start_index, ordered,
+            let success = self.execute_batch(batch, start_index, ordered, &wc,
                                              &mut result, &mut exception);
 
             if !success && ordered {
@@ -467,12 +585,127 @@ impl Collection {
         result
     }
 
+    // Sends legacy write messages to the server without waiting for a reply. The messages
+    // are built once a server is selected, so that they can respect its size limits. Command
+    // monitoring events are emitted for the equivalent write command, under the request id
+    // of the first message.
+    fn write_unacknowledged<F>(&self, cmd: bson::Document, cmd_type: CommandType,
+                               timeout_ms: Option<i64>, build_messages: F) -> Result<()>
+        where F: FnOnce(&ServerDescription) -> Result<Vec<Message>> {
+
+        let deadline = self.deadline(timeout_ms);
+        let stream = try!(self.db.client.acquire_write_stream(&deadline));
+        let description = try!(self.db.client.topology.server_description(stream.host()));
+        let messages = try!(build_messages(&description));
+
+        let req_id = match messages.first() {
+            Some(message) => message.request_id(),
+            None => return Ok(()),
+        };
+
+        let mut socket = stream.get_socket();
+        let cmd_name = cmd_type.to_str();
+        let connstring = format!("{}", try!(socket.peer_addr()));
+
+        let hook_result = self.db.client.run_start_hooks(&CommandStarted {
+            command: cmd,
+            database_name: self.db.name.to_owned(),
+            command_name: cmd_name.to_owned(),
+            request_id: req_id as i64,
+            connection_string: connstring.clone(),
+        });
+
+        if let Err(_) = hook_result {
+            return Err(EventListenerError(None));
+        }
+
+        let init_time = time::precise_time_ns();
+
+        for message in messages {
+            if let Err(err) = message.write(&mut socket).map_err(|e| deadline.map_err(e)) {
+                let hook_result = self.db.client.run_completion_hooks(&CommandResult::Failure {
+                    duration: time::precise_time_ns() - init_time,
+                    command_name: cmd_name.to_owned(),
+                    failure: &err,
+                    request_id: req_id as i64,
+                    connection_string: connstring.clone(),
+                });
+
+                if let Err(_) = hook_result {
+                    return Err(EventListenerError(Some(Box::new(err))));
+                }
+
+                return Err(err);
+            }
+        }
+
+        let fin_time = time::precise_time_ns();
+
+        let hook_result = self.db.client.run_completion_hooks(&CommandResult::Success {
+            duration: fin_time - init_time,
+            reply: doc! { "ok" => 1 },
+            command_name: cmd_name.to_owned(),
+            request_id: req_id as i64,
+            connection_string: connstring,
+        });
+
+        match hook_result {
+            Ok(()) => Ok(()),
+            Err(_) => Err(EventListenerError(None)),
+        }
+    }
+
+    // Splits documents into legacy insert messages that fit within the server's message size
+    // and write batch limits.
+    fn insert_messages(&self, docs: Vec<bson::Document>, flags: OpInsertFlags,
+                       description: &ServerDescription) -> Result<Vec<Message>> {
+        // The header, flags and null-terminated namespace precede the documents.
+        let overhead = 16 + 4 + self.namespace.len() as i64 + 1;
+
+        let mut messages = Vec::new();
+        let mut batch = Vec::new();
+        let mut batch_length = overhead;
+
+        for doc in docs {
+            let mut buffer = Vec::new();
+            try!(bson::encode_document(&mut buffer, &doc));
+            let length = buffer.len() as i64;
+
+            if length > description.max_bson_object_size {
+                return Err(ArgumentError(format!(
+                    "Document of {} bytes exceeds the server's maxBsonObjectSize of {} bytes.",
+                    length, description.max_bson_object_size)));
+            }
+
+            let full = batch_length + length > description.max_message_size_bytes ||
+                       batch.len() as i64 >= description.max_write_batch_size;
+
+            if full && !batch.is_empty() {
+                let docs = mem::replace(&mut batch, Vec::new());
+                messages.push(try!(Message::new_insert(self.get_req_id(), flags,
+                                                       self.namespace.to_owned(), docs)));
+                batch_length = overhead;
+            }
+
+            batch_length += length;
+            batch.push(doc);
+        }
+
+        if !batch.is_empty() {
+            messages.push(try!(Message::new_insert(self.get_req_id(), flags,
+                                                   self.namespace.to_owned(), batch)));
+        }
+
+        Ok(messages)
+    }
+
     // Internal insertion helper function. Returns a vec of collected ids and a possible exception.
     fn insert(&self, docs: Vec<bson::Document>, ordered: bool,
-              write_concern: Option<WriteConcern>,
+              write_concern: Option<WriteConcern>, timeout_ms: Option<i64>,
               cmd_type: CommandType) -> Result<(Vec<Bson>, Option<BulkWriteException>)> {
 
         let wc =  write_concern.unwrap_or(self.write_concern.clone());
+        try!(wc.validate());
 
         let mut converted_docs = Vec::new();
         let mut ids = Vec::new();
@@ -487,16 +720,26 @@ impl Collection {
                     ids.push(id);
                 }
             }
-            converted_docs.push(Bson::Document(cdoc));
+            converted_docs.push(cdoc);
         }
 
         let mut cmd = bson::Document::new();
         cmd.insert("insert".to_owned(), Bson::String(self.name()));
-        cmd.insert("documents".to_owned(), Bson::Array(converted_docs));
+        cmd.insert("documents".to_owned(),
+                   Bson::Array(converted_docs.iter().map(|doc| Bson::Document(doc.clone())).collect()));
         cmd.insert("ordered".to_owned(), Bson::Boolean(ordered));
         cmd.insert("writeConcern".to_owned(), Bson::Document(wc.to_bson()));
 
-        let result = try!(self.db.command(cmd, cmd_type, None));
+        // Unacknowledged writes are sent as legacy inserts with no reply.
+        if !wc.is_acknowledged() {
+            let flags = OpInsertFlags { continue_on_error: !ordered };
+            try!(self.write_unacknowledged(cmd, cmd_type, timeout_ms, |description| {
+                self.insert_messages(converted_docs, flags, description)
+            }));
+            return Ok((ids, None));
+        }
+
+        let result = try!(self.command(cmd, cmd_type, None, timeout_ms));
 
         // Intercept bulk write exceptions and insert into the result
         let exception_res = BulkWriteException::validate_bulk_write_result(result.clone(), wc);
@@ -510,16 +753,21 @@ impl Collection {
     }
 
     /// Inserts the provided document. If the document is missing an identifier,
-    /// the driver should generate one.
+    /// the driver should generate one. Use `with_timeout` to bound a single insert.
     pub fn insert_one(&self, doc: bson::Document,
                       write_concern: Option<WriteConcern>) -> Result<InsertOneResult> {
-        let (ids, bulk_exception) = try!(self.insert(vec!(doc), true, write_concern.clone(),
+        let wc = write_concern.unwrap_or(self.write_concern.clone());
+        let (ids, bulk_exception) = try!(self.insert(vec!(doc), true, Some(wc.clone()), None,
                                                      CommandType::InsertOne));
 
         if ids.is_empty() {
             return Err(OperationError("No ids returned for insert_one.".to_owned()));
         }
 
+        if !wc.is_acknowledged() {
+            return Ok(InsertOneResult::unacknowledged(Some(ids[0].to_owned())));
+        }
+
         // Downgrade bulk exception, if it exists.
         let exception = match bulk_exception {
             Some(e) => Some(WriteException::with_bulk_exception(e)),
@@ -541,14 +789,19 @@ impl Collection {
     /// the driver should generate them.
     pub fn insert_many(&self, docs: Vec<bson::Document>, options: Option<InsertManyOptions>) -> Result<InsertManyResult> {
         let options = options.unwrap_or(InsertManyOptions::new(false, None));
-        let (ids, exception) = try!(self.insert(docs, options.ordered, options.write_concern,
-                                                CommandType::InsertMany));
+        let wc = options.write_concern.unwrap_or(self.write_concern.clone());
+        let (ids, exception) = try!(self.insert(docs, options.ordered, Some(wc.clone()),
+                                                options.timeout_ms, CommandType::InsertMany));
 
         let mut map = BTreeMap::new();
         for i in 0..ids.len() {
             map.insert(i as i64, ids.get(i).unwrap().to_owned());
         }
 
+        if !wc.is_acknowledged() {
+            return Ok(InsertManyResult::unacknowledged(Some(map)));
+        }
+
         if let Some(ref exc) = exception {
             for error in &exc.write_errors {
                 map.remove(&(error.index as i64));
@@ -560,13 +813,21 @@ impl Collection {
 
     // Sends a batch of delete ops to the server at once.
     fn bulk_delete(&self, models: Vec<DeleteModel>, ordered: bool,
-                   write_concern: Option<WriteConcern>,
+                   write_concern: Option<WriteConcern>, timeout_ms: Option<i64>,
                    cmd_type: CommandType) -> Result<BulkDeleteResult> {
 
         let wc = write_concern.unwrap_or(self.write_concern.clone());
+        try!(wc.validate());
 
         let mut deletes = Vec::new();
+        let mut messages = Vec::new();
         for model in models {
+            if !wc.is_acknowledged() {
+                let flags = OpDeleteFlags { single_remove: !model.multi };
+                messages.push(try!(Message::new_delete(self.get_req_id(), self.namespace.to_owned(),
+                                                       flags, model.filter.clone())));
+            }
+
             let mut delete = bson::Document::new();
             delete.insert("q".to_owned(), Bson::Document(model.filter));
             let limit = if model.multi { 0 } else { 1 };
@@ -582,7 +843,13 @@ impl Collection {
         }
         cmd.insert("writeConcern".to_owned(), Bson::Document(wc.to_bson()));
 
-        let result = try!(self.db.command(cmd, cmd_type, None));
+        // Unacknowledged writes are sent as legacy deletes with no reply.
+        if !wc.is_acknowledged() {
+            try!(self.write_unacknowledged(cmd, cmd_type, timeout_ms, |_| Ok(messages)));
+            return Ok(BulkDeleteResult::unacknowledged());
+        }
+
+        let result = try!(self.command(cmd, cmd_type, None, timeout_ms));
 
         // Intercept write exceptions and insert into the result
         let exception_res = BulkWriteException::validate_bulk_write_result(result.clone(), wc);
@@ -605,18 +872,18 @@ impl Collection {
         };
 
         let result = try!(self.bulk_delete(vec![DeleteModel::new(filter, multi)],
-                                           true, write_concern, cmd_type));
+                                           true, write_concern, None, cmd_type));
 
         Ok(DeleteResult::with_bulk_result(result))
     }
 
-    /// Deletes a single document.
+    /// Deletes a single document. Use `with_timeout` to bound a single delete.
     pub fn delete_one(&self, filter: bson::Document,
                       write_concern: Option<WriteConcern>) -> Result<DeleteResult> {
         self.delete(filter, false, write_concern)
     }
 
-    /// Deletes multiple documents.
+    /// Deletes multiple documents. Use `with_timeout` to bound a single delete.
     pub fn delete_many(&self, filter: bson::Document,
                        write_concern: Option<WriteConcern>) -> Result<DeleteResult> {
         self.delete(filter, true, write_concern)
@@ -624,12 +891,21 @@ impl Collection {
 
     // Sends a batch of replace and update ops to the server at once.
     fn bulk_update(&self, models: Vec<UpdateModel>, ordered: bool,
-                   write_concern: Option<WriteConcern>,
+                   write_concern: Option<WriteConcern>, timeout_ms: Option<i64>,
                    cmd_type: CommandType) -> Result<BulkUpdateResult> {
         let wc = write_concern.unwrap_or(self.write_concern.clone());
+        try!(wc.validate());
 
         let mut updates = Vec::new();
+        let mut messages = Vec::new();
         for model in models {
+            if !wc.is_acknowledged() {
+                let flags = OpUpdateFlags { upsert: model.upsert, multi_update: model.multi };
+                messages.push(try!(Message::new_update(self.get_req_id(), self.namespace.to_owned(),
+                                                       flags, model.filter.clone(),
+                                                       model.update.clone())));
+            }
+
             let mut update = bson::Document::new();
             update.insert("q".to_owned(), Bson::Document(model.filter));
             update.insert("u".to_owned(), Bson::Document(model.update));
@@ -648,7 +924,13 @@ impl Collection {
         cmd.insert("updates".to_owned(), Bson::Array(updates));
         cmd.insert("writeConcern".to_owned(), Bson::Document(wc.to_bson()));
 
-        let result = try!(self.db.command(cmd, cmd_type, None));
+        // Unacknowledged writes are sent as legacy updates with no reply.
+        if !wc.is_acknowledged() {
+            try!(self.write_unacknowledged(cmd, cmd_type, timeout_ms, |_| Ok(messages)));
+            return Ok(BulkUpdateResult::unacknowledged());
+        }
+
+        let result = try!(self.command(cmd, cmd_type, None, timeout_ms));
 
         // Intercept write exceptions and insert into the result
         let exception_res = BulkWriteException::validate_bulk_write_result(result.clone(), wc);
@@ -663,8 +945,7 @@ impl Collection {
 
     // Internal update helper function.
     fn update(&self, filter: bson::Document, update: bson::Document,
-              upsert: bool, multi: bool,
-              write_concern: Option<WriteConcern>) -> Result<UpdateResult> {
+              multi: bool, options: UpdateOptions) -> Result<UpdateResult> {
 
         let cmd_type = if multi {
             CommandType::UpdateMany
@@ -672,8 +953,9 @@ impl Collection {
             CommandType::UpdateOne
         };
 
-        let result = try!(self.bulk_update(vec![UpdateModel::new(filter, update, upsert, multi)],
-                                           true, write_concern, cmd_type));
+        let model = UpdateModel::new(filter, update, options.upsert, multi);
+        let result = try!(self.bulk_update(vec![model], true, options.write_concern,
+                                           options.timeout_ms, cmd_type));
 
         Ok(UpdateResult::with_bulk_result(result))
     }
@@ -683,21 +965,21 @@ impl Collection {
                        options: Option<ReplaceOptions>) -> Result<UpdateResult> {
         let options = options.unwrap_or(ReplaceOptions::new(false, None));
         let _ = try!(Collection::validate_replace(&replacement));
-        self.update(filter, replacement, options.upsert, false, options.write_concern)
+        self.update(filter, replacement, false, options)
     }
 
     /// Updates a single document.
     pub fn update_one(&self, filter: bson::Document, update: bson::Document, options: Option<UpdateOptions>) -> Result<UpdateResult> {
         let options = options.unwrap_or(UpdateOptions::new(false, None));
         let _ = try!(Collection::validate_update(&update));
-        self.update(filter, update, options.upsert, false, options.write_concern)
+        self.update(filter, update, false, options)
     }
 
     /// Updates multiple documents.
     pub fn update_many(&self, filter: bson::Document, update: bson::Document, options: Option<UpdateOptions>) -> Result<UpdateResult> {
         let options = options.unwrap_or(UpdateOptions::new(false, None));
         let _ = try!(Collection::validate_update(&update));
-        self.update(filter, update, options.upsert, true, options.write_concern)
+        self.update(filter, update, true, options)
     }
 
     fn validate_replace(replacement: &bson::Document) -> Result<()> {
@@ -743,7 +1025,7 @@ impl Collection {
         let mut cmd = bson::Document::new();
         cmd.insert("createIndexes".to_owned(), Bson::String(self.name()));
         cmd.insert("indexes".to_owned(), Bson::Array(indexes));
-        let result = try!(self.db.command(cmd, CommandType::CreateIndexes, None));
+        let result = try!(self.command(cmd, CommandType::CreateIndexes, None, None));
 
         match result.get("errmsg") {
             Some(&Bson::String(ref msg)) => return Err(OperationError(msg.to_owned())),
@@ -772,7 +1054,7 @@ impl Collection {
         cmd.insert("dropIndexes".to_owned(), Bson::String(self.name()));
         cmd.insert("index".to_owned(), Bson::String(try!(model.name())));
 
-        let result = try!(self.db.command(cmd, CommandType::DropIndexes, None));
+        let result = try!(self.command(cmd, CommandType::DropIndexes, None, None));
         match result.get("errmsg") {
             Some(&Bson::String(ref msg)) => return Err(OperationError(msg.to_owned())),
             _ => Ok(()),
@@ -792,6 +1074,7 @@ impl Collection {
     pub fn list_indexes(&self) -> Result<Cursor> {
         let mut cmd = bson::Document::new();
         cmd.insert("listIndexes".to_owned(), Bson::String(self.name()));
-        self.db.command_cursor(cmd, CommandType::ListIndexes, self.read_preference.to_owned())
+        self.command_cursor(cmd, CommandType::ListIndexes, self.read_preference.to_owned(),
+                            None, None)
     }
 }
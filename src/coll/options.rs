@@ -1,7 +1,9 @@
 //! Options for collection-level operations.
 use bson::{self, Bson};
+use cancel::CancellationToken;
 use cursor;
 use common::{ReadPreference, WriteConcern};
+use db::options::{ValidationAction, ValidationLevel};
 use Error::ArgumentError;
 use Result;
 
@@ -56,7 +58,26 @@ pub struct AggregateOptions {
     pub use_cursor: bool,
     pub batch_size: i32,
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub read_preference: Option<ReadPreference>,
+    pub cancellation_token: Option<CancellationToken>,
+}
+
+/// Identifies an existing index by name or key pattern.
+#[derive(Clone)]
+pub enum IndexSelector {
+    Name(String),
+    Keys(bson::Document),
+}
+
+/// Options for modifying a collection with collMod.
+#[derive(Clone)]
+pub struct CollModOptions {
+    pub validator: Option<bson::Document>,
+    pub validation_level: Option<ValidationLevel>,
+    pub validation_action: Option<ValidationAction>,
+    /// An index whose TTL to change, and its new expiration in seconds.
+    pub index_ttl: Option<(IndexSelector, i32)>,
 }
 
 /// Options for count queries.
@@ -67,6 +88,7 @@ pub struct CountOptions {
     pub hint: Option<String>,
     pub hint_doc: Option<bson::Document>,
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub read_preference: Option<ReadPreference>,
 }
 
@@ -74,6 +96,7 @@ pub struct CountOptions {
 #[derive(Clone)]
 pub struct DistinctOptions {
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub read_preference: Option<ReadPreference>,
 }
 
@@ -89,16 +112,19 @@ pub struct FindOptions {
     pub batch_size: i32,
     pub comment: Option<String>,
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub modifiers: Option<bson::Document>,
     pub projection: Option<bson::Document>,
     pub sort: Option<bson::Document>,
     pub read_preference: Option<ReadPreference>,
+    pub cancellation_token: Option<CancellationToken>,
 }
 
 /// Options for findOneAndDelete operations.
 #[derive(Clone)]
 pub struct FindOneAndDeleteOptions {
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub projection: Option<bson::Document>,
     pub sort: Option<bson::Document>,
     pub write_concern: Option<WriteConcern>,
@@ -109,6 +135,7 @@ pub struct FindOneAndDeleteOptions {
 pub struct FindOneAndUpdateOptions {
     pub return_document: ReturnDocument,
     pub max_time_ms: Option<i64>,
+    pub timeout_ms: Option<i64>,
     pub projection: Option<bson::Document>,
     pub sort: Option<bson::Document>,
     pub upsert: bool,
@@ -152,6 +179,7 @@ pub struct IndexModel {
 pub struct InsertManyOptions {
     pub ordered: bool,
     pub write_concern: Option<WriteConcern>,
+    pub timeout_ms: Option<i64>,
 }
 
 /// Options for update operations.
@@ -159,6 +187,7 @@ pub struct InsertManyOptions {
 pub struct UpdateOptions {
     pub upsert: bool,
     pub write_concern: Option<WriteConcern>,
+    pub timeout_ms: Option<i64>,
 }
 
 pub type ReplaceOptions = UpdateOptions;
@@ -170,8 +199,79 @@ impl AggregateOptions {
             use_cursor: true,
             batch_size: cursor::DEFAULT_BATCH_SIZE,
             max_time_ms: None,
+            timeout_ms: None,
             read_preference: None,
+            cancellation_token: None,
+        }
+    }
+}
+
+impl CollModOptions {
+    /// Creates a new CollModOptions struct that changes nothing.
+    pub fn new() -> CollModOptions {
+        CollModOptions {
+            validator: None,
+            validation_level: None,
+            validation_action: None,
+            index_ttl: None,
+        }
+    }
+
+    /// Clone the current options struct with a new validator.
+    pub fn with_validator(&self, validator: bson::Document) -> CollModOptions {
+        let mut new_opts = self.clone();
+        new_opts.validator = Some(validator);
+        new_opts
+    }
+
+    /// Clone the current options struct with a new validation level.
+    pub fn with_validation_level(&self, level: ValidationLevel) -> CollModOptions {
+        let mut new_opts = self.clone();
+        new_opts.validation_level = Some(level);
+        new_opts
+    }
+
+    /// Clone the current options struct with a new validation action.
+    pub fn with_validation_action(&self, action: ValidationAction) -> CollModOptions {
+        let mut new_opts = self.clone();
+        new_opts.validation_action = Some(action);
+        new_opts
+    }
+
+    /// Clone the current options struct with a new TTL for an existing TTL index.
+    pub fn with_index_ttl(&self, index: IndexSelector, expire_after_seconds: i32) -> CollModOptions {
+        let mut new_opts = self.clone();
+        new_opts.index_ttl = Some((index, expire_after_seconds));
+        new_opts
+    }
+
+    /// Creates the collMod command for a collection.
+    pub fn to_command(&self, coll_name: &str) -> bson::Document {
+        let mut doc = doc! { "collMod" => coll_name };
+
+        if let Some(ref validator) = self.validator {
+            doc.insert("validator".to_owned(), Bson::Document(validator.clone()));
+        }
+
+        if let Some(level) = self.validation_level {
+            doc.insert("validationLevel".to_owned(), Bson::String(level.as_str().to_owned()));
+        }
+
+        if let Some(action) = self.validation_action {
+            doc.insert("validationAction".to_owned(), Bson::String(action.as_str().to_owned()));
         }
+
+        if let Some((ref index, ref seconds)) = self.index_ttl {
+            let mut index_doc = match index {
+                &IndexSelector::Name(ref name) => doc! { "name" => (Bson::String(name.to_owned())) },
+                &IndexSelector::Keys(ref keys) => doc! { "keyPattern" => (Bson::Document(keys.clone())) },
+            };
+
+            index_doc.insert("expireAfterSeconds".to_owned(), Bson::I32(*seconds));
+            doc.insert("index".to_owned(), Bson::Document(index_doc));
+        }
+
+        doc
     }
 }
 
@@ -183,6 +283,7 @@ impl CountOptions {
             hint: None,
             hint_doc: None,
             max_time_ms: None,
+            timeout_ms: None,
             read_preference: None,
         }
     }
@@ -192,6 +293,7 @@ impl DistinctOptions {
     pub fn new() -> DistinctOptions {
         DistinctOptions {
             max_time_ms: None,
+            timeout_ms: None,
             read_preference: None,
         }
     }
@@ -210,10 +312,12 @@ impl FindOptions {
             batch_size: cursor::DEFAULT_BATCH_SIZE,
             comment: None,
             max_time_ms: None,
+            timeout_ms: None,
             modifiers: None,
             projection: None,
             sort: None,
             read_preference: None,
+            cancellation_token: None,
         }
     }
 
@@ -229,6 +333,7 @@ impl FindOneAndDeleteOptions {
     pub fn new() -> FindOneAndDeleteOptions {
         FindOneAndDeleteOptions {
             max_time_ms: None,
+            timeout_ms: None,
             projection: None,
             sort: None,
             write_concern: None,
@@ -241,6 +346,7 @@ impl FindOneAndUpdateOptions {
         FindOneAndUpdateOptions {
             return_document: ReturnDocument::Before,
             max_time_ms: None,
+            timeout_ms: None,
             projection: None,
             sort: None,
             upsert: false,
@@ -370,7 +476,7 @@ impl IndexModel {
 
 impl InsertManyOptions {
     pub fn new(ordered: bool, write_concern: Option<WriteConcern>) -> InsertManyOptions {
-        InsertManyOptions { ordered: ordered, write_concern: write_concern }
+        InsertManyOptions { ordered: ordered, write_concern: write_concern, timeout_ms: None }
     }
 }
 
@@ -385,6 +491,6 @@ impl ReturnDocument {
 
 impl UpdateOptions {
     pub fn new(upsert: bool, write_concern: Option<WriteConcern>) -> UpdateOptions {
-        UpdateOptions { upsert: upsert, write_concern: write_concern }
+        UpdateOptions { upsert: upsert, write_concern: write_concern, timeout_ms: None }
     }
 }
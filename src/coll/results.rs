@@ -186,6 +186,15 @@ impl BulkDeleteResult {
             write_exception: exception,
         }
     }
+
+    /// Returns a result for a delete that was sent without waiting for a server reply.
+    pub fn unacknowledged() -> BulkDeleteResult {
+        BulkDeleteResult {
+            acknowledged: false,
+            deleted_count: 0,
+            write_exception: None,
+        }
+    }
 }
 
 impl BulkUpdateResult {
@@ -216,6 +225,17 @@ impl BulkUpdateResult {
             write_exception: exception,
         }
     }
+
+    /// Returns a result for an update that was sent without waiting for a server reply.
+    pub fn unacknowledged() -> BulkUpdateResult {
+        BulkUpdateResult {
+            acknowledged: false,
+            matched_count: 0,
+            modified_count: 0,
+            upserted_ids: None,
+            write_exception: None,
+        }
+    }
 }
 
 impl InsertOneResult {
@@ -227,6 +247,15 @@ impl InsertOneResult {
             write_exception: exception,
         }
     }
+
+    /// Returns a result for an insert that was sent without waiting for a server reply.
+    pub fn unacknowledged(inserted_id: Option<Bson>) -> InsertOneResult {
+        InsertOneResult {
+            acknowledged: false,
+            inserted_id: inserted_id,
+            write_exception: None,
+        }
+    }
 }
 
 impl InsertManyResult {
@@ -238,6 +267,15 @@ impl InsertManyResult {
             bulk_write_exception: exception,
         }
     }
+
+    /// Returns a result for an insert that was sent without waiting for a server reply.
+    pub fn unacknowledged(inserted_ids: Option<BTreeMap<i64, Bson>>) -> InsertManyResult {
+        InsertManyResult {
+            acknowledged: false,
+            inserted_ids: inserted_ids,
+            bulk_write_exception: None,
+        }
+    }
 }
 
 impl DeleteResult {
@@ -4,10 +4,16 @@
 #[derive(PartialEq, Eq, Clone)]
 pub enum CommandType {
     Aggregate,
+    CollMod,
+    CollStats,
+    ConvertToCapped,
     Count,
     CreateCollection,
     CreateIndexes,
+    CreateRole,
     CreateUser,
+    CurrentOp,
+    DbStats,
     DeleteMany,
     DeleteOne,
     Distinct,
@@ -15,32 +21,50 @@ pub enum CommandType {
     DropCollection,
     DropDatabase,
     DropIndexes,
+    DropRole,
     DropUser,
     Find,
     FindOneAndDelete,
     FindOneAndReplace,
     FindOneAndUpdate,
+    GetRoles,
     GetUser,
     GetUsers,
+    GrantPrivilegesToRole,
+    GrantRolesToUser,
     InsertMany,
     InsertOne,
     IsMaster,
+    KillOp,
     ListCollections,
     ListDatabases,
     ListIndexes,
+    Logout,
+    RenameCollection,
+    RevokePrivilegesFromRole,
+    RevokeRolesFromUser,
+    ServerStatus,
     Suppressed,
     UpdateMany,
     UpdateOne,
+    UpdateRole,
+    UpdateUser,
 }
 
 impl CommandType {
     pub fn to_str(&self) -> &str {
         match self {
             &CommandType::Aggregate => "aggregate",
+            &CommandType::CollMod => "coll_mod",
+            &CommandType::CollStats => "coll_stats",
+            &CommandType::ConvertToCapped => "convert_to_capped",
             &CommandType::Count => "count",
             &CommandType::CreateCollection => "create_collection",
             &CommandType::CreateIndexes => "create_indexes",
+            &CommandType::CreateRole => "create_role",
             &CommandType::CreateUser => "create_user",
+            &CommandType::CurrentOp => "current_op",
+            &CommandType::DbStats => "db_stats",
             &CommandType::DeleteMany => "delete_many",
             &CommandType::DeleteOne => "delete_one",
             &CommandType::Distinct => "distinct",
@@ -48,32 +72,50 @@ impl CommandType {
             &CommandType::DropCollection => "drop_collection",
             &CommandType::DropDatabase => "drop_database",
             &CommandType::DropIndexes => "drop_indexes",
+            &CommandType::DropRole => "drop_role",
             &CommandType::DropUser => "drop_user",
             &CommandType::Find => "find",
             &CommandType::FindOneAndDelete => "find_one_and_delete",
             &CommandType::FindOneAndReplace => "find_one_and_replace",
             &CommandType::FindOneAndUpdate => "find_one_and_update",
+            &CommandType::GetRoles => "get_roles",
             &CommandType::GetUser => "get_user",
             &CommandType::GetUsers => "get_users",
+            &CommandType::GrantPrivilegesToRole => "grant_privileges_to_role",
+            &CommandType::GrantRolesToUser => "grant_roles_to_user",
             &CommandType::InsertMany => "insert_many",
             &CommandType::InsertOne => "insert_one",
             &CommandType::IsMaster => "is_master",
+            &CommandType::KillOp => "kill_op",
             &CommandType::ListCollections => "list_collections",
             &CommandType::ListDatabases => "list_databases",
             &CommandType::ListIndexes => "list_indexes",
+            &CommandType::Logout => "logout",
+            &CommandType::RenameCollection => "rename_collection",
+            &CommandType::RevokePrivilegesFromRole => "revoke_privileges_from_role",
+            &CommandType::RevokeRolesFromUser => "revoke_roles_from_user",
+            &CommandType::ServerStatus => "server_status",
             &CommandType::Suppressed => "suppressed",
             &CommandType::UpdateMany => "update_many",
             &CommandType::UpdateOne => "update_one",
+            &CommandType::UpdateRole => "update_role",
+            &CommandType::UpdateUser => "update_user",
         }
     }
 
     pub fn is_write_command(&self) -> bool {
         match self {
             &CommandType::Aggregate => false,
+            &CommandType::CollMod => true,
+            &CommandType::CollStats => false,
+            &CommandType::ConvertToCapped => true,
             &CommandType::Count => false,
             &CommandType::CreateCollection => true,
             &CommandType::CreateIndexes => true,
+            &CommandType::CreateRole => true,
             &CommandType::CreateUser => true,
+            &CommandType::CurrentOp => false,
+            &CommandType::DbStats => false,
             &CommandType::DeleteMany => true,
             &CommandType::DeleteOne => true,
             &CommandType::Distinct => false,
@@ -81,22 +123,34 @@ impl CommandType {
             &CommandType::DropCollection => true,
             &CommandType::DropDatabase => true,
             &CommandType::DropIndexes => true,
+            &CommandType::DropRole => true,
             &CommandType::DropUser => true,
             &CommandType::Find => false,
             &CommandType::FindOneAndDelete => true,
             &CommandType::FindOneAndReplace => true,
             &CommandType::FindOneAndUpdate => true,
+            &CommandType::GetRoles => false,
             &CommandType::GetUser => false,
             &CommandType::GetUsers => false,
+            &CommandType::GrantPrivilegesToRole => true,
+            &CommandType::GrantRolesToUser => true,
             &CommandType::InsertMany => true,
             &CommandType::InsertOne => true,
             &CommandType::IsMaster => false,
+            &CommandType::KillOp => false,
             &CommandType::ListCollections => false,
             &CommandType::ListDatabases => false,
             &CommandType::ListIndexes => false,
+            &CommandType::Logout => false,
+            &CommandType::RenameCollection => true,
+            &CommandType::RevokePrivilegesFromRole => true,
+            &CommandType::RevokeRolesFromUser => true,
+            &CommandType::ServerStatus => false,
             &CommandType::Suppressed => false,
             &CommandType::UpdateMany => true,
-            &CommandType::UpdateOne => true,           
+            &CommandType::UpdateOne => true,
+            &CommandType::UpdateRole => true,
+            &CommandType::UpdateUser => true,
         }
     }
 }
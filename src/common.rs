@@ -1,11 +1,14 @@
 //! Library-wide utilities.
-use Error::{self, ArgumentError};
+use Error::{self, ArgumentError, TimeoutError};
 use Result;
 
 use bson::{self, Bson};
 use std::ascii::AsciiExt;
+use std::cmp;
 use std::collections::BTreeMap;
 use std::str::FromStr;
+use std::time::Duration;
+use time;
 
 /// Indicates how a server should be selected during read operations.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -37,6 +40,9 @@ pub struct ReadPreference {
     pub mode: ReadMode,
     /// Filters servers based on the first tag set that matches at least one server.
     pub tag_sets: Vec<BTreeMap<String, String>>,
+    /// The maximum estimated replication lag, in seconds, of secondaries eligible for reads.
+    /// Must be at least 90 seconds, and cannot be used with ReadMode::Primary.
+    pub max_staleness_seconds: Option<i64>,
 }
 
 impl ReadPreference {
@@ -44,6 +50,7 @@ impl ReadPreference {
         ReadPreference {
             mode: mode,
             tag_sets: tag_sets.unwrap_or(Vec::new()),
+            max_staleness_seconds: None,
         }
     }
 
@@ -58,14 +65,56 @@ impl ReadPreference {
         }).collect();
 
         doc.insert("tag_sets".to_owned(), Bson::Array(bson_tag_sets));
+
+        if let Some(max_staleness) = self.max_staleness_seconds {
+            doc.insert("maxStalenessSeconds".to_owned(), Bson::I64(max_staleness));
+        }
+
         doc
     }
 }
 
+/// Indicates the number or kind of servers that must acknowledge a write operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Acknowledgment {
+    /// Requires acknowledgment from the given number of servers; 0 is unacknowledged.
+    Nodes(i32),
+    /// Requires acknowledgment from a majority of voting replica set members.
+    Majority,
+    /// Requires acknowledgment from members satisfying a custom getLastErrorModes tag set.
+    Tag(String),
+}
+
+impl Acknowledgment {
+    /// Converts the acknowledgment into its BSON representation for the 'w' field.
+    pub fn to_bson(&self) -> Bson {
+        match self {
+            &Acknowledgment::Nodes(n) => Bson::I32(n),
+            &Acknowledgment::Majority => Bson::String("majority".to_owned()),
+            &Acknowledgment::Tag(ref tag) => Bson::String(tag.to_owned()),
+        }
+    }
+}
+
+impl FromStr for Acknowledgment {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(ArgumentError("Write concern 'w' cannot be empty.".to_owned()));
+        }
+
+        Ok(match s.parse::<i32>() {
+            Ok(n) => Acknowledgment::Nodes(n),
+            Err(_) if s == "majority" => Acknowledgment::Majority,
+            Err(_) => Acknowledgment::Tag(s.to_owned()),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct WriteConcern {
     /// Write replication
-    pub w: i32,
+    pub w: Acknowledgment,
     /// Used in conjunction with 'w'. Propagation timeout in ms.
     pub w_timeout: i32,
     /// If true, will block until write operations have been committed to journal.
@@ -77,18 +126,155 @@ pub struct WriteConcern {
 impl WriteConcern {
     pub fn new() -> WriteConcern {
         WriteConcern {
-            w: 1,
+            w: Acknowledgment::Nodes(1),
             w_timeout: 0,
             j: false,
             fsync: false,
         }
     }
 
+    /// Returns a write concern that does not wait for any server acknowledgment.
+    pub fn unacknowledged() -> WriteConcern {
+        WriteConcern { w: Acknowledgment::Nodes(0), .. WriteConcern::new() }
+    }
+
+    /// Returns a write concern requiring acknowledgment from a majority of the replica set.
+    pub fn majority() -> WriteConcern {
+        WriteConcern { w: Acknowledgment::Majority, .. WriteConcern::new() }
+    }
+
+    /// Returns false if writes using this concern should be sent without waiting for a reply.
+    pub fn is_acknowledged(&self) -> bool {
+        self.w != Acknowledgment::Nodes(0)
+    }
+
+    /// Ensures that the write concern options are valid and do not conflict.
+    pub fn validate(&self) -> Result<()> {
+        match self.w {
+            Acknowledgment::Nodes(n) if n < 0 =>
+                return Err(ArgumentError("Write concern 'w' cannot be negative.".to_owned())),
+            Acknowledgment::Tag(ref tag) if tag.is_empty() =>
+                return Err(ArgumentError("Write concern tag cannot be empty.".to_owned())),
+            _ => (),
+        }
+
+        if self.w_timeout < 0 {
+            return Err(ArgumentError("Write concern 'wtimeout' cannot be negative.".to_owned()));
+        }
+
+        if self.j && self.fsync {
+            return Err(ArgumentError("Write concern cannot specify both 'j' and 'fsync'.".to_owned()));
+        }
+
+        if self.w == Acknowledgment::Nodes(0) && (self.j || self.fsync) {
+            return Err(ArgumentError(
+                "An unacknowledged write concern cannot request journaling or fsync.".to_owned()));
+        }
+
+        Ok(())
+    }
+
     pub fn to_bson(&self) -> bson::Document {
         let mut bson = bson::Document::new();
-        bson.insert("w".to_owned(), Bson::I32(self.w));
-        bson.insert("wtimeout".to_owned(), Bson::I32(self.w_timeout));
-        bson.insert("j".to_owned(), Bson::Boolean(self.j));
+        bson.insert("w".to_owned(), self.w.to_bson());
+
+        if self.w_timeout > 0 {
+            bson.insert("wtimeout".to_owned(), Bson::I32(self.w_timeout));
+        }
+
+        if self.j {
+            bson.insert("j".to_owned(), Bson::Boolean(self.j));
+        }
+
+        if self.fsync {
+            bson.insert("fsync".to_owned(), Bson::Boolean(self.fsync));
+        }
+
         bson
     }
 }
+
+/// The point in time by which an entire operation must complete, derived from `timeout_ms`.
+///
+/// The deadline bounds server selection, connecting, and socket reads and writes. Resolving
+/// a host name is not bounded, as the system resolver offers no timeout; a slow DNS lookup
+/// may delay the operation past its deadline, after which it fails with a `TimeoutError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    // The monotonic clock reading, in nanoseconds, at which the operation expires, if bounded.
+    expires_at_ns: Option<u64>,
+}
+
+impl Deadline {
+    /// Creates a deadline that expires `timeout_ms` milliseconds from now.
+    /// A timeout of None or zero, or one too large to represent, places no bound
+    /// on the operation.
+    pub fn new(timeout_ms: Option<i64>) -> Deadline {
+        match timeout_ms {
+            Some(ms) if ms > 0 => {
+                let expires_at_ns = (ms as u64).checked_mul(1000000)
+                    .and_then(|ns| ns.checked_add(time::precise_time_ns()));
+                Deadline { expires_at_ns: expires_at_ns }
+            },
+            _ => Deadline::none(),
+        }
+    }
+
+    /// Returns a deadline that never expires.
+    pub fn none() -> Deadline {
+        Deadline { expires_at_ns: None }
+    }
+
+    /// Returns true if the operation is bounded by a timeout.
+    pub fn is_bounded(&self) -> bool {
+        self.expires_at_ns.is_some()
+    }
+
+    /// Returns the milliseconds remaining before expiration, or None if unbounded.
+    pub fn remaining_ms(&self) -> Option<i64> {
+        self.expires_at_ns.map(|expires| {
+            let now = time::precise_time_ns();
+            if expires > now { ((expires - now) / 1000000) as i64 } else { 0 }
+        })
+    }
+
+    /// Returns true if the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.remaining_ms() == Some(0)
+    }
+
+    /// Returns a TimeoutError if the deadline has passed.
+    pub fn check(&self) -> Result<()> {
+        if self.is_expired() {
+            Err(TimeoutError)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Caps the provided duration by the time remaining.
+    pub fn cap_ms(&self, ms: i64) -> i64 {
+        match self.remaining_ms() {
+            Some(remaining) => cmp::min(ms, remaining),
+            None => ms,
+        }
+    }
+
+    /// Returns the remaining time as a socket timeout, or None if unbounded.
+    pub fn socket_timeout(&self) -> Result<Option<Duration>> {
+        match self.remaining_ms() {
+            Some(0) => Err(TimeoutError),
+            Some(remaining) => Ok(Some(Duration::from_millis(remaining as u64))),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces an error with a TimeoutError if it was caused by the deadline passing.
+    pub fn map_err(&self, err: Error) -> Error {
+        if self.is_expired() {
+            TimeoutError
+        } else {
+            err
+        }
+    }
+}
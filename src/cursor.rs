@@ -25,18 +25,22 @@
 //! ```
 use {Client, CommandType, Error, ErrorCode, Result, ThreadedClient};
 use apm::{CommandStarted, CommandResult, EventRunner};
+use cancel::CancellationToken;
 
 use bson::{self, Bson};
-use common::{ReadMode, ReadPreference};
+use common::{Deadline, ReadMode, ReadPreference};
 use pool::PooledStream;
 use time;
 use wire_protocol::flags::OpQueryFlags;
 use wire_protocol::operations::Message;
 
 use std::collections::vec_deque::VecDeque;
+use std::i64;
 use std::io::{Read, Write};
 
 pub const DEFAULT_BATCH_SIZE: i32 = 20;
+/// The minimum wire version of servers that accept a comment on any command.
+pub const COMMAND_COMMENT_WIRE_VERSION: i64 = 6;
 
 /// Maintains a connection to the server and lazily returns documents from a
 /// query.
@@ -62,6 +66,8 @@ pub struct Cursor {
     buffer: VecDeque<bson::Document>,
     read_preference: ReadPreference,
     cmd_type: CommandType,
+    deadline: Deadline,
+    cancellation: Option<CancellationToken>,
 }
 
 macro_rules! try_or_emit {
@@ -97,15 +103,18 @@ impl Cursor {
     /// `client` - Client making the request.
     /// `db` - Which database the command is being sent to.
     /// `doc` - Specifies the command that is being run.
+    /// `deadline` - The time by which the command and any subsequent getMores must complete.
+    /// `cancellation` - An optional token for cancelling the command from another thread.
     ///
     /// # Return value
     ///
     /// Returns the newly created Cursor on success, or an Error on failure.
     pub fn command_cursor(client: Client, db: &str,
                           doc: bson::Document, cmd_type: CommandType,
-                          read_pref: ReadPreference) -> Result<Cursor> {
+                          read_pref: ReadPreference, deadline: Deadline,
+                          cancellation: Option<CancellationToken>) -> Result<Cursor> {
         Cursor::query(client.clone(), format!("{}.$cmd", db), 1, OpQueryFlags::no_flags(), 0, 0,
-                      doc, None, cmd_type, true, read_pref)
+                      doc, None, cmd_type, true, read_pref, deadline, cancellation)
     }
 
     fn get_bson_and_cid_from_message(message: Message) -> Result<(bson::Document, VecDeque<bson::Document>, i64)> {
@@ -124,6 +133,8 @@ impl Cursor {
                         if *code == ErrorCode::CommandNotFound as i32 ||
                             *code == ErrorCode::NamespaceNotFound as i32 {
                                 return Ok((docs[0].clone(), v, cid));
+                            } else if *code == ErrorCode::ExceededTimeLimit as i32 {
+                                return Err(Error::TimeoutError);
                             } else if let Some(&Bson::String(ref msg)) = docs[0].get("errmsg") {
                                 return Err(Error::OperationError(msg.to_owned()));
                             }
@@ -191,7 +202,9 @@ impl Cursor {
     ///                          be present in the documents to be returned by
     ///                          the query.
     /// `is_cmd_cursor` - Whether or not the Cursor is for a database command.
-    /// `monitor_host` - The host being monitored, if this is a query from a server monitor.
+    /// `read_pref` - The read preference used to select a server.
+    /// `deadline` - The time by which the query and any subsequent getMores must complete.
+    /// `cancellation` - An optional token for cancelling the query from another thread.
     /// # Return value
     ///
     /// Returns the cursor for the query results on success, or an Error on
@@ -199,13 +212,49 @@ impl Cursor {
     pub fn query(client: Client, namespace: String, batch_size: i32, flags: OpQueryFlags,
                  number_to_skip: i32, number_to_return: i32, query: bson::Document,
                  return_field_selector: Option<bson::Document>, cmd_type: CommandType,
-                 is_cmd_cursor: bool, read_pref: ReadPreference) -> Result<Cursor> {
+                 is_cmd_cursor: bool, read_pref: ReadPreference,
+                 deadline: Deadline,
+                 cancellation: Option<CancellationToken>) -> Result<Cursor> {
+
+        try!(deadline.check());
+        if let Some(ref token) = cancellation {
+            try!(token.check());
+        }
 
         // Select a server stream from the topology.
         let (stream, slave_ok, send_read_pref) = if cmd_type.is_write_command() {
-            (try!(client.acquire_write_stream()), false, false)
+            (try!(client.acquire_write_stream(&deadline)), false, false)
+        } else {
+            try!(client.acquire_stream(read_pref.to_owned(), &deadline))
+        };
+
+        // Bound the server-side execution time by the remaining operation budget.
+        // Write commands are excluded, as older servers reject unrecognized fields.
+        let query = if cmd_type.is_write_command() {
+            query
         } else {
-            try!(client.acquire_stream(read_pref.to_owned()))
+            try!(Cursor::with_max_time_ms(query, namespace.ends_with(".$cmd"), &deadline))
+        };
+
+        // Tag the operation so that it can be located and killed on the server.
+        // Servers before MongoDB 3.6 reject a comment on commands such as aggregate,
+        // so commands sent to them can only be cancelled on the client side.
+        let query = match cancellation {
+            Some(ref token) => {
+                let is_command = namespace.ends_with(".$cmd");
+                let max_wire_version = if is_command {
+                    try!(client.topology.server_description(stream.host())).max_wire_version
+                } else {
+                    0
+                };
+
+                if is_command && max_wire_version < COMMAND_COMMENT_WIRE_VERSION {
+                    query
+                } else {
+                    Cursor::with_comment(query, is_command, token.tag())
+                }
+            },
+            None => query,
         };
 
         // Set slave_ok flag based on the result from server selection.
@@ -237,7 +286,76 @@ impl Cursor {
 
         Cursor::query_with_stream(stream, client, namespace, batch_size, new_flags,
                                   number_to_skip, number_to_return, new_query,
-                                  return_field_selector, cmd_type, is_cmd_cursor, Some(read_pref))
+                                  return_field_selector, cmd_type, is_cmd_cursor, Some(read_pref),
+                                  deadline, cancellation)
+    }
+
+    // Attaches a comment to the query or command.
+    fn with_comment(query: bson::Document, is_command: bool, comment: &str) -> bson::Document {
+        if is_command {
+            let mut cmd = query;
+            cmd.insert("comment".to_owned(), Bson::String(comment.to_owned()));
+            return cmd;
+        }
+
+        let mut nq = match query.get("$query") {
+            Some(_) => query.clone(),
+            None => doc! { "$query" => query },
+        };
+
+        nq.insert("$comment".to_owned(), Bson::String(comment.to_owned()));
+        nq
+    }
+
+    // Determines whether a network failure was caused by the operation's cancellation
+    // or deadline. Interrupted streams are discarded rather than returned to the pool,
+    // since they may still hold an unread reply.
+    fn interrupted(err: Error, stream: &PooledStream, deadline: &Deadline,
+                   cancellation: &Option<CancellationToken>) -> Error {
+        if let Some(ref token) = *cancellation {
+            if token.is_cancelled() {
+                stream.discard();
+                return Error::CancelledError;
+            }
+        }
+
+        if deadline.is_expired() {
+            stream.discard();
+            return Error::TimeoutError;
+        }
+
+        err
+    }
+
+    // Adds a server-side time limit to the query or command, derived from the time
+    // remaining before the deadline. A smaller limit set by the caller is kept.
+    fn with_max_time_ms(query: bson::Document, is_command: bool,
+                        deadline: &Deadline) -> Result<bson::Document> {
+        if deadline.remaining_ms() == Some(0) {
+            return Err(Error::TimeoutError);
+        }
+
+        if !deadline.is_bounded() {
+            return Ok(query);
+        }
+
+        let key = if is_command { "maxTimeMS" } else { "$maxTimeMS" };
+
+        let mut nq = if is_command || query.get("$query").is_some() {
+            query
+        } else {
+            doc! { "$query" => query }
+        };
+
+        let max_time_ms = match nq.get(key) {
+            Some(&Bson::I32(ms)) if ms > 0 => deadline.cap_ms(ms as i64),
+            Some(&Bson::I64(ms)) if ms > 0 => deadline.cap_ms(ms),
+            Some(&Bson::FloatingPoint(ms)) if ms > 0.0 => deadline.cap_ms(ms as i64),
+            _ => deadline.cap_ms(i64::MAX),
+        };
+
+        nq.insert(key.to_owned(), Bson::I64(max_time_ms));
+        Ok(nq)
     }
 
     pub fn query_with_stream(stream: PooledStream,
@@ -248,11 +366,19 @@ impl Cursor {
                              return_field_selector: Option<bson::Document>,
                              cmd_type: CommandType,
                              is_cmd_cursor: bool,
-                             read_pref: Option<ReadPreference>) -> Result<Cursor> {
+                             read_pref: Option<ReadPreference>,
+                             deadline: Deadline,
+                             cancellation: Option<CancellationToken>) -> Result<Cursor> {
 
         let mut socket = stream.get_socket();
         let req_id = client.get_req_id();
 
+        // Allow the token to interrupt the round trip from another thread.
+        let _registration = match cancellation {
+            Some(ref token) => Some(try!(token.register(&stream))),
+            None => None,
+        };
+
         let index = namespace.find(".").unwrap_or(namespace.len());
         let db_name = namespace[..index].to_owned();
         let coll_name = namespace[index + 1..].to_owned();
@@ -310,9 +436,14 @@ impl Cursor {
             }
         }
 
-        try_or_emit!(cmd_type, cmd_name, req_id, connstring, message.write(&mut socket), client);
-        let reply = try_or_emit!(cmd_type, cmd_name, req_id, connstring, Message::read(&mut socket),
-                                 client);
+        try_or_emit!(cmd_type, cmd_name, req_id, connstring,
+                     message.write(&mut socket).map_err(|e| {
+                         Cursor::interrupted(e, &stream, &deadline, &cancellation)
+                     }), client);
+        let reply = try_or_emit!(cmd_type, cmd_name, req_id, connstring,
+                                 Message::read(&mut socket).map_err(|e| {
+                                     Cursor::interrupted(e, &stream, &deadline, &cancellation)
+                                 }), client);
 
         let fin_time = time::precise_time_ns();
 
@@ -356,13 +487,33 @@ impl Cursor {
                     batch_size: batch_size, cursor_id: cursor_id,
                     limit: number_to_return, count: 0, buffer: buf,
                     read_preference: read_preference,
-                    cmd_type: cmd_type.clone(), })
+                    cmd_type: cmd_type.clone(), deadline: deadline,
+                    cancellation: cancellation.clone(), })
+    }
+
+    /// Sets a token that can cancel subsequent iteration of the cursor from another thread.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
     }
 
     fn get_from_stream(&mut self) -> Result<()> {
-        let (stream, _, _) = try!(self.client.acquire_stream(self.read_preference.to_owned()));
+        let deadline = self.deadline;
+        let cancellation = self.cancellation.clone();
+
+        try!(deadline.check());
+        if let Some(ref token) = cancellation {
+            try!(token.check());
+        }
+
+        let (stream, _, _) = try!(self.client.acquire_stream(self.read_preference.to_owned(),
+                                                             &deadline));
         let mut socket = stream.get_socket();
 
+        let _registration = match cancellation {
+            Some(ref token) => Some(try!(token.register(&stream))),
+            None => None,
+        };
+
         let req_id = self.client.get_req_id();
         let get_more = Message::new_get_more(req_id, self.namespace.to_owned(), self.batch_size,
                                              self.cursor_id);
@@ -386,8 +537,13 @@ impl Cursor {
             }
         }
 
-        try_or_emit!(self.cmd_type, cmd_name, req_id, connstring, get_more.write(&mut socket), self.client);
-        let reply = try!(Message::read(&mut socket));
+        try_or_emit!(self.cmd_type, cmd_name, req_id, connstring,
+                     get_more.write(&mut socket).map_err(|e| {
+                         Cursor::interrupted(e, &stream, &deadline, &cancellation)
+                     }), self.client);
+        let reply = try!(Message::read(&mut socket).map_err(|e| {
+            Cursor::interrupted(e, &stream, &deadline, &cancellation)
+        }));
 
         let (_, v, _) = try!(Cursor::get_bson_and_cid_from_message(reply));
         self.buffer.extend(v);
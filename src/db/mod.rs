@@ -3,7 +3,7 @@
 //! # Usage
 //!
 //! The database API provides methods for opening, creating, deleting, and listing collections. It also handles
-//! user-level authentication over SCRAM-SHA-1.
+//! user-level authentication over SCRAM-SHA-1, SCRAM-SHA-256 and MONGODB-CR.
 //!
 //! ## Collection Operations
 //!
@@ -29,7 +29,7 @@
 //! db.create_user("saghm", "1234", None).unwrap();
 //! db.auth("saghm", "1234").unwrap();
 //!
-//! let success = db.list_collections(None).unwrap();
+//! let success = db.list_collections(None, None).unwrap();
 //! ```
 //!
 //! ## Arbitrary Database Commands
@@ -56,18 +56,23 @@
 //! ```
 pub mod options;
 pub mod roles;
+pub mod specs;
 
-use auth::Authenticator;
+use auth::{AuthMechanism, Authenticator};
 use bson;
 use bson::Bson;
 use {Client, CommandType, ThreadedClient, Result};
-use Error::{CursorNotFoundError, OperationError};
+use Error::{ArgumentError, CursorNotFoundError, OperationError, ResponseError};
 use coll::Collection;
 use coll::options::FindOptions;
-use common::{ReadPreference, WriteConcern};
-use cursor::{Cursor, DEFAULT_BATCH_SIZE};
-use self::options::{CreateCollectionOptions, CreateUserOptions, UserInfoOptions};
-use self::roles::Role;
+use common::{Deadline, ReadPreference, WriteConcern};
+use cursor::Cursor;
+use self::options::{CreateCollectionOptions, CreateRoleOptions, CreateUserOptions,
+                    ListCollectionsOptions, RoleInfoOptions, UpdateRoleOptions,
+                    UpdateUserOptions, UserInfoOptions};
+use self::specs::CollectionSpecification;
+use self::roles::{Privilege, Role};
+use stats::DatabaseStats;
 use std::sync::Arc;
 
 /// Interfaces with a MongoDB database.
@@ -80,6 +85,8 @@ pub struct DatabaseInner {
     pub read_preference: ReadPreference,
     /// Describes the guarantees provided by MongoDB when reporting the success of a write operation.
     pub write_concern: WriteConcern,
+    /// The time limit in milliseconds for an entire operation, if any.
+    pub timeout_ms: Option<i64>,
 }
 
 pub type Database = Arc<DatabaseInner>;
@@ -88,8 +95,20 @@ pub trait ThreadedDatabase {
     /// Creates a database representation with optional read and write controls.
     fn open(client: Client, name: &str, read_preference: Option<ReadPreference>,
             write_concern: Option<WriteConcern>) -> Database;
-    /// Logs in a user using the SCRAM-SHA-1 mechanism.
+    /// Creates a copy of the database representation with a different operation time limit.
+    /// A timeout of zero places no limit on operations.
+    fn with_timeout(&self, timeout_ms: Option<i64>) -> Database;
+    /// Logs in a user using the mechanism specified by the authMechanism connection string
+    /// option, or the strongest mechanism the server reports for the user through
+    /// saslSupportedMechs, falling back to SCRAM-SHA-1, or MONGODB-CR for servers before 3.0.
     fn auth(&self, user: &str, password: &str) -> Result<()>;
+    /// Logs in a user using the specified mechanism.
+    fn auth_with_mechanism(&self, user: &str, password: &str,
+                           mechanism: AuthMechanism) -> Result<()>;
+    /// Logs out the users authenticated on this database over the connection that runs the
+    /// command. The connection is then closed rather than returned to the pool, so later
+    /// operations only use connections authenticated with the client's credential.
+    fn logout(&self) -> Result<()>;
     /// Creates a collection representation with inherited read and write controls.
     fn collection(&self, coll_name: &str) -> Collection;
     /// Creates a collection representation with custom read and write controls.
@@ -104,8 +123,11 @@ pub trait ThreadedDatabase {
     /// Sends an administrative command over find_one.
     fn command(&self, spec: bson::Document, cmd_type: CommandType,
                read_preference: Option<ReadPreference>) -> Result<bson::Document>;
-    /// Returns a list of collections within the database.
-    fn list_collections(&self, filter: Option<bson::Document>) -> Result<Cursor>;
+    /// Returns storage statistics for the database, as reported by dbStats.
+    fn stats(&self) -> Result<DatabaseStats>;
+    /// Returns descriptions of the collections and views within the database.
+    fn list_collections(&self, filter: Option<bson::Document>,
+                        options: Option<ListCollectionsOptions>) -> Result<Vec<CollectionSpecification>>;
     /// Returns a list of collections within the database with a custom batch size.
     fn list_collections_with_batch_size(&self, filter: Option<bson::Document>,
                                         batch_size: i32) -> Result<Cursor>;
@@ -114,9 +136,15 @@ pub trait ThreadedDatabase {
     /// Creates a new collection.
     ///
     /// Note that due to the implicit creation of collections during insertion, this
-    /// method should only be used to instantiate capped collections.
+    /// method should only be used to instantiate collections with options, such as capped
+    /// collections or collections with document validators.
     fn create_collection(&self, name: &str,
                          options: Option<CreateCollectionOptions>) -> Result<()>;
+    /// Creates a read-only view of the results of an aggregation pipeline on another
+    /// collection or view. Only the collation option applies to views; setting any other
+    /// option is an ArgumentError.
+    fn create_view(&self, name: &str, view_on: &str, pipeline: Vec<bson::Document>,
+                   options: Option<CreateCollectionOptions>) -> Result<()>;
     /// Creates a new user.
     fn create_user(&self, name: &str, password: &str,
                    options: Option<CreateUserOptions>) -> Result<()>;
@@ -136,6 +164,30 @@ pub trait ThreadedDatabase {
     /// Retrieves information about a given set of users from the database.
     fn get_users(&self, users: Vec<&str>,
                  options: Option<UserInfoOptions>) -> Result<Vec<bson::Document>>;
+    /// Updates a user's password, custom data, or roles.
+    fn update_user(&self, name: &str, options: UpdateUserOptions) -> Result<()>;
+    /// Grants additional roles to a user.
+    fn grant_roles_to_user(&self, name: &str, roles: Vec<Role>,
+                           write_concern: Option<WriteConcern>) -> Result<()>;
+    /// Revokes roles from a user.
+    fn revoke_roles_from_user(&self, name: &str, roles: Vec<Role>,
+                              write_concern: Option<WriteConcern>) -> Result<()>;
+    /// Creates a user-defined role.
+    fn create_role(&self, name: &str, options: Option<CreateRoleOptions>) -> Result<()>;
+    /// Replaces a user-defined role's privileges or inherited roles.
+    fn update_role(&self, name: &str, options: UpdateRoleOptions) -> Result<()>;
+    /// Permanently deletes a user-defined role from the database.
+    fn drop_role(&self, name: &str, write_concern: Option<WriteConcern>) -> Result<()>;
+    /// Grants additional privileges to a user-defined role.
+    fn grant_privileges_to_role(&self, name: &str, privileges: Vec<Privilege>,
+                                write_concern: Option<WriteConcern>) -> Result<()>;
+    /// Revokes privileges from a user-defined role.
+    fn revoke_privileges_from_role(&self, name: &str, privileges: Vec<Privilege>,
+                                   write_concern: Option<WriteConcern>) -> Result<()>;
+    /// Retrieves information about the given roles, or all roles in the database if none
+    /// are given.
+    fn roles_info(&self, roles: Vec<&str>,
+                  options: Option<RoleInfoOptions>) -> Result<Vec<bson::Document>>;
 }
 
 impl ThreadedDatabase for Database {
@@ -146,17 +198,45 @@ impl ThreadedDatabase for Database {
 
         Arc::new(DatabaseInner {
             name: name.to_owned(),
+            timeout_ms: client.timeout_ms,
             client: client,
             read_preference: rp,
             write_concern: wc,
         })
     }
 
+    fn with_timeout(&self, timeout_ms: Option<i64>) -> Database {
+        Arc::new(DatabaseInner {
+            name: self.name.to_owned(),
+            client: self.client.clone(),
+            read_preference: self.read_preference.to_owned(),
+            write_concern: self.write_concern.to_owned(),
+            timeout_ms: timeout_ms,
+        })
+    }
+
     fn auth(&self, user: &str, password: &str) -> Result<()> {
         let authenticator = Authenticator::new(self.clone());
         authenticator.auth(user, password)
     }
 
+    fn auth_with_mechanism(&self, user: &str, password: &str,
+                           mechanism: AuthMechanism) -> Result<()> {
+        let authenticator = Authenticator::new(self.clone());
+        authenticator.auth_with_mechanism(user, password, mechanism)
+    }
+
+    fn logout(&self) -> Result<()> {
+        let stream = try!(self.client.acquire_write_stream(&Deadline::new(self.timeout_ms)));
+
+        // The connection no longer carries the client's credential once logged out.
+        stream.discard();
+
+        let authenticator = Authenticator::with_socket(self.clone(),
+                                                       try!(stream.get_socket().try_clone()));
+        authenticator.logout()
+    }
+
     fn collection(&self, coll_name: &str) -> Collection {
         Collection::new(self.clone(), coll_name, false, Some(self.read_preference.to_owned()), Some(self.write_concern.to_owned()))
     }
@@ -173,7 +253,8 @@ impl ThreadedDatabase for Database {
 
     fn command_cursor(&self, spec: bson::Document, cmd_type: CommandType,
                       read_pref: ReadPreference) -> Result<Cursor> {
-        Cursor::command_cursor(self.client.clone(), &self.name[..], spec, cmd_type, read_pref)
+        Cursor::command_cursor(self.client.clone(), &self.name[..], spec, cmd_type, read_pref,
+                               Deadline::new(self.timeout_ms), None)
     }
 
     fn command(&self, spec: bson::Document, cmd_type: CommandType,
@@ -188,8 +269,44 @@ impl ThreadedDatabase for Database {
         res.ok_or(OperationError(format!("Failed to execute command with spec {:?}.", spec)))
     }
 
-    fn list_collections(&self, filter: Option<bson::Document>) -> Result<Cursor> {
-        self.list_collections_with_batch_size(filter, DEFAULT_BATCH_SIZE)
+    fn stats(&self) -> Result<DatabaseStats> {
+        let res = try!(self.command(doc! { "dbStats" => 1 }, CommandType::DbStats, None));
+        Ok(DatabaseStats::with_doc(res))
+    }
+
+    fn list_collections(&self, filter: Option<bson::Document>,
+                        options: Option<ListCollectionsOptions>) -> Result<Vec<CollectionSpecification>> {
+        let list_options = options.unwrap_or(ListCollectionsOptions::new());
+        let mut spec = doc! {
+            "listCollections" => 1,
+            "cursor" => { "batchSize" => (list_options.batch_size) }
+        };
+
+        if let Some(filter) = filter {
+            spec.insert("filter".to_owned(), Bson::Document(filter));
+        }
+
+        if list_options.name_only {
+            spec.insert("nameOnly".to_owned(), Bson::Boolean(true));
+        }
+
+        if let Some(authorized) = list_options.authorized_collections {
+            spec.insert("authorizedCollections".to_owned(), Bson::Boolean(authorized));
+        }
+
+        let cursor = try!(self.command_cursor(spec, CommandType::ListCollections,
+                                              self.read_preference.to_owned()));
+        let mut specs = vec![];
+
+        for result in cursor {
+            let doc = try!(result);
+            match CollectionSpecification::with_doc(&doc) {
+                Some(spec) => specs.push(spec),
+                None => return Err(ResponseError(format!("Invalid collection specification: {:?}", doc))),
+            }
+        }
+
+        Ok(specs)
     }
 
     fn list_collections_with_batch_size(&self, filter: Option<bson::Document>,
@@ -209,27 +326,51 @@ impl ThreadedDatabase for Database {
     }
 
     fn collection_names(&self, filter: Option<bson::Document>) -> Result<Vec<String>> {
-        let mut cursor = try!(self.list_collections(filter));
-        let mut results = vec![];
-        loop {
-            match cursor.next() {
-                Some(Ok(doc)) => if let Some(&Bson::String(ref name)) = doc.get("name") {
-                    results.push(name.to_owned());
-                },
-                Some(Err(err)) => return Err(err),
-                None => return Ok(results),
-            }
-        }
+        let mut options = ListCollectionsOptions::new();
+        options.name_only = true;
+
+        let specs = try!(self.list_collections(filter, Some(options)));
+        Ok(specs.into_iter().map(|spec| spec.name).collect())
     }
 
     fn create_collection(&self, name: &str,
                          options: Option<CreateCollectionOptions>) -> Result<()> {
         let coll_options = options.unwrap_or(CreateCollectionOptions::new());
-        let mut doc = doc! {
-            "create" => name,
-            "capped" => (coll_options.capped),
-            "auto_index_id" => (coll_options.auto_index_id)
-        };
+        let mut doc = doc! { "create" => name };
+
+        if let Some(view_on) = coll_options.view_on {
+            // Views only accept a source, pipeline and collation.
+            let defaults = CreateCollectionOptions::new();
+            if coll_options.capped != defaults.capped ||
+               coll_options.auto_index_id != defaults.auto_index_id ||
+               coll_options.size.is_some() || coll_options.max.is_some() ||
+               coll_options.use_power_of_two_sizes != defaults.use_power_of_two_sizes ||
+               coll_options.no_padding != defaults.no_padding ||
+               coll_options.validator.is_some() || coll_options.validation_level.is_some() ||
+               coll_options.validation_action.is_some() || coll_options.storage_engine.is_some() ||
+               coll_options.index_option_defaults.is_some() {
+                return Err(ArgumentError("Views only accept the pipeline and collation \
+                                          options.".to_owned()));
+            }
+
+            let pipeline = coll_options.pipeline.unwrap_or(vec![]);
+            doc.insert("viewOn".to_owned(), Bson::String(view_on));
+            doc.insert("pipeline".to_owned(),
+                       Bson::Array(pipeline.into_iter().map(Bson::Document).collect()));
+
+            if let Some(collation) = coll_options.collation {
+                doc.insert("collation".to_owned(), Bson::Document(collation));
+            }
+
+            return self.command(doc, CommandType::CreateCollection, None).map(|_| ());
+        }
+
+        if coll_options.pipeline.is_some() {
+            return Err(ArgumentError("A pipeline requires a view source; use create_view.".to_owned()));
+        }
+
+        doc.insert("capped".to_owned(), Bson::Boolean(coll_options.capped));
+        doc.insert("auto_index_id".to_owned(), Bson::Boolean(coll_options.auto_index_id));
 
         if let Some(i) = coll_options.size {
             doc.insert("size".to_owned(), Bson::I64(i));
@@ -244,9 +385,41 @@ impl ThreadedDatabase for Database {
 
         doc.insert("flags".to_owned(), Bson::I32(flag_one + flag_two));
 
+        if let Some(validator) = coll_options.validator {
+            doc.insert("validator".to_owned(), Bson::Document(validator));
+        }
+
+        if let Some(level) = coll_options.validation_level {
+            doc.insert("validationLevel".to_owned(), Bson::String(level.as_str().to_owned()));
+        }
+
+        if let Some(action) = coll_options.validation_action {
+            doc.insert("validationAction".to_owned(), Bson::String(action.as_str().to_owned()));
+        }
+
+        if let Some(collation) = coll_options.collation {
+            doc.insert("collation".to_owned(), Bson::Document(collation));
+        }
+
+        if let Some(storage_engine) = coll_options.storage_engine {
+            doc.insert("storageEngine".to_owned(), Bson::Document(storage_engine));
+        }
+
+        if let Some(defaults) = coll_options.index_option_defaults {
+            doc.insert("indexOptionDefaults".to_owned(), Bson::Document(defaults));
+        }
+
         self.command(doc, CommandType::CreateCollection, None).map(|_| ())
     }
 
+    fn create_view(&self, name: &str, view_on: &str, pipeline: Vec<bson::Document>,
+                   options: Option<CreateCollectionOptions>) -> Result<()> {
+        let mut view_options = options.unwrap_or(CreateCollectionOptions::new());
+        view_options.view_on = Some(view_on.to_owned());
+        view_options.pipeline = Some(pipeline);
+        self.create_collection(name, Some(view_options))
+    }
+
     fn create_user(&self, name: &str, password: &str,
                    options: Option<CreateUserOptions>) -> Result<()> {
         let user_options = options.unwrap_or(CreateUserOptions::new());
@@ -389,4 +562,161 @@ impl ThreadedDatabase for Database {
 
         Ok(users)
     }
+
+    fn update_user(&self, name: &str, options: UpdateUserOptions) -> Result<()> {
+        let mut doc = doc! { "updateUser" => name };
+
+        if let Some(password) = options.password {
+            doc.insert("pwd".to_owned(), Bson::String(password));
+        }
+
+        if let Some(data) = options.custom_data {
+            doc.insert("customData".to_owned(), Bson::Document(data));
+        }
+
+        if let Some(roles) = options.roles {
+            doc.insert("roles".to_owned(), Role::to_bson_array(roles));
+        }
+
+        if let Some(concern) = options.write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::UpdateUser, None).map(|_| ())
+    }
+
+    fn grant_roles_to_user(&self, name: &str, roles: Vec<Role>,
+                           write_concern: Option<WriteConcern>) -> Result<()> {
+        let mut doc = doc! {
+            "grantRolesToUser" => name,
+            "roles" => (Role::to_bson_array(roles))
+        };
+
+        if let Some(concern) = write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::GrantRolesToUser, None).map(|_| ())
+    }
+
+    fn revoke_roles_from_user(&self, name: &str, roles: Vec<Role>,
+                              write_concern: Option<WriteConcern>) -> Result<()> {
+        let mut doc = doc! {
+            "revokeRolesFromUser" => name,
+            "roles" => (Role::to_bson_array(roles))
+        };
+
+        if let Some(concern) = write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::RevokeRolesFromUser, None).map(|_| ())
+    }
+
+    fn create_role(&self, name: &str, options: Option<CreateRoleOptions>) -> Result<()> {
+        let role_options = options.unwrap_or(CreateRoleOptions::new());
+        let mut doc = doc! {
+            "createRole" => name,
+            "privileges" => (Privilege::to_bson_array(role_options.privileges)),
+            "roles" => (Role::to_bson_array(role_options.roles))
+        };
+
+        if let Some(concern) = role_options.write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::CreateRole, None).map(|_| ())
+    }
+
+    fn update_role(&self, name: &str, options: UpdateRoleOptions) -> Result<()> {
+        let mut doc = doc! { "updateRole" => name };
+
+        if let Some(privileges) = options.privileges {
+            doc.insert("privileges".to_owned(), Privilege::to_bson_array(privileges));
+        }
+
+        if let Some(roles) = options.roles {
+            doc.insert("roles".to_owned(), Role::to_bson_array(roles));
+        }
+
+        if let Some(concern) = options.write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::UpdateRole, None).map(|_| ())
+    }
+
+    fn drop_role(&self, name: &str, write_concern: Option<WriteConcern>) -> Result<()> {
+        let mut doc = doc! { "dropRole" => name };
+
+        if let Some(concern) = write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::DropRole, None).map(|_| ())
+    }
+
+    fn grant_privileges_to_role(&self, name: &str, privileges: Vec<Privilege>,
+                                write_concern: Option<WriteConcern>) -> Result<()> {
+        let mut doc = doc! {
+            "grantPrivilegesToRole" => name,
+            "privileges" => (Privilege::to_bson_array(privileges))
+        };
+
+        if let Some(concern) = write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::GrantPrivilegesToRole, None).map(|_| ())
+    }
+
+    fn revoke_privileges_from_role(&self, name: &str, privileges: Vec<Privilege>,
+                                   write_concern: Option<WriteConcern>) -> Result<()> {
+        let mut doc = doc! {
+            "revokePrivilegesFromRole" => name,
+            "privileges" => (Privilege::to_bson_array(privileges))
+        };
+
+        if let Some(concern) = write_concern {
+            doc.insert("writeConcern".to_owned(), Bson::Document(concern.to_bson()));
+        }
+
+        self.command(doc, CommandType::RevokePrivilegesFromRole, None).map(|_| ())
+    }
+
+    fn roles_info(&self, roles: Vec<&str>,
+                  options: Option<RoleInfoOptions>) -> Result<Vec<bson::Document>> {
+        let info_options = options.unwrap_or(RoleInfoOptions::new());
+
+        let roles = if roles.is_empty() {
+            Bson::I32(1)
+        } else {
+            Bson::Array(roles.into_iter().map(|role| {
+                Bson::Document(doc! { "role" => role, "db" => (Bson::String(self.name.to_owned())) })
+            }).collect())
+        };
+
+        let doc = doc! {
+            "rolesInfo" => (roles),
+            "showPrivileges" => (info_options.show_privileges),
+            "showBuiltinRoles" => (info_options.show_builtin_roles)
+        };
+
+        let out = try!(self.command(doc, CommandType::GetRoles, None));
+        let vec = match out.get("roles") {
+            Some(&Bson::Array(ref vec)) => vec.clone(),
+            _ => return Err(CursorNotFoundError)
+        };
+
+        let mut roles = vec![];
+
+        for bson in vec {
+            match bson {
+                Bson::Document(doc) => roles.push(doc),
+                _ => return Err(CursorNotFoundError)
+            };
+        }
+
+        Ok(roles)
+    }
 }
@@ -1,7 +1,45 @@
 //! Options for database-level commands.
 use bson::Document;
 use common::WriteConcern;
-use db::roles::Role;
+use cursor::DEFAULT_BATCH_SIZE;
+use db::roles::{Privilege, Role};
+
+/// How strictly a collection's validator is applied to updates of existing documents.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// Validation is disabled.
+    Off,
+    /// All inserts and updates are validated.
+    Strict,
+    /// Updates to existing documents that are already invalid are not validated.
+    Moderate,
+}
+
+impl ValidationLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            &ValidationLevel::Off => "off",
+            &ValidationLevel::Strict => "strict",
+            &ValidationLevel::Moderate => "moderate",
+        }
+    }
+}
+
+/// Whether invalid documents are rejected or only logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationAction {
+    Error,
+    Warn,
+}
+
+impl ValidationAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            &ValidationAction::Error => "error",
+            &ValidationAction::Warn => "warn",
+        }
+    }
+}
 
 pub struct CreateCollectionOptions {
     pub capped: bool,
@@ -10,12 +48,30 @@ pub struct CreateCollectionOptions {
     pub max: Option<i64>,
     pub use_power_of_two_sizes: bool,
     pub no_padding: bool,
+    /// A query filter that inserted and updated documents must match.
+    pub validator: Option<Document>,
+    pub validation_level: Option<ValidationLevel>,
+    pub validation_action: Option<ValidationAction>,
+    /// The default collation for operations on the collection.
+    pub collation: Option<Document>,
+    /// Storage engine configuration, keyed by storage engine name.
+    pub storage_engine: Option<Document>,
+    /// Default storage engine configuration for indexes created on the collection.
+    pub index_option_defaults: Option<Document>,
+    /// The source collection or view, if creating a view.
+    pub view_on: Option<String>,
+    /// The aggregation pipeline applied to the source, if creating a view.
+    pub pipeline: Option<Vec<Document>>,
 }
 
 impl CreateCollectionOptions {
     pub fn new() -> CreateCollectionOptions {
         CreateCollectionOptions { capped: false, auto_index_id: true, size: None, max: None,
-                                  use_power_of_two_sizes: true, no_padding: false }
+                                  use_power_of_two_sizes: true, no_padding: false,
+                                  validator: None, validation_level: None,
+                                  validation_action: None, collation: None,
+                                  storage_engine: None, index_option_defaults: None,
+                                  view_on: None, pipeline: None }
     }
 }
 
@@ -31,6 +87,20 @@ impl CreateUserOptions {
     }
 }
 
+pub struct UpdateUserOptions {
+    pub password: Option<String>,
+    pub custom_data: Option<Document>,
+    /// Replaces the user's roles, if set.
+    pub roles: Option<Vec<Role>>,
+    pub write_concern: Option<WriteConcern>,
+}
+
+impl UpdateUserOptions {
+    pub fn new() -> UpdateUserOptions {
+        UpdateUserOptions { password: None, custom_data: None, roles: None, write_concern: None }
+    }
+}
+
 pub struct UserInfoOptions {
     pub show_credentials: bool,
     pub show_privileges: bool,
@@ -41,3 +111,70 @@ impl UserInfoOptions {
         UserInfoOptions { show_credentials: false, show_privileges: false }
     }
 }
+
+pub struct CreateRoleOptions {
+    pub privileges: Vec<Privilege>,
+    /// Roles whose privileges this role inherits.
+    pub roles: Vec<Role>,
+    pub write_concern: Option<WriteConcern>,
+}
+
+impl CreateRoleOptions {
+    pub fn new() -> CreateRoleOptions {
+        CreateRoleOptions { privileges: vec![], roles: vec![], write_concern: None }
+    }
+}
+
+pub struct UpdateRoleOptions {
+    /// Replaces the role's privileges, if set.
+    pub privileges: Option<Vec<Privilege>>,
+    /// Replaces the role's inherited roles, if set.
+    pub roles: Option<Vec<Role>>,
+    pub write_concern: Option<WriteConcern>,
+}
+
+impl UpdateRoleOptions {
+    pub fn new() -> UpdateRoleOptions {
+        UpdateRoleOptions { privileges: None, roles: None, write_concern: None }
+    }
+}
+
+pub struct RoleInfoOptions {
+    pub show_privileges: bool,
+    pub show_builtin_roles: bool,
+}
+
+impl RoleInfoOptions {
+    pub fn new() -> RoleInfoOptions {
+        RoleInfoOptions { show_privileges: false, show_builtin_roles: false }
+    }
+}
+
+pub struct ListDatabasesOptions {
+    /// Returns only database names, which does not require locking the databases.
+    pub name_only: bool,
+    /// Lists only the databases the user is authorized to access, if set. Servers default
+    /// to true when only names are requested by a user without the listDatabases privilege.
+    pub authorized_databases: Option<bool>,
+}
+
+impl ListDatabasesOptions {
+    pub fn new() -> ListDatabasesOptions {
+        ListDatabasesOptions { name_only: false, authorized_databases: None }
+    }
+}
+
+pub struct ListCollectionsOptions {
+    /// Returns only collection names and types, which does not require locking the collections.
+    pub name_only: bool,
+    /// Lists only the collections the user is authorized to access, if set.
+    pub authorized_collections: Option<bool>,
+    pub batch_size: i32,
+}
+
+impl ListCollectionsOptions {
+    pub fn new() -> ListCollectionsOptions {
+        ListCollectionsOptions { name_only: false, authorized_collections: None,
+                                 batch_size: DEFAULT_BATCH_SIZE }
+    }
+}
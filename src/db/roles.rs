@@ -63,6 +63,11 @@ pub enum Role {
         role: SingleDatabaseRole,
         db: String,
     },
+    /// A user-defined role, created with `ThreadedDatabase::create_role`.
+    Custom {
+        role: String,
+        db: String,
+    },
 }
 
 impl Role {
@@ -72,6 +77,10 @@ impl Role {
             &Role::Single { ref role, ref db } => Bson::Document(doc! {
                 "role" => (Bson::String(role.to_string())),
                 "db" => (Bson::String(db.to_owned()))
+            }),
+            &Role::Custom { ref role, ref db } => Bson::Document(doc! {
+                "role" => (Bson::String(role.to_owned())),
+                "db" => (Bson::String(db.to_owned()))
             })
         }
     }
@@ -80,3 +89,59 @@ impl Role {
         Bson::Array(vec.into_iter().map(|r| r.to_bson()).collect())
     }
 }
+
+/// The resource that a privilege applies to.
+pub enum Resource {
+    /// A collection within a database. An empty database name matches all databases,
+    /// and an empty collection name matches all collections.
+    Collection {
+        db: String,
+        collection: String,
+    },
+    /// The cluster, for cluster-wide actions such as shutdown.
+    Cluster,
+    /// Every resource in the system.
+    AnyResource,
+}
+
+impl Resource {
+    fn to_bson(&self) -> Bson {
+        match self {
+            &Resource::Collection { ref db, ref collection } => Bson::Document(doc! {
+                "db" => (Bson::String(db.to_owned())),
+                "collection" => (Bson::String(collection.to_owned()))
+            }),
+            &Resource::Cluster => Bson::Document(doc! { "cluster" => true }),
+            &Resource::AnyResource => Bson::Document(doc! { "anyResource" => true }),
+        }
+    }
+}
+
+/// A set of actions permitted on a resource, as granted by a user-defined role.
+pub struct Privilege {
+    pub resource: Resource,
+    /// Privilege actions, such as 'find' or 'insert'.
+    pub actions: Vec<String>,
+}
+
+impl Privilege {
+    pub fn new(resource: Resource, actions: Vec<&str>) -> Privilege {
+        Privilege {
+            resource: resource,
+            actions: actions.into_iter().map(|action| action.to_owned()).collect(),
+        }
+    }
+
+    fn to_bson(&self) -> Bson {
+        let actions = self.actions.iter().map(|action| Bson::String(action.to_owned())).collect();
+
+        Bson::Document(doc! {
+            "resource" => (self.resource.to_bson()),
+            "actions" => (Bson::Array(actions))
+        })
+    }
+
+    pub fn to_bson_array(vec: Vec<Privilege>) -> Bson {
+        Bson::Array(vec.into_iter().map(|p| p.to_bson()).collect())
+    }
+}
@@ -0,0 +1,115 @@
+//! Typed descriptions of databases and collections, as returned by listDatabases and
+//! listCollections.
+use bson::{self, Bson};
+
+/// Describes a database on the server.
+#[derive(Clone, Debug)]
+pub struct DatabaseSpecification {
+    pub name: String,
+    /// The size of the database files in bytes, omitted if only names were requested.
+    pub size_on_disk: Option<i64>,
+    /// Whether the database is empty, omitted if only names were requested.
+    pub empty: Option<bool>,
+}
+
+impl DatabaseSpecification {
+    /// Reads a database description from a listDatabases entry.
+    pub fn with_doc(doc: &bson::Document) -> Option<DatabaseSpecification> {
+        let name = match doc.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return None,
+        };
+
+        let size_on_disk = match doc.get("sizeOnDisk") {
+            Some(&Bson::I32(size)) => Some(size as i64),
+            Some(&Bson::I64(size)) => Some(size),
+            Some(&Bson::FloatingPoint(size)) => Some(size as i64),
+            _ => None,
+        };
+
+        let empty = match doc.get("empty") {
+            Some(&Bson::Boolean(empty)) => Some(empty),
+            _ => None,
+        };
+
+        Some(DatabaseSpecification { name: name, size_on_disk: size_on_disk, empty: empty })
+    }
+}
+
+/// The kind of namespace described by a collection specification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CollectionType {
+    Collection,
+    View,
+    /// A namespace type not known to the driver.
+    Other(String),
+}
+
+/// Additional information about a collection.
+#[derive(Clone, Debug)]
+pub struct CollectionInfo {
+    /// Whether the namespace is read-only, as views are.
+    pub read_only: bool,
+    /// The collection's UUID, reported by MongoDB 3.6 and later.
+    pub uuid: Option<Bson>,
+}
+
+/// Describes a collection or view within a database.
+#[derive(Clone, Debug)]
+pub struct CollectionSpecification {
+    pub name: String,
+    pub collection_type: CollectionType,
+    /// The options the collection was created with.
+    pub options: bson::Document,
+    pub info: CollectionInfo,
+    /// The specification of the _id index, which views do not have.
+    pub id_index: Option<bson::Document>,
+}
+
+impl CollectionSpecification {
+    /// Reads a collection description from a listCollections entry. Servers before MongoDB
+    /// 3.4 omit the type, info, and _id index fields.
+    pub fn with_doc(doc: &bson::Document) -> Option<CollectionSpecification> {
+        let name = match doc.get("name") {
+            Some(&Bson::String(ref name)) => name.to_owned(),
+            _ => return None,
+        };
+
+        let collection_type = match doc.get("type") {
+            Some(&Bson::String(ref kind)) if kind == "view" => CollectionType::View,
+            Some(&Bson::String(ref kind)) if kind != "collection" => {
+                CollectionType::Other(kind.to_owned())
+            },
+            _ => CollectionType::Collection,
+        };
+
+        let options = match doc.get("options") {
+            Some(&Bson::Document(ref options)) => options.clone(),
+            _ => bson::Document::new(),
+        };
+
+        let info = match doc.get("info") {
+            Some(&Bson::Document(ref info)) => CollectionInfo {
+                read_only: match info.get("readOnly") {
+                    Some(&Bson::Boolean(read_only)) => read_only,
+                    _ => false,
+                },
+                uuid: info.get("uuid").cloned(),
+            },
+            _ => CollectionInfo { read_only: collection_type == CollectionType::View, uuid: None },
+        };
+
+        let id_index = match doc.get("idIndex") {
+            Some(&Bson::Document(ref index)) => Some(index.clone()),
+            _ => None,
+        };
+
+        Some(CollectionSpecification {
+            name: name,
+            collection_type: collection_type,
+            options: options,
+            info: info,
+            id_index: id_index,
+        })
+    }
+}
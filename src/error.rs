@@ -13,6 +13,7 @@ pub enum MaliciousServerErrorType {
     InvalidRnonce,
     InvalidServerSignature,
     NoServerSignature,
+    InsufficientIterations,
 }
 
 impl fmt::Display for MaliciousServerErrorType {
@@ -21,6 +22,7 @@ impl fmt::Display for MaliciousServerErrorType {
             &MaliciousServerErrorType::InvalidRnonce => fmt.write_str("The server returned an invalid rnonce during authentication"),
             &MaliciousServerErrorType::InvalidServerSignature => fmt.write_str("The server returned an invalid signature during authentication"),
             &MaliciousServerErrorType::NoServerSignature => fmt.write_str("The server did not sign its reponse during authentication"),
+            &MaliciousServerErrorType::InsufficientIterations => fmt.write_str("The server requested too few iterations during authentication"),
         }
     }
 }
@@ -50,6 +52,12 @@ pub enum Error {
     ResponseError(String),
     /// A cursor operation failed to return a cursor.
     CursorNotFoundError,
+    /// An operation did not complete before its client-side timeout expired.
+    TimeoutError,
+    /// An operation was interrupted because its cancellation token was cancelled.
+    CancelledError,
+    /// A new connection could not be authenticated with the client's credential.
+    AuthenticationError(Box<Error>),
     /// The application failed to secure a mutex due to a poisoned lock.
     PoisonLockError,
     /// A server error with a given code.
@@ -153,6 +161,9 @@ impl fmt::Display for Error {
             &Error::OperationError(ref inner) => inner.fmt(fmt),
             &Error::ResponseError(ref inner) => inner.fmt(fmt),
             &Error::CursorNotFoundError => write!(fmt, "No cursor found for cursor operation."),
+            &Error::TimeoutError => write!(fmt, "The operation exceeded its timeout."),
+            &Error::CancelledError => write!(fmt, "The operation was cancelled."),
+            &Error::AuthenticationError(ref err) => write!(fmt, "Failed to authenticate a new connection: {}", err),
             &Error::PoisonLockError => write!(fmt, "Socket lock poisoned while attempting to access."),
             &Error::CodedError(ref err) => write!(fmt, "{}", err),
             &Error::EventListenerError(ref err) => match err {
@@ -179,6 +190,9 @@ impl error::Error for Error {
             &Error::OperationError(ref inner) => &inner,
             &Error::ResponseError(ref inner) => &inner,
             &Error::CursorNotFoundError => "No cursor found for cursor operation.",
+            &Error::TimeoutError => "The operation exceeded its timeout.",
+            &Error::CancelledError => "The operation was cancelled.",
+            &Error::AuthenticationError(_) => "Failed to authenticate a new connection.",
             &Error::PoisonLockError => "Socket lock poisoned while attempting to access.",
             &Error::CodedError(ref err) => err.to_str(),
             &Error::EventListenerError(ref err) => match err {
@@ -189,6 +203,7 @@ impl error::Error for Error {
                 &MaliciousServerErrorType::InvalidRnonce => "The server returned an invalid rnonce during authentication",
                 &MaliciousServerErrorType::InvalidServerSignature => "The server returned an invalid signature during authentication",
                 &MaliciousServerErrorType::NoServerSignature => "The server did not sign its reponse during authentication",
+                &MaliciousServerErrorType::InsufficientIterations => "The server requested too few iterations during authentication",
             },
             &Error::DefaultError(ref inner) => &inner,
         }
@@ -207,6 +222,9 @@ impl error::Error for Error {
             &Error::OperationError(_) => None,
             &Error::ResponseError(_) => None,
             &Error::CursorNotFoundError => None,
+            &Error::TimeoutError => None,
+            &Error::CancelledError => None,
+            &Error::AuthenticationError(ref inner) => Some(&**inner),
             &Error::PoisonLockError => None,
             &Error::CodedError(_) => None,
             &Error::EventListenerError(_) => None,
@@ -0,0 +1,305 @@
+//! GridFS buckets, as defined by the GridFS specification.
+//!
+//! A bucket stores files in the `<bucket_name>.files` and `<bucket_name>.chunks` collections
+//! using the same layout as other MongoDB drivers, so files uploaded through a bucket can be
+//! read by other drivers and vice versa.
+//!
+//! ```no_run
+//! # extern crate bson;
+//! # extern crate mongodb;
+//! #
+//! # use bson::Bson;
+//! # use mongodb::{Client, ThreadedClient};
+//! # use mongodb::db::ThreadedDatabase;
+//! # use mongodb::gridfs::bucket::GridFsBucket;
+//! # use std::fs;
+//! #
+//! # fn main() {
+//! let client = Client::connect("localhost", 27017).unwrap();
+//! let bucket = GridFsBucket::new(client.db("grid"), None);
+//!
+//! let mut source = fs::File::open("/path/to/local_file.mp4").unwrap();
+//! let id = bucket.upload_from_stream("movie.mp4", &mut source, None).unwrap();
+//!
+//! let mut destination = fs::File::create("/path/to/copy.mp4").unwrap();
+//! bucket.download_to_stream(Bson::ObjectId(id), &mut destination).unwrap();
+//! # }
+//! ```
+use bson::{self, Bson, oid};
+
+use coll::Collection;
+use coll::options::{FindOptions, IndexOptions};
+use db::{Database, ThreadedDatabase};
+use Error::ArgumentError;
+use Result;
+
+use super::{FileCursor, Store, StoreInner, ThreadedStore, upload_file};
+use super::file::File;
+use super::options::{GridFsBucketOptions, GridFsDownloadByNameOptions, GridFsUploadOptions};
+use super::verify::VerificationReport;
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// A GridFS bucket within a database.
+#[derive(Clone)]
+pub struct GridFsBucket {
+    store: Store,
+    options: GridFsBucketOptions,
+    // Whether the bucket indexes have been verified for uploads.
+    indexes_checked: Arc<AtomicBool>,
+}
+
+impl GridFsBucket {
+    /// Creates a bucket within the database. The bucket collections are created lazily
+    /// by the first upload.
+    pub fn new(db: Database, options: Option<GridFsBucketOptions>) -> GridFsBucket {
+        let options = options.unwrap_or(GridFsBucketOptions::new());
+
+        let files = db.collection_with_prefs(&format!("{}.files", options.bucket_name), false,
+                                             options.read_preference.clone(),
+                                             options.write_concern.clone());
+        let chunks = db.collection_with_prefs(&format!("{}.chunks", options.bucket_name), false,
+                                              options.read_preference.clone(),
+                                              options.write_concern.clone());
+
+        GridFsBucket {
+            store: Arc::new(StoreInner {
+                files: files,
+                chunks: chunks,
+                chunk_size: options.chunk_size_bytes,
+                max_concurrent_writes: options.max_concurrent_writes,
+            }),
+            options: options,
+            indexes_checked: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the bucket name.
+    pub fn bucket_name(&self) -> &str {
+        &self.options.bucket_name
+    }
+
+    /// Returns the collection holding file documents.
+    pub fn files(&self) -> &Collection {
+        &self.store.files
+    }
+
+    /// Returns the collection holding file chunks.
+    pub fn chunks(&self) -> &Collection {
+        &self.store.chunks
+    }
+
+    /// Opens a stream for uploading a new file with a generated id. The file is stored
+    /// when the stream is closed.
+    pub fn open_upload_stream(&self, filename: &str,
+                              options: Option<GridFsUploadOptions>) -> Result<File> {
+        self.open_upload_stream_with_id(Bson::ObjectId(try!(oid::ObjectId::new())), filename,
+                                        options)
+    }
+
+    /// Opens a stream for uploading a new file with the provided id, which may be any
+    /// unique value.
+    pub fn open_upload_stream_with_id(&self, id: Bson, filename: &str,
+                                      options: Option<GridFsUploadOptions>) -> Result<File> {
+        let options = options.unwrap_or(GridFsUploadOptions::new());
+        try!(self.ensure_indexes());
+        upload_file(self.store.clone(), filename.to_owned(), id, self.options.chunk_size_bytes,
+                    &options)
+    }
+
+    /// Uploads the contents of a reader as a new file, returning the generated id.
+    pub fn upload_from_stream(&self, filename: &str, source: &mut Read,
+                              options: Option<GridFsUploadOptions>) -> Result<oid::ObjectId> {
+        let id = try!(oid::ObjectId::new());
+        try!(self.upload_from_stream_with_id(Bson::ObjectId(id.clone()), filename, source,
+                                             options));
+        Ok(id)
+    }
+
+    /// Uploads the contents of a reader as a new file with the provided id.
+    pub fn upload_from_stream_with_id(&self, id: Bson, filename: &str,
+                                      source: &mut Read,
+                                      options: Option<GridFsUploadOptions>) -> Result<()> {
+        let mut file = try!(self.open_upload_stream_with_id(id, filename, options));
+
+        if let Err(err) = io::copy(source, &mut file) {
+            // Abandon the upload and remove any chunks that were already written.
+            try!(file.abort());
+            return Err(From::from(err));
+        }
+
+        file.close()
+    }
+
+    /// Opens a stream for reading the file with the given id.
+    pub fn open_download_stream(&self, id: Bson) -> Result<File> {
+        match try!(self.store.files.find_one(Some(doc!{ "_id" => id }), None)) {
+            Some(doc) => File::with_doc(self.store.clone(), doc),
+            None => Err(ArgumentError("File does not exist.".to_owned())),
+        }
+    }
+
+    /// Writes the contents of the file with the given id into the writer.
+    pub fn download_to_stream(&self, id: Bson, destination: &mut Write) -> Result<()> {
+        let mut file = try!(self.open_download_stream(id));
+        try!(io::copy(&mut file, destination));
+        file.close()
+    }
+
+    /// Opens a stream for reading a revision of the file with the given filename.
+    pub fn open_download_stream_by_name(&self, filename: &str,
+                                        options: Option<GridFsDownloadByNameOptions>)
+                                        -> Result<File> {
+
+        let revision = options.unwrap_or(GridFsDownloadByNameOptions::new()).revision;
+
+        // Non-negative revisions count up from the original upload,
+        // and negative revisions count down from the most recent.
+        let mut find_options = FindOptions::new();
+        if revision >= 0 {
+            find_options.sort = Some(doc!{ "uploadDate" => 1 });
+            find_options.skip = revision as u32;
+        } else {
+            find_options.sort = Some(doc!{ "uploadDate" => (-1) });
+            find_options.skip = (-(revision as i64) - 1) as u32;
+        }
+
+        match try!(self.store.files.find_one(Some(doc!{ "filename" => filename }),
+                                             Some(find_options))) {
+            Some(doc) => File::with_doc(self.store.clone(), doc),
+            None => Err(ArgumentError(format!("Revision {} of file '{}' does not exist.",
+                                              revision, filename))),
+        }
+    }
+
+    /// Writes the contents of a revision of the named file into the writer.
+    pub fn download_to_stream_by_name(&self, filename: &str, destination: &mut Write,
+                                      options: Option<GridFsDownloadByNameOptions>)
+                                      -> Result<()> {
+        let mut file = try!(self.open_download_stream_by_name(filename, options));
+        try!(io::copy(&mut file, destination));
+        file.close()
+    }
+
+    /// Deletes the file with the given id and all of its chunks.
+    pub fn delete(&self, id: Bson) -> Result<()> {
+        let result = try!(self.store.files.delete_one(doc!{ "_id" => (id.clone()) }, None));
+
+        // Remove chunks even if the file document is missing, to clean up partial uploads.
+        try!(self.store.chunks.delete_many(doc!{ "files_id" => id }, None));
+
+        if result.deleted_count == 0 {
+            return Err(ArgumentError("File does not exist.".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Renames the file with the given id.
+    pub fn rename(&self, id: Bson, new_filename: &str) -> Result<()> {
+        let update = doc!{ "$set" => { "filename" => new_filename } };
+        let result = try!(self.store.files.update_one(doc!{ "_id" => id }, update, None));
+
+        if result.matched_count == 0 {
+            return Err(ArgumentError("File does not exist.".to_owned()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns a cursor over the files matching the filter.
+    pub fn find(&self, filter: Option<bson::Document>,
+                options: Option<FindOptions>) -> Result<FileCursor> {
+        self.store.find(filter, options)
+    }
+
+    /// Returns a cursor over the files whose metadata matches the filter.
+    pub fn find_by_metadata(&self, filter: bson::Document,
+                            options: Option<FindOptions>) -> Result<FileCursor> {
+        self.store.find_by_metadata(filter, options)
+    }
+
+    /// Reads every chunk of a file, checking the chunk sequence, chunk sizes and md5 hash.
+    pub fn verify(&self, id: Bson) -> Result<VerificationReport> {
+        self.store.verify(id)
+    }
+
+    /// Drops the files and chunks collections of the bucket.
+    pub fn drop(&self) -> Result<()> {
+        try!(self.store.files.drop());
+        try!(self.store.chunks.drop());
+        self.indexes_checked.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // Creates the bucket indexes before the first upload, if the files collection is empty.
+    fn ensure_indexes(&self) -> Result<()> {
+        if self.indexes_checked.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let mut options = FindOptions::new();
+        options.projection = Some(doc!{ "_id" => 1 });
+
+        // Indexes are only created while the bucket is empty, and only if they do not
+        // already exist, so that a user without createIndex privileges can still read.
+        if try!(self.store.files.find_one(None, Some(options))).is_none() {
+            let files_keys = doc!{ "filename" => 1, "uploadDate" => 1 };
+            if !try!(has_index(&self.store.files, &files_keys)) {
+                try!(self.store.files.create_index(files_keys, None));
+            }
+
+            let chunks_keys = doc!{ "files_id" => 1, "n" => 1 };
+            if !try!(has_index(&self.store.chunks, &chunks_keys)) {
+                let mut chunk_options = IndexOptions::new();
+                chunk_options.unique = Some(true);
+                try!(self.store.chunks.create_index(chunks_keys, Some(chunk_options)));
+            }
+        }
+
+        self.indexes_checked.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+// Returns true if the collection has an index on exactly the given keys. Key directions
+// are compared numerically, as other drivers may have stored them as doubles.
+fn has_index(coll: &Collection, keys: &bson::Document) -> Result<bool> {
+    fn direction(value: &Bson) -> Option<i64> {
+        match *value {
+            Bson::I32(v) => Some(v as i64),
+            Bson::I64(v) => Some(v),
+            Bson::FloatingPoint(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    for index in try!(coll.list_indexes()) {
+        let index = try!(index);
+        if let Some(&Bson::Document(ref key)) = index.get("key") {
+            let matches = key.len() == keys.len() &&
+                          key.iter().zip(keys.iter()).all(|((name, value), (other, expected))| {
+                              name == other && direction(value) == direction(expected)
+                          });
+
+            if matches {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+impl From<Store> for GridFsBucket {
+    /// Exposes an existing store through the bucket API.
+    fn from(store: Store) -> GridFsBucket {
+        GridFsBucket {
+            store: store,
+            options: GridFsBucketOptions::new(),
+            indexes_checked: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
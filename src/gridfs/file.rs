@@ -6,21 +6,22 @@ use chrono::{DateTime, UTC};
 use crypto::digest::Digest;
 use crypto::md5::Md5;
 
-use Error::{self, ArgumentError, OperationError, PoisonLockError};
+use Error::{self, ArgumentError, OperationError, PoisonLockError, ResponseError};
 use Result;
 
-use super::Store;
+use super::{Store, bound_concurrent_writes};
 use coll::options::IndexOptions;
 
 use std::{cmp, io, thread};
 use std::error::Error as ErrorTrait;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::{Deref, DerefMut};
 use std::sync::{Arc, Condvar, Mutex, RwLock};
 use std::sync::atomic::{AtomicIsize, ATOMIC_ISIZE_INIT, Ordering};
 
 pub const DEFAULT_CHUNK_SIZE: i32 = 255 * 1024;
 pub const MEGABYTE: usize = 1024 * 1024;
+pub const DEFAULT_MAX_CONCURRENT_WRITES: usize = 4;
 
 /// File modes.
 #[derive(Debug, PartialEq, Eq)]
@@ -33,6 +34,9 @@ pub enum Mode {
 // Helper class to implement a threaded mutable error.
 struct InnerError {
     inner: Option<Error>,
+    // The index of the chunk that failed, if the error occurred while inserting a chunk.
+    // Only the error of the earliest failed chunk is kept.
+    chunk: Option<i32>,
 }
 
 /// A writable or readable file stream within GridFS.
@@ -49,6 +53,8 @@ pub struct File {
     offset: i64,
     // The number of writes in progress.
     wpending: Arc<AtomicIsize>,
+    // The maximum number of writes in progress.
+    max_writes: isize,
     // The write buffer.
     wbuf: Vec<u8>,
     // The file md5 hash builder.
@@ -71,8 +77,8 @@ pub struct GfsFile {
     len: i64,
     // The md5 hash.
     md5: String,
-    // The unique object id.
-    pub id: oid::ObjectId,
+    // The unique id, usually an ObjectId, though other drivers may store any value.
+    pub id: Bson,
     // The chunk size.
     pub chunk_size: i32,
     // An array of alias strings.
@@ -84,7 +90,7 @@ pub struct GfsFile {
     // The content type of the file.
     pub content_type: Option<String>,
     // Any additional metadata provided by the user.
-    pub metadata: Option<Vec<u8>>,
+    pub metadata: Option<bson::Document>,
 }
 
 // A pre-loaded chunk.
@@ -113,18 +119,22 @@ impl DerefMut for File {
 
 impl File {
     /// A new file stream with an id-referenced GridFS file.
-    pub fn new(gfs: Store, id: oid::ObjectId, mode: Mode) -> File {
-        File::with_gfs_file(gfs, GfsFile::new(id), mode)
+    pub fn new(gfs: Store, id: Bson, mode: Mode) -> File {
+        let mut file = GfsFile::new(id);
+        file.chunk_size = gfs.chunk_size;
+        File::with_gfs_file(gfs, file, mode)
     }
 
     /// A new file stream with a name-and-id-referenced GridFS file.
-    pub fn with_name(gfs: Store, name: String, id: oid::ObjectId, mode: Mode) -> File {
-        File::with_gfs_file(gfs, GfsFile::with_name(name, id), mode)
+    pub fn with_name(gfs: Store, name: String, id: Bson, mode: Mode) -> File {
+        let mut file = GfsFile::with_name(name, id);
+        file.chunk_size = gfs.chunk_size;
+        File::with_gfs_file(gfs, file, mode)
     }
 
     /// A new file stream from a read file document.
-    pub fn with_doc(gfs: Store, doc: bson::Document) -> File {
-        File::with_gfs_file(gfs, GfsFile::with_doc(doc), Mode::Read)
+    pub fn with_doc(gfs: Store, doc: bson::Document) -> Result<File> {
+        Ok(File::with_gfs_file(gfs, try!(GfsFile::with_doc(doc)), Mode::Read))
     }
 
     // Generic new file stream.
@@ -133,16 +143,17 @@ impl File {
             mutex: Arc::new(Mutex::new(())),
             condvar: Arc::new(Condvar::new()),
             mode: mode,
-            gfs: gfs,
             chunk_num: 0,
             offset: 0,
             wpending: Arc::new(ATOMIC_ISIZE_INIT),
+            max_writes: bound_concurrent_writes(&gfs, gfs.max_concurrent_writes) as isize,
             wbuf: Vec::new(),
             wsum: Md5::new(),
             rbuf: Vec::new(),
             rcache: None,
             doc: file,
-            err: Arc::new(RwLock::new(InnerError { inner: None })),
+            gfs: gfs,
+            err: Arc::new(RwLock::new(InnerError { inner: None, chunk: None })),
         }
     }
 
@@ -196,7 +207,7 @@ impl File {
                 try!(self.gfs.files.insert_one(self.doc.to_bson(), None));
 
                 // Ensure indexes
-                try!(self.gfs.files.create_index(doc!{ "filename" => 1 }, None));
+                try!(self.gfs.files.create_index(doc!{ "filename" => 1, "uploadDate" => 1 }, None));
 
                 let mut opts = IndexOptions::new();
                 opts.unique = Some(true);
@@ -225,6 +236,44 @@ impl File {
         }
     }
 
+    /// Abandons a file being written, removing any chunks that were already stored.
+    /// The file document is never inserted.
+    pub fn abort(&mut self) -> Result<()> {
+        try!(self.assert_mode(Mode::Write));
+
+        {
+            let mut guard = try!(self.mutex.lock());
+            while self.wpending.load(Ordering::SeqCst) > 0 {
+                guard = try!(self.condvar.wait(guard));
+            }
+        }
+
+        self.wbuf.clear();
+        self.mode = Mode::Closed;
+        try!(self.gfs.chunks.delete_many(doc!{ "files_id" => (self.doc.id.clone()) }, None));
+        Ok(())
+    }
+
+    /// Reads up to `len` bytes starting at the byte `offset` of a file open for reading.
+    /// Fewer bytes are returned if the range extends past the end of the file.
+    pub fn read_range(&mut self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        try!(self.seek(SeekFrom::Start(offset)));
+
+        let remaining = cmp::max(self.doc.len - offset as i64, 0) as usize;
+        let mut buf = vec![0u8; cmp::min(len, remaining)];
+        let mut filled = 0;
+
+        while filled < buf.len() {
+            match try!(self.read(&mut buf[filled..])) {
+                0 => break,
+                n => filled += n,
+            }
+        }
+
+        buf.truncate(filled);
+        Ok(buf)
+    }
+
     /// Inserts a file chunk into GridFS.
     fn insert_chunk(&self, n: i32, buf: &[u8]) -> Result<()> {
 
@@ -250,12 +299,20 @@ impl File {
         thread::spawn(move || {
             let result = arc_gfs.chunks.insert_one(document, None);
 
-            // Complete pending write
-            let _ = arc_mutex.lock();
+            // Complete pending write, keeping the error of the earliest failed chunk. The lock
+            // is held through the notification so that a waiting writer cannot miss it.
+            let _guard = match arc_mutex.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
             arc_wpending.fetch_sub(1, Ordering::SeqCst);
-            if result.is_err() {
+            if let Err(result_err) = result {
                 if let Ok(mut err_mut) = err.write() {
-                    err_mut.inner = Some(result.err().unwrap());
+                    if err_mut.chunk.map_or(err_mut.inner.is_none(), |chunk| n < chunk) {
+                        err_mut.inner = Some(OperationError(
+                            format!("Failed to insert chunk {}: {}", n, result_err)));
+                        err_mut.chunk = Some(n);
+                    }
                 }
             }
             cvar.notify_all();
@@ -265,7 +322,7 @@ impl File {
     }
 
     // Retrieves a binary file chunk from GridFS.
-    pub fn find_chunk(&mut self, id: oid::ObjectId, chunk_num: i32) -> Result<Vec<u8>> {
+    pub fn find_chunk(&mut self, id: Bson, chunk_num: i32) -> Result<Vec<u8>> {
         let filter = doc!{"files_id" => id, "n" => chunk_num };
         match try!(self.gfs.chunks.find_one(Some(filter), None)) {
             Some(doc) => match doc.get("data") {
@@ -376,10 +433,10 @@ impl io::Write for File {
 
             let chunk_num = self.chunk_num;
             self.chunk_num += 1;
-            self.wsum.input(buf);
+            self.wsum.input(&self.wbuf);
 
-            // If over a megabyte is being written at once, wait for the load to reduce.
-            while self.doc.chunk_size * self.wpending.load(Ordering::SeqCst) as i32 >= MEGABYTE as i32 {
+            // If the maximum number of chunks are being written at once, wait for the load to reduce.
+            while self.wpending.load(Ordering::SeqCst) >= self.max_writes {
                 guard = match self.condvar.wait(guard) {
                     Ok(guard) => guard,
                     Err(_) => return Err(io::Error::new(
@@ -407,10 +464,10 @@ impl io::Write for File {
 
             let chunk_num = self.chunk_num;
             self.chunk_num += 1;
-            self.wsum.input(buf);
+            self.wsum.input(part1);
 
-            // Pending megabyte
-            while self.doc.chunk_size * self.wpending.load(Ordering::SeqCst) as i32 >= MEGABYTE as i32 {
+            // Pending writes
+            while self.wpending.load(Ordering::SeqCst) >= self.max_writes {
                 guard = match self.condvar.wait(guard) {
                     Ok(guard) => guard,
                     Err(_) => return Err(io::Error::new(
@@ -449,8 +506,8 @@ impl io::Write for File {
             self.chunk_num += 1;
             self.wsum.input(&self.wbuf);
 
-            // Pending megabyte
-            while self.doc.chunk_size * self.wpending.load(Ordering::SeqCst) as i32 >= MEGABYTE as i32 {
+            // Pending writes
+            while self.wpending.load(Ordering::SeqCst) >= self.max_writes {
                 guard = match self.condvar.wait(guard) {
                     Ok(guard) => guard,
                     Err(_) => return Err(io::Error::new(
@@ -497,12 +554,13 @@ impl io::Read for File {
         };
 
         // End of File (EOF)
-        if self.offset == self.doc.len {
+        if self.offset >= self.doc.len {
             return Ok(0);
         }
 
         // Read all required chunks into memory
-        while self.rbuf.len() < buf.len() {
+        while self.rbuf.len() < buf.len() &&
+            (self.chunk_num as i64) * (self.doc.chunk_size as i64) < self.doc.len {
             let chunk = try!(self.get_chunk());
             self.rbuf.extend(chunk);
         }
@@ -525,6 +583,55 @@ impl io::Read for File {
     }
 }
 
+impl io::Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        try!(self.assert_mode(Mode::Read));
+
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(delta) => self.doc.len + delta,
+            SeekFrom::Current(delta) => self.offset + delta,
+        };
+
+        if offset < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      ArgumentError("Cannot seek before the start of a file.".to_owned())));
+        }
+
+        if self.doc.chunk_size <= 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      ResponseError("Cannot seek within a file without a positive chunk size.".to_owned())));
+        }
+
+        {
+            let mutex = self.mutex.clone();
+            let _guard = match mutex.lock() {
+                Ok(guard) => guard,
+                Err(_) => return Err(io::Error::new(
+                    io::ErrorKind::Other, PoisonLockError)),
+            };
+
+            // Discard buffered data and any pre-loaded chunk; the pre-load thread
+            // completes on its own.
+            self.rbuf.clear();
+            self.rcache = None;
+            self.offset = offset;
+
+            let chunk_size = self.doc.chunk_size as i64;
+            self.chunk_num = (offset / chunk_size) as i32;
+
+            // Load the chunk containing the new offset and drop the bytes preceding it.
+            if offset < self.doc.len {
+                let chunk = try!(self.get_chunk());
+                let skip = cmp::min((offset % chunk_size) as usize, chunk.len());
+                self.rbuf.extend(chunk[skip..].iter().cloned());
+            }
+        }
+
+        Ok(offset as u64)
+    }
+}
+
 impl Drop for File {
     fn drop(&mut self) {
         // This ignores errors during closing; instead, the close function should be
@@ -534,8 +641,8 @@ impl Drop for File {
 }
 
 impl GfsFile {
-    /// Create a new GfsFile by ObjectId.
-    pub fn new(id: oid::ObjectId) -> GfsFile {
+    /// Create a new GfsFile by id.
+    pub fn new(id: Bson) -> GfsFile {
         GfsFile {
             id: id,
             chunk_size: DEFAULT_CHUNK_SIZE,
@@ -549,8 +656,8 @@ impl GfsFile {
         }
     }
 
-    /// Create a new GfsFile by filename and ObjectId.
-    pub fn with_name(name: String, id: oid::ObjectId) -> GfsFile {
+    /// Create a new GfsFile by filename and id.
+    pub fn with_name(name: String, id: Bson) -> GfsFile {
         GfsFile {
             id: id,
             chunk_size: DEFAULT_CHUNK_SIZE,
@@ -565,44 +672,70 @@ impl GfsFile {
     }
 
     /// Read a GridFS file document into a new GfsFile.
-    pub fn with_doc(doc: bson::Document) -> GfsFile {
-        let mut file: GfsFile;
-
-        if let Some(&Bson::ObjectId(ref id)) = doc.get("_id") {
-            file = GfsFile::new(id.clone())
-        } else {
-            panic!("Document has no _id!");
-        }
+    pub fn with_doc(doc: bson::Document) -> Result<GfsFile> {
+        let mut file = match doc.get("_id") {
+            Some(id) => GfsFile::new(id.clone()),
+            None => return Err(ResponseError("GridFS file document has no _id.".to_owned())),
+        };
 
         if let Some(&Bson::String(ref name)) = doc.get("filename") {
             file.name = Some(name.to_owned());
         }
 
-        if let Some(&Bson::I32(ref chunk_size)) = doc.get("chunkSize") {
-            file.chunk_size = *chunk_size;
+        // Other drivers may store numeric fields with any numeric type.
+        match doc.get("chunkSize") {
+            Some(&Bson::I32(chunk_size)) => file.chunk_size = chunk_size,
+            Some(&Bson::I64(chunk_size)) => file.chunk_size = chunk_size as i32,
+            Some(&Bson::FloatingPoint(chunk_size)) => file.chunk_size = chunk_size as i32,
+            _ => file.chunk_size = 0,
+        }
+
+        // Offsets within the file are located by dividing by the chunk size.
+        if file.chunk_size <= 0 {
+            return Err(ResponseError("GridFS file document has no positive chunkSize.".to_owned()));
         }
 
         if let Some(&Bson::UtcDatetime(ref datetime)) = doc.get("uploadDate") {
             file.upload_date = Some(datetime.to_owned());
         }
 
-        if let Some(&Bson::I64(ref length)) = doc.get("length") {
-            file.len = *length;
+        match doc.get("length") {
+            Some(&Bson::I32(length)) => file.len = length as i64,
+            Some(&Bson::I64(length)) => file.len = length,
+            Some(&Bson::FloatingPoint(length)) => file.len = length as i64,
+            _ => (),
         }
 
         if let Some(&Bson::String(ref hash)) = doc.get("md5") {
             file.md5 = hash.to_owned();
         }
 
+        if let Some(&Bson::Array(ref aliases)) = doc.get("aliases") {
+            file.aliases = aliases.iter().filter_map(|alias| match alias {
+                &Bson::String(ref alias) => Some(alias.to_owned()),
+                _ => None,
+            }).collect();
+        }
+
         if let Some(&Bson::String(ref content_type)) = doc.get("contentType") {
             file.content_type = Some(content_type.to_owned());
         }
 
-        if let Some(&Bson::Binary(_, ref metadata)) = doc.get("metadata") {
+        if let Some(&Bson::Document(ref metadata)) = doc.get("metadata") {
             file.metadata = Some(metadata.clone());
         }
 
-        file
+        Ok(file)
+    }
+
+    /// Returns the byte length of the file.
+    pub fn len(&self) -> i64 {
+        self.len
+    }
+
+    /// Returns the md5 hash of the file contents, as recorded when the file was stored.
+    pub fn md5(&self) -> &str {
+        &self.md5
     }
 
     /// Converts a GfsFile into a bson document.
@@ -625,10 +758,16 @@ impl GfsFile {
                        Bson::String(self.content_type.as_ref().unwrap().to_owned()));
         }
 
+        if !self.aliases.is_empty() {
+            doc.insert("aliases".to_owned(),
+                       Bson::Array(self.aliases.iter().map(|alias| {
+                           Bson::String(alias.to_owned())
+                       }).collect()));
+        }
+
         if self.metadata.is_some() {
             doc.insert("metadata".to_owned(),
-                       Bson::Binary(BinarySubtype::Generic,
-                                    self.metadata.as_ref().unwrap().clone()));
+                       Bson::Document(self.metadata.as_ref().unwrap().clone()));
         }
 
         doc
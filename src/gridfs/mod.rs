@@ -20,16 +20,19 @@
 //! let db = client.db("grid");
 //! let fs = Store::with_db(db.clone());
 //!
-//! fs.put("/path/to/local_file.mp4".to_owned()).unwrap();
+//! fs.put("/path/to/local_file.mp4".to_owned(), None).unwrap();
 //! let mut file = fs.open("/path/to/local_file.mp4".to_owned()).unwrap();
 //!
 //! let id = file.doc.id.clone();
 //! let chunk_bytes = file.find_chunk(id, 5).unwrap();
 //! file.close().unwrap();
 //! ```
+pub mod bucket;
 pub mod file;
+pub mod options;
+pub mod verify;
 
-use bson::{self, oid};
+use bson::{self, Bson, oid};
 
 use db::{Database, ThreadedDatabase};
 use coll::Collection;
@@ -38,9 +41,13 @@ use cursor::Cursor;
 use Error::{self, ArgumentError};
 use Result;
 
-use self::file::{File, Mode};
+use self::file::{DEFAULT_CHUNK_SIZE, DEFAULT_MAX_CONCURRENT_WRITES, File, Mode};
+use self::options::{GridFsStoreOptions, GridFsUploadOptions};
+use pool::DEFAULT_POOL_SIZE;
+use self::verify::{OrphanReport, VerificationReport};
 
-use std::{io, fs};
+use std::{cmp, io, fs};
+use std::io::{Read, Write};
 use std::sync::Arc;
 
 /// A default cursor wrapper that maps bson documents into GridFS file representations.
@@ -55,7 +62,13 @@ impl Iterator for FileCursor {
 
     fn next(&mut self) -> Option<File> {
         match self.cursor.next() {
-            Some(Ok(bdoc)) => Some(File::with_doc(self.store.clone(), bdoc)),
+            Some(Ok(bdoc)) => match File::with_doc(self.store.clone(), bdoc) {
+                Ok(file) => Some(file),
+                Err(err) => {
+                    self.err = Some(err);
+                    None
+                },
+            },
             Some(Err(err)) => {
                 self.err = Some(err);
                 None
@@ -69,20 +82,34 @@ impl FileCursor {
     /// Returns the next n files.
     pub fn next_n(&mut self, n: i32) -> Result<Vec<File>> {
         let docs = try!(self.cursor.next_n(n));
-        Ok(docs.into_iter().map(|doc| {
-            File::with_doc(self.store.clone(), doc.clone())
-        }).collect())
+        docs.into_iter().map(|doc| File::with_doc(self.store.clone(), doc)).collect()
     }
 
     /// Returns the next batch of files.
     pub fn next_batch(&mut self) -> Result<Vec<File>> {
         let docs = try!(self.cursor.next_batch());
-        Ok(docs.into_iter().map(|doc| {
-            File::with_doc(self.store.clone(), doc)
-        }).collect())
+        docs.into_iter().map(|doc| File::with_doc(self.store.clone(), doc)).collect()
     }
 }
 
+/// Identifies a stored file by filename or by id.
+#[derive(Clone, Debug)]
+pub enum FileSelector {
+    /// The first stored file with this filename.
+    Name(String),
+    /// The file with this id.
+    Id(Bson),
+}
+
+/// The progress of a streaming transfer, reported after each chunk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of bytes transferred so far.
+    pub bytes_transferred: u64,
+    /// The total length of the file, if known. Uploads from a reader have no known length.
+    pub total_bytes: Option<u64>,
+}
+
 /// Alias for a thread-safe GridFS instance.
 pub type Store = Arc<StoreInner>;
 
@@ -90,6 +117,10 @@ pub type Store = Arc<StoreInner>;
 pub struct StoreInner {
     files: Collection,
     chunks: Collection,
+    // The chunk size of files created through the store.
+    chunk_size: i32,
+    // The maximum number of chunk inserts in flight for a single file.
+    max_concurrent_writes: usize,
 }
 
 pub trait ThreadedStore {
@@ -97,22 +128,41 @@ pub trait ThreadedStore {
     fn with_db(db: Database) -> Store;
     /// A new GridFS store within the database with a specified prefix.
     fn with_prefix(db: Database, prefix: String) -> Store;
+    /// A new GridFS store within the database with a specified prefix and options.
+    fn with_options(db: Database, prefix: String, options: GridFsStoreOptions) -> Result<Store>;
     /// Creates a new file.
-    fn create(&self, name: String) -> Result<File>;
+    fn create(&self, name: String, options: Option<GridFsUploadOptions>) -> Result<File>;
     /// Opens a file by filename.
     fn open(&self, name: String) -> Result<File>;
-    /// Opens a file by object ID.
-    fn open_id(&self, id: oid::ObjectId) -> Result<File>;
+    /// Opens a file by id.
+    fn open_id(&self, id: Bson) -> Result<File>;
     /// Returns a cursor to all file documents matching the provided filter.
     fn find(&self, filter: Option<bson::Document>, options: Option<FindOptions>) -> Result<FileCursor>;
+    /// Returns a cursor to all file documents whose metadata matches the provided filter.
+    fn find_by_metadata(&self, filter: bson::Document, options: Option<FindOptions>) -> Result<FileCursor>;
+    /// Returns a cursor to all file documents with the provided content type.
+    fn find_by_content_type(&self, content_type: &str, options: Option<FindOptions>) -> Result<FileCursor>;
     /// Removes a file from GridFS by filename.
     fn remove(&self, name: String) -> Result<()>;
-    /// Removes a file from GridFS by object ID.
-    fn remove_id(&self, id: oid::ObjectId) -> Result<()>;
+    /// Removes a file from GridFS by id.
+    fn remove_id(&self, id: Bson) -> Result<()>;
     /// Inserts a new file from local into GridFS.
-    fn put(&self, name: String) -> Result<()>;
+    fn put(&self, name: String, options: Option<GridFsUploadOptions>) -> Result<()>;
     /// Retrieves a file from GridFS into local storage.
     fn get(&self, name: String) -> Result<()>;
+    /// Streams the contents of a reader into a new file chunk-by-chunk, returning its ID.
+    /// If a read fails, any chunks already written are removed.
+    fn put_reader<R: Read>(&self, name: String, reader: R, options: Option<GridFsUploadOptions>,
+                           progress: Option<&mut FnMut(Progress)>) -> Result<Bson>;
+    /// Streams the contents of a file into a writer chunk-by-chunk, returning the number
+    /// of bytes written.
+    fn get_writer<W: Write>(&self, file: FileSelector, writer: W,
+                            progress: Option<&mut FnMut(Progress)>) -> Result<u64>;
+    /// Reads every chunk of a file, checking the chunk sequence, chunk sizes and md5 hash.
+    fn verify(&self, id: Bson) -> Result<VerificationReport>;
+    /// Reports chunks without a file document and files with missing chunks,
+    /// deleting them if `delete` is true.
+    fn clean_orphans(&self, delete: bool) -> Result<OrphanReport>;
 }
 
 impl ThreadedStore for Store {
@@ -124,11 +174,28 @@ impl ThreadedStore for Store {
         Arc::new(StoreInner {
             files: db.collection(&format!("{}.files", prefix)[..]),
             chunks: db.collection(&format!("{}.chunks", prefix)[..]),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            max_concurrent_writes: DEFAULT_MAX_CONCURRENT_WRITES,
         })
     }
 
-    fn create(&self, name: String) -> Result<File> {
-        Ok(File::with_name(self.clone(), name, try!(oid::ObjectId::new()), Mode::Write))
+    fn with_options(db: Database, prefix: String, options: GridFsStoreOptions) -> Result<Store> {
+        if options.chunk_size_bytes <= 0 {
+            return Err(ArgumentError("GridFS chunk size must be positive.".to_owned()));
+        }
+
+        Ok(Arc::new(StoreInner {
+            files: db.collection(&format!("{}.files", prefix)[..]),
+            chunks: db.collection(&format!("{}.chunks", prefix)[..]),
+            chunk_size: options.chunk_size_bytes,
+            max_concurrent_writes: options.max_concurrent_writes,
+        }))
+    }
+
+    fn create(&self, name: String, options: Option<GridFsUploadOptions>) -> Result<File> {
+        let options = options.unwrap_or(GridFsUploadOptions::new());
+        let id = Bson::ObjectId(try!(oid::ObjectId::new()));
+        upload_file(self.clone(), name, id, self.chunk_size, &options)
     }
 
     fn open(&self, name: String) -> Result<File> {
@@ -136,14 +203,14 @@ impl ThreadedStore for Store {
         options.sort = Some(doc!{ "uploadDate" => 1 });
 
         match try!(self.files.find_one(Some(doc!{ "filename" => name }), Some(options))) {
-            Some(bdoc) => Ok(File::with_doc(self.clone(), bdoc)),
+            Some(bdoc) => File::with_doc(self.clone(), bdoc),
             None => Err(ArgumentError("File does not exist.".to_owned())),
         }
     }
 
-    fn open_id(&self, id: oid::ObjectId) -> Result<File> {
+    fn open_id(&self, id: Bson) -> Result<File> {
         match try!(self.files.find_one(Some(doc!{ "_id" => id }), None)) {
-            Some(bdoc) => Ok(File::with_doc(self.clone(), bdoc)),
+            Some(bdoc) => File::with_doc(self.clone(), bdoc),
             None => Err(ArgumentError("File does not exist.".to_owned())),
         }
     }
@@ -157,6 +224,16 @@ impl ThreadedStore for Store {
         })
     }
 
+    fn find_by_metadata(&self, filter: bson::Document, options: Option<FindOptions>)
+                        -> Result<FileCursor> {
+        self.find(Some(metadata_filter(filter)), options)
+    }
+
+    fn find_by_content_type(&self, content_type: &str, options: Option<FindOptions>)
+                            -> Result<FileCursor> {
+        self.find(Some(doc!{ "contentType" => content_type }), options)
+    }
+
     fn remove(&self, name: String) -> Result<()> {
         let mut options = FindOptions::new();
         options.projection = Some(doc!{ "_id" => 1 });
@@ -169,14 +246,14 @@ impl ThreadedStore for Store {
         Ok(())
     }
 
-    fn remove_id(&self, id: oid::ObjectId) -> Result<()> {
+    fn remove_id(&self, id: Bson) -> Result<()> {
         try!(self.files.delete_many(doc!{ "_id" => (id.clone()) }, None));
         try!(self.chunks.delete_many(doc!{ "files_id" => (id.clone()) }, None));
         Ok(())
     }
 
-    fn put(&self, name: String) -> Result<()> {
-        let mut file = try!(self.create(name.to_owned()));
+    fn put(&self, name: String, options: Option<GridFsUploadOptions>) -> Result<()> {
+        let mut file = try!(self.create(name.to_owned(), options));
         let mut f = try!(fs::File::open(name.to_owned()));
         try!(io::copy(&mut f, &mut file));
         try!(file.close());
@@ -190,4 +267,130 @@ impl ThreadedStore for Store {
         try!(file.close());
         Ok(())
     }
+
+    fn put_reader<R: Read>(&self, name: String, mut reader: R,
+                           options: Option<GridFsUploadOptions>,
+                           mut progress: Option<&mut FnMut(Progress)>) -> Result<Bson> {
+        let mut file = try!(self.create(name, options));
+        let id = file.id.clone();
+        let mut buf = vec![0u8; file.chunk_size as usize];
+        let mut transferred = 0;
+
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    try!(file.abort());
+                    return Err(Error::from(err));
+                },
+            };
+
+            try!(file.write_all(&buf[..n]));
+            transferred += n as u64;
+
+            if let Some(ref mut progress) = progress {
+                progress(Progress { bytes_transferred: transferred, total_bytes: None });
+            }
+        }
+
+        try!(file.close());
+        Ok(id)
+    }
+
+    fn get_writer<W: Write>(&self, selector: FileSelector, mut writer: W,
+                            mut progress: Option<&mut FnMut(Progress)>) -> Result<u64> {
+        let mut file = match selector {
+            FileSelector::Name(name) => try!(self.open(name)),
+            FileSelector::Id(id) => try!(self.open_id(id)),
+        };
+
+        let total = file.len() as u64;
+        let mut buf = vec![0u8; file.chunk_size as usize];
+        let mut transferred = 0;
+
+        loop {
+            let n = try!(file.read(&mut buf));
+            if n == 0 {
+                break;
+            }
+
+            try!(writer.write_all(&buf[..n]));
+            transferred += n as u64;
+
+            if let Some(ref mut progress) = progress {
+                progress(Progress { bytes_transferred: transferred, total_bytes: Some(total) });
+            }
+        }
+
+        try!(writer.flush());
+        try!(file.close());
+        Ok(transferred)
+    }
+
+    fn verify(&self, id: Bson) -> Result<VerificationReport> {
+        verify::verify_file(self, id)
+    }
+
+    fn clean_orphans(&self, delete: bool) -> Result<OrphanReport> {
+        verify::find_orphans(self, delete)
+    }
+}
+
+// Limits concurrent chunk inserts to the number of pooled connections, since each insert
+// holds a connection for its duration. Servers that have not yet been discovered are
+// assumed to use the default pool size.
+fn bound_concurrent_writes(store: &Store, max_concurrent_writes: usize) -> usize {
+    let pool_size = match store.chunks.db.client.topology.description.read() {
+        Ok(description) => description.servers.values().map(|server| server.pool_size())
+            .max().unwrap_or(DEFAULT_POOL_SIZE),
+        Err(_) => DEFAULT_POOL_SIZE,
+    };
+
+    cmp::max(1, cmp::min(max_concurrent_writes, pool_size))
+}
+
+// Opens a file for writing with the provided upload options. Options are validated before
+// the file is created, since dropping a file open for writing stores it.
+fn upload_file(store: Store, name: String, id: Bson, default_chunk_size: i32,
+               options: &GridFsUploadOptions) -> Result<File> {
+    let chunk_size = options.chunk_size_bytes.unwrap_or(default_chunk_size);
+    if chunk_size <= 0 {
+        return Err(ArgumentError("GridFS chunk size must be positive.".to_owned()));
+    }
+
+    let mut file = File::with_name(store, name, id, Mode::Write);
+    file.chunk_size = chunk_size;
+    file.content_type = options.content_type.clone();
+    file.aliases = options.aliases.clone();
+    file.metadata = options.metadata.clone();
+    Ok(file)
+}
+
+// Prefixes each top-level key of a filter with 'metadata.', so that it matches against
+// the user-provided metadata of file documents. Top-level operators such as '$and' are
+// applied recursively to their sub-filters.
+fn metadata_filter(filter: bson::Document) -> bson::Document {
+    let mut prefixed = bson::Document::new();
+
+    for (key, value) in filter.iter() {
+        if key.starts_with("$") {
+            let value = match value {
+                &Bson::Array(ref filters) => Bson::Array(filters.iter().map(|filter| {
+                    match filter {
+                        &Bson::Document(ref doc) => Bson::Document(metadata_filter(doc.clone())),
+                        other => other.clone(),
+                    }
+                }).collect()),
+                &Bson::Document(ref doc) => Bson::Document(metadata_filter(doc.clone())),
+                other => other.clone(),
+            };
+            prefixed.insert(key.to_owned(), value);
+        } else {
+            prefixed.insert(format!("metadata.{}", key), value.clone());
+        }
+    }
+
+    prefixed
 }
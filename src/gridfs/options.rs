@@ -0,0 +1,99 @@
+//! Options for GridFS bucket operations.
+use bson;
+use common::{ReadPreference, WriteConcern};
+use super::file::{DEFAULT_CHUNK_SIZE, DEFAULT_MAX_CONCURRENT_WRITES};
+
+pub const DEFAULT_BUCKET_NAME: &'static str = "fs";
+
+/// Options for a GridFS store.
+#[derive(Clone)]
+pub struct GridFsStoreOptions {
+    /// The number of bytes per chunk for files created through the store.
+    pub chunk_size_bytes: i32,
+    /// The maximum number of chunk inserts in flight for a single file. This is bounded
+    /// by the connection pool size.
+    pub max_concurrent_writes: usize,
+}
+
+/// Options for a GridFS bucket.
+#[derive(Clone)]
+pub struct GridFsBucketOptions {
+    /// The bucket name, used as the prefix of the files and chunks collections.
+    pub bucket_name: String,
+    /// The number of bytes per chunk for uploaded files.
+    pub chunk_size_bytes: i32,
+    /// The maximum number of chunk inserts in flight for a single file. This is bounded
+    /// by the connection pool size.
+    pub max_concurrent_writes: usize,
+    /// The write concern for bucket writes; defaults to the database write concern.
+    pub write_concern: Option<WriteConcern>,
+    /// The read preference for bucket reads; defaults to the database read preference.
+    pub read_preference: Option<ReadPreference>,
+}
+
+/// Options for uploading a file to a GridFS bucket or store.
+#[derive(Clone)]
+pub struct GridFsUploadOptions {
+    /// The number of bytes per chunk for this file; defaults to the bucket chunk size.
+    pub chunk_size_bytes: Option<i32>,
+    /// The MIME type of the file.
+    pub content_type: Option<String>,
+    /// Alternative names for the file.
+    pub aliases: Vec<String>,
+    /// User data stored in the `metadata` field of the file document.
+    pub metadata: Option<bson::Document>,
+}
+
+/// Options for downloading a file from a GridFS bucket by filename.
+#[derive(Clone)]
+pub struct GridFsDownloadByNameOptions {
+    /// Which revision of the file to retrieve. Revisions are numbered by upload date:
+    ///
+    /// 0 = the original stored file
+    /// 1 = the first revision
+    /// -2 = the second most recent revision
+    /// -1 = the most recent revision
+    ///
+    /// The default is -1.
+    pub revision: i32,
+}
+
+impl GridFsBucketOptions {
+    pub fn new() -> GridFsBucketOptions {
+        GridFsBucketOptions {
+            bucket_name: DEFAULT_BUCKET_NAME.to_owned(),
+            chunk_size_bytes: DEFAULT_CHUNK_SIZE,
+            max_concurrent_writes: DEFAULT_MAX_CONCURRENT_WRITES,
+            write_concern: None,
+            read_preference: None,
+        }
+    }
+}
+
+impl GridFsStoreOptions {
+    pub fn new() -> GridFsStoreOptions {
+        GridFsStoreOptions {
+            chunk_size_bytes: DEFAULT_CHUNK_SIZE,
+            max_concurrent_writes: DEFAULT_MAX_CONCURRENT_WRITES,
+        }
+    }
+}
+
+impl GridFsUploadOptions {
+    pub fn new() -> GridFsUploadOptions {
+        GridFsUploadOptions {
+            chunk_size_bytes: None,
+            content_type: None,
+            aliases: Vec::new(),
+            metadata: None,
+        }
+    }
+}
+
+impl GridFsDownloadByNameOptions {
+    pub fn new() -> GridFsDownloadByNameOptions {
+        GridFsDownloadByNameOptions {
+            revision: -1,
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Integrity verification and maintenance of GridFS stores.
+use bson::{self, Bson};
+use crypto::digest::Digest;
+use crypto::md5::Md5;
+
+use coll::options::FindOptions;
+use Error::{ArgumentError, OperationError};
+use Result;
+
+use super::Store;
+use super::file::GfsFile;
+
+use std::collections::HashSet;
+
+/// The outcome of verifying a stored file against its file document.
+#[derive(Clone, Debug)]
+pub struct VerificationReport {
+    /// The id of the verified file.
+    pub id: Bson,
+    /// The number of chunks implied by the file length and chunk size.
+    pub expected_chunks: i64,
+    /// The number of chunk documents found for the file.
+    pub found_chunks: i64,
+    /// Chunk indices missing from the sequence.
+    pub missing_chunks: Vec<i32>,
+    /// Chunk indices that are duplicated or beyond the end of the file.
+    pub extra_chunks: Vec<i32>,
+    /// Chunk indices whose data does not match the expected chunk size.
+    pub wrong_size_chunks: Vec<i32>,
+    /// The number of bytes read from in-sequence chunks.
+    pub bytes_read: i64,
+    /// The md5 hash recorded in the file document, if any.
+    pub expected_md5: Option<String>,
+    /// The md5 hash of the bytes read.
+    pub md5: String,
+}
+
+/// The outcome of scanning a store for orphaned chunks and incomplete files.
+#[derive(Clone, Debug)]
+pub struct OrphanReport {
+    /// The files_id values of chunks that have no matching file document.
+    pub orphaned_files_ids: Vec<Bson>,
+    /// The total number of chunks without a matching file document.
+    pub orphaned_chunks: i64,
+    /// The ids of file documents that are missing one or more chunks.
+    pub incomplete_files: Vec<Bson>,
+    /// Whether the orphaned chunks and incomplete files were deleted.
+    pub deleted: bool,
+}
+
+impl VerificationReport {
+    /// Returns true if the chunk sequence is complete, all chunks have the expected size,
+    /// and the md5 hash matches the recorded hash, if one was recorded.
+    pub fn is_valid(&self) -> bool {
+        let md5_valid = match self.expected_md5 {
+            Some(ref expected) => expected.to_lowercase() == self.md5,
+            None => true,
+        };
+
+        self.missing_chunks.is_empty() && self.extra_chunks.is_empty() &&
+            self.wrong_size_chunks.is_empty() && md5_valid
+    }
+}
+
+// Reads a numeric field stored as any bson numeric type.
+fn get_number(doc: &bson::Document, key: &str) -> Option<i64> {
+    match doc.get(key) {
+        Some(&Bson::I32(n)) => Some(n as i64),
+        Some(&Bson::I64(n)) => Some(n),
+        Some(&Bson::FloatingPoint(n)) => Some(n as i64),
+        _ => None,
+    }
+}
+
+// Returns the number of chunks needed to store a file.
+fn chunk_count(len: i64, chunk_size: i32) -> i64 {
+    if chunk_size <= 0 {
+        return 0;
+    }
+    let chunk_size = chunk_size as i64;
+    (len + chunk_size - 1) / chunk_size
+}
+
+/// Streams the chunks of a file in order, checking the chunk sequence and sizes and
+/// computing the md5 hash of the contents.
+pub fn verify_file(store: &Store, id: Bson) -> Result<VerificationReport> {
+    let file = match try!(store.files.find_one(Some(doc!{ "_id" => (id.clone()) }), None)) {
+        Some(doc) => try!(GfsFile::with_doc(doc)),
+        None => return Err(ArgumentError("File does not exist.".to_owned())),
+    };
+
+    let expected_chunks = chunk_count(file.len(), file.chunk_size);
+    let expected_md5 = if file.md5().is_empty() {
+        None
+    } else {
+        Some(file.md5().to_owned())
+    };
+
+    let mut report = VerificationReport {
+        id: id.clone(),
+        expected_chunks: expected_chunks,
+        found_chunks: 0,
+        missing_chunks: Vec::new(),
+        extra_chunks: Vec::new(),
+        wrong_size_chunks: Vec::new(),
+        bytes_read: 0,
+        expected_md5: expected_md5,
+        md5: String::new(),
+    };
+
+    let mut options = FindOptions::new();
+    options.sort = Some(doc!{ "n" => 1 });
+
+    let cursor = try!(store.chunks.find(Some(doc!{ "files_id" => id }), Some(options)));
+    let mut md5 = Md5::new();
+    let mut next: i64 = 0;
+
+    for result in cursor {
+        let chunk = try!(result);
+        report.found_chunks += 1;
+
+        let n = match get_number(&chunk, "n") {
+            Some(n) => n,
+            None => return Err(OperationError("Chunk has no index.".to_owned())),
+        };
+
+        // Duplicates and chunks past the end of the file are not part of the contents.
+        if n < next || n >= expected_chunks {
+            report.extra_chunks.push(n as i32);
+            continue;
+        }
+
+        while next < n {
+            report.missing_chunks.push(next as i32);
+            next += 1;
+        }
+
+        let data = match chunk.get("data") {
+            Some(&Bson::Binary(_, ref data)) => data,
+            _ => return Err(OperationError("Chunk contained no data.".to_owned())),
+        };
+
+        let expected_size = if n == expected_chunks - 1 {
+            file.len() - n * file.chunk_size as i64
+        } else {
+            file.chunk_size as i64
+        };
+
+        if data.len() as i64 != expected_size {
+            report.wrong_size_chunks.push(n as i32);
+        }
+
+        md5.input(data);
+        report.bytes_read += data.len() as i64;
+        next += 1;
+    }
+
+    while next < expected_chunks {
+        report.missing_chunks.push(next as i32);
+        next += 1;
+    }
+
+    report.md5 = md5.result_str();
+    Ok(report)
+}
+
+/// Finds chunks that have no file document and file documents that are missing chunks,
+/// deleting both if requested.
+///
+/// Files that are still being uploaded have chunks but no file document until they are
+/// closed, so this should not be run with `delete` while uploads are in progress.
+pub fn find_orphans(store: &Store, delete: bool) -> Result<OrphanReport> {
+    let mut report = OrphanReport {
+        orphaned_files_ids: Vec::new(),
+        orphaned_chunks: 0,
+        incomplete_files: Vec::new(),
+        deleted: delete,
+    };
+
+    // Chunks whose files_id has no matching file document.
+    let pipeline = vec![doc!{ "$group" => { "_id" => "$files_id", "count" => { "$sum" => 1 } } }];
+    for result in try!(store.chunks.aggregate(pipeline, None)) {
+        let group = try!(result);
+        let files_id = match group.get("_id") {
+            Some(files_id) => files_id.clone(),
+            None => continue,
+        };
+
+        let mut filter = bson::Document::new();
+        filter.insert("_id".to_owned(), files_id.clone());
+
+        if try!(store.files.find_one(Some(filter), None)).is_none() {
+            report.orphaned_chunks += get_number(&group, "count").unwrap_or(0);
+            report.orphaned_files_ids.push(files_id);
+        }
+    }
+
+    // File documents without all of their chunks.
+    let mut options = FindOptions::new();
+    options.projection = Some(doc!{ "_id" => 1, "length" => 1, "chunkSize" => 1 });
+
+    for result in try!(store.files.find(None, Some(options))) {
+        let doc = try!(result);
+        let id = match doc.get("_id") {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+
+        let len = get_number(&doc, "length").unwrap_or(0);
+        let chunk_size = get_number(&doc, "chunkSize").unwrap_or(0) as i32;
+        let expected = chunk_count(len, chunk_size);
+
+        let mut filter = bson::Document::new();
+        filter.insert("files_id".to_owned(), id.clone());
+        filter.insert("n".to_owned(), Bson::Document(doc!{ "$gte" => 0, "$lt" => expected }));
+
+        // Count each chunk index once, so that a duplicate chunk cannot hide a missing one.
+        let found: HashSet<i64> = try!(store.chunks.distinct("n", Some(filter), None))
+            .into_iter()
+            .filter_map(|n| match n {
+                Bson::I32(n) => Some(n as i64),
+                Bson::I64(n) => Some(n),
+                Bson::FloatingPoint(n) if n.fract() == 0.0 => Some(n as i64),
+                _ => None,
+            })
+            .collect();
+
+        if (found.len() as i64) < expected {
+            report.incomplete_files.push(id);
+        }
+    }
+
+    if delete {
+        for files_id in report.orphaned_files_ids.iter().chain(report.incomplete_files.iter()) {
+            let mut filter = bson::Document::new();
+            filter.insert("files_id".to_owned(), files_id.clone());
+            try!(store.chunks.delete_many(filter, None));
+        }
+
+        for id in report.incomplete_files.iter() {
+            let mut filter = bson::Document::new();
+            filter.insert("_id".to_owned(), id.clone());
+            try!(store.files.delete_one(filter, None));
+        }
+    }
+
+    Ok(report)
+}
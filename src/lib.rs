@@ -86,6 +86,13 @@
 //!
 //! Each server within a MongoDB server set is maintained by the driver with a separate connection
 //! pool. By default, each pool has a maximum of 5 concurrent open connections.
+//!
+//! ## TLS
+//!
+//! With the `ssl` feature enabled, `ClientOptions::with_ssl` configures clients to connect over
+//! TLS, optionally presenting a client certificate. Each new connection completes its TLS
+//! handshake before it is authenticated, so `authMechanism=MONGODB-X509` authenticates against
+//! '$external' with the subject of the configured certificate.
 
 #[doc(html_root_url = "https://mongodbinc-interns.github.io/mongo-rust-driver-prototype")]
 
@@ -94,6 +101,8 @@ extern crate bson;
 extern crate byteorder;
 extern crate chrono;
 extern crate crypto;
+#[cfg(feature = "ssl")]
+extern crate openssl;
 extern crate rand;
 extern crate rustc_serialize;
 #[macro_use]
@@ -101,15 +110,19 @@ extern crate scan_fmt;
 extern crate separator;
 extern crate textnonce;
 extern crate time;
+extern crate unicode_normalization;
 
 pub mod db;
 pub mod coll;
+pub mod cancel;
 pub mod common;
 pub mod connstring;
 pub mod cursor;
 pub mod error;
 pub mod gridfs;
 pub mod pool;
+pub mod stats;
+pub mod stream;
 pub mod topology;
 pub mod wire_protocol;
 
@@ -118,22 +131,28 @@ mod auth;
 mod command_type;
 
 pub use apm::{CommandStarted, CommandResult};
+pub use auth::{AuthMechanism, Credential};
 pub use command_type::CommandType;
 pub use error::{Error, ErrorCode, Result};
 
 use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::ops::DerefMut;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::sync::atomic::{AtomicIsize, Ordering, ATOMIC_ISIZE_INIT};
 
 use apm::Listener;
+use auth::{ConnectionAuth, ScramCache};
 use bson::Bson;
-use common::{ReadPreference, ReadMode, WriteConcern};
+use common::{Deadline, ReadPreference, ReadMode, WriteConcern};
 use connstring::ConnectionString;
 use db::{Database, ThreadedDatabase};
-use error::Error::ResponseError;
+use db::options::ListDatabasesOptions;
+use db::specs::DatabaseSpecification;
+use error::Error::{ArgumentError, ResponseError};
 use pool::PooledStream;
+use stats::{CurrentOperation, ServerStatus};
+use stream::StreamConnector;
 use topology::{Topology, TopologyDescription, TopologyType,
                DEFAULT_HEARTBEAT_FREQUENCY_MS, DEFAULT_LOCAL_THRESHOLD_MS, DEFAULT_SERVER_SELECTION_TIMEOUT_MS};
 use topology::server::Server;
@@ -144,8 +163,16 @@ pub struct ClientInner {
     pub read_preference: ReadPreference,
     /// Describes the guarantees provided by MongoDB when reporting the success of a write operation.
     pub write_concern: WriteConcern,
+    /// The client-level time limit in milliseconds for an entire operation, if any.
+    pub timeout_ms: Option<i64>,
+    /// Whether mechanisms that send cleartext passwords may run over unencrypted connections.
+    pub allow_insecure_auth: bool,
+    /// Describes how new connections to servers are established.
+    pub stream_connector: StreamConnector,
     req_id: Arc<AtomicIsize>,
     topology: Topology,
+    credential: RwLock<Option<Credential>>,
+    scram_cache: ScramCache,
     listener: Listener,
     log_file: Option<Mutex<File>>,
 }
@@ -164,6 +191,14 @@ pub struct ClientOptions {
     pub server_selection_timeout_ms: i64,
     /// The size of the latency window for selecting suitable servers; default 15 ms.
     pub local_threshold_ms: i64,
+    /// Time limit for an entire operation, including server selection, connection checkout,
+    /// and network round trips; default None, which does not limit operations.
+    pub timeout_ms: Option<i64>,
+    /// Allows mechanisms that send cleartext passwords, such as PLAIN, to authenticate over
+    /// connections without TLS; default false.
+    pub allow_insecure_auth: bool,
+    /// Describes how new connections to servers are established; default plain TCP.
+    pub stream_connector: StreamConnector,
 }
 
 impl ClientOptions {
@@ -176,6 +211,9 @@ impl ClientOptions {
             heartbeat_frequency_ms: DEFAULT_HEARTBEAT_FREQUENCY_MS,
             server_selection_timeout_ms: DEFAULT_SERVER_SELECTION_TIMEOUT_MS,
             local_threshold_ms: DEFAULT_LOCAL_THRESHOLD_MS,
+            timeout_ms: None,
+            allow_insecure_auth: false,
+            stream_connector: StreamConnector::Tcp,
         }
     }
 
@@ -185,6 +223,22 @@ impl ClientOptions {
         options.log_file = Some(file.to_owned());
         options
     }
+
+    /// Creates a new options struct that connects to servers over TLS, verifying them against
+    /// the certificate authorities in `ca_file` and presenting the client certificate in
+    /// `certificate_file`, if any. Requires the `ssl` feature.
+    #[cfg(feature = "ssl")]
+    pub fn with_ssl(ca_file: Option<&str>, certificate_file: Option<&str>,
+                    key_file: Option<&str>, verify_peer: bool) -> ClientOptions {
+        let mut options = ClientOptions::new();
+        options.stream_connector = StreamConnector::Ssl {
+            ca_file: ca_file.map(|file| file.to_owned()),
+            certificate_file: certificate_file.map(|file| file.to_owned()),
+            key_file: key_file.map(|file| file.to_owned()),
+            verify_peer: verify_peer,
+        };
+        options
+    }
 }
 
 pub trait ThreadedClient: Sync + Sized {
@@ -208,17 +262,35 @@ pub trait ThreadedClient: Sync + Sized {
     fn db_with_prefs(&self, db_name: &str, read_preference: Option<ReadPreference>,
                      write_concern: Option<WriteConcern>) -> Database;
     /// Acquires a connection stream from the pool, along with slave_ok and should_send_read_pref.
-    fn acquire_stream(&self, read_pref: ReadPreference) -> Result<(PooledStream, bool, bool)>;
+    fn acquire_stream(&self, read_pref: ReadPreference,
+                      deadline: &Deadline) -> Result<(PooledStream, bool, bool)>;
     /// Acquires a connection stream from the pool for write operations.
-    fn acquire_write_stream(&self) -> Result<PooledStream>;
+    fn acquire_write_stream(&self, deadline: &Deadline) -> Result<PooledStream>;
     /// Returns a unique operational request id.
     fn get_req_id(&self) -> i32;
     /// Returns a list of all database names that exist on the server.
     fn database_names(&self) -> Result<Vec<String>>;
+    /// Returns descriptions of the databases that match the filter.
+    fn list_databases(&self, filter: Option<bson::Document>,
+                      name_only: bool) -> Result<Vec<DatabaseSpecification>>;
+    /// Returns descriptions of the databases that match the filter, with custom options.
+    fn list_databases_with_options(&self, filter: Option<bson::Document>,
+                                   options: ListDatabasesOptions) -> Result<Vec<DatabaseSpecification>>;
     /// Drops the database defined by `db_name`.
     fn drop_database(&self, db_name: &str) -> Result<()>;
     /// Reports whether this instance is a primary, master, mongos, or standalone mongod instance.
     fn is_master(&self) -> Result<bool>;
+    /// Returns the state of the primary, or of the server selected by the client's read
+    /// preference, as reported by serverStatus.
+    fn server_status(&self) -> Result<ServerStatus>;
+    /// Returns the operations in progress that match the filter, as reported by currentOp.
+    fn current_op(&self, filter: Option<bson::Document>) -> Result<Vec<CurrentOperation>>;
+    /// Terminates the operation with the given id, as reported by `current_op`.
+    fn kill_op(&self, opid: Bson) -> Result<()>;
+    /// Replaces the credential used to authenticate new connections. Idle connections are
+    /// closed, and connections in use are closed when returned, so that operations
+    /// reconnect with the new credential.
+    fn set_credential(&self, credential: Option<Credential>) -> Result<()>;
     /// Sets a function to be run every time a command starts.
     fn add_start_hook(&mut self, hook: fn(Client, &CommandStarted)) -> Result<()>;
     /// Sets a function to be run every time a command completes.
@@ -259,6 +331,13 @@ impl ThreadedClient for Client {
 
         let rp = client_options.read_preference.unwrap_or(ReadPreference::new(ReadMode::Primary, None));
         let wc = client_options.write_concern.unwrap_or(WriteConcern::new());
+        try!(wc.validate());
+
+        if client_options.timeout_ms.map_or(false, |ms| ms < 0) {
+            return Err(ArgumentError("timeoutMS cannot be negative.".to_owned()));
+        }
+
+        let credential = try!(Credential::from_config(&config));
 
         let listener = Listener::new();
         let file = match client_options.log_file {
@@ -273,9 +352,14 @@ impl ThreadedClient for Client {
         let client = Arc::new(ClientInner {
             req_id: Arc::new(ATOMIC_ISIZE_INIT),
             topology: try!(Topology::new(config.clone(), description)),
+            credential: RwLock::new(credential),
+            scram_cache: ScramCache::new(),
             listener: listener,
             read_preference: rp,
             write_concern: wc,
+            timeout_ms: client_options.timeout_ms,
+            allow_insecure_auth: client_options.allow_insecure_auth,
+            stream_connector: client_options.stream_connector,
             log_file: file,
         });
 
@@ -305,12 +389,16 @@ impl ThreadedClient for Client {
         Database::open(self.clone(), db_name, read_preference, write_concern)
     }
 
-    fn acquire_stream(&self, read_preference: ReadPreference) -> Result<(PooledStream, bool, bool)> {
-        self.topology.acquire_stream(read_preference)
+    fn acquire_stream(&self, read_preference: ReadPreference,
+                      deadline: &Deadline) -> Result<(PooledStream, bool, bool)> {
+        // Read the credential before server selection takes any topology lock.
+        let auth = try!(ConnectionAuth::read(self));
+        self.topology.acquire_stream(read_preference, deadline, &auth)
     }
 
-    fn acquire_write_stream(&self) -> Result<PooledStream> {
-        self.topology.acquire_write_stream()
+    fn acquire_write_stream(&self, deadline: &Deadline) -> Result<PooledStream> {
+        let auth = try!(ConnectionAuth::read(self));
+        self.topology.acquire_write_stream(deadline, &auth)
     }
 
     fn get_req_id(&self) -> i32 {
@@ -318,22 +406,52 @@ impl ThreadedClient for Client {
     }
 
     fn database_names(&self) -> Result<Vec<String>> {
+        let specs = try!(self.list_databases(None, true));
+        Ok(specs.into_iter().map(|spec| spec.name).collect())
+    }
+
+    fn list_databases(&self, filter: Option<bson::Document>,
+                      name_only: bool) -> Result<Vec<DatabaseSpecification>> {
+        let mut options = ListDatabasesOptions::new();
+        options.name_only = name_only;
+        self.list_databases_with_options(filter, options)
+    }
+
+    fn list_databases_with_options(&self, filter: Option<bson::Document>,
+                                   options: ListDatabasesOptions) -> Result<Vec<DatabaseSpecification>> {
         let mut doc = bson::Document::new();
         doc.insert("listDatabases".to_owned(), Bson::I32(1));
 
+        if let Some(filter) = filter {
+            doc.insert("filter".to_owned(), Bson::Document(filter));
+        }
+
+        if options.name_only {
+            doc.insert("nameOnly".to_owned(), Bson::Boolean(true));
+        }
+
+        if let Some(authorized) = options.authorized_databases {
+            doc.insert("authorizedDatabases".to_owned(), Bson::Boolean(authorized));
+        }
+
         let db = self.db("admin");
         let res = try!(db.command(doc, CommandType::ListDatabases, None));
         if let Some(&Bson::Array(ref batch)) = res.get("databases") {
-            // Extract database names
-            let map = batch.iter().filter_map(|bdoc| {
-                if let &Bson::Document(ref doc) = bdoc {
-                    if let Some(&Bson::String(ref name)) = doc.get("name") {
-                        return Some(name.to_owned());
-                    }
+            let mut specs = vec![];
+
+            for bdoc in batch.iter() {
+                let spec = match bdoc {
+                    &Bson::Document(ref doc) => DatabaseSpecification::with_doc(doc),
+                    _ => None,
+                };
+
+                match spec {
+                    Some(spec) => specs.push(spec),
+                    None => return Err(ResponseError(format!("Invalid database specification: {:?}", bdoc))),
                 }
-                None
-            }).collect();
-            return Ok(map)
+            }
+
+            return Ok(specs)
         }
 
         Err(ResponseError("Server reply does not contain 'databases'.".to_owned()))
@@ -358,6 +476,59 @@ impl ThreadedClient for Client {
         }
     }
 
+    fn server_status(&self) -> Result<ServerStatus> {
+        let db = self.db("admin");
+        let res = try!(db.command(doc! { "serverStatus" => 1 }, CommandType::ServerStatus, None));
+        Ok(ServerStatus::with_doc(res))
+    }
+
+    fn current_op(&self, filter: Option<bson::Document>) -> Result<Vec<CurrentOperation>> {
+        let mut doc = doc! { "currentOp" => 1 };
+
+        if let Some(filter) = filter {
+            for (key, value) in filter.into_iter() {
+                doc.insert(key, value);
+            }
+        }
+
+        let db = self.db("admin");
+        let res = try!(db.command(doc, CommandType::CurrentOp, None));
+
+        match res.get("inprog") {
+            Some(&Bson::Array(ref ops)) => Ok(ops.iter().filter_map(|op| match op {
+                &Bson::Document(ref op) => Some(CurrentOperation::with_doc(op.clone())),
+                _ => None,
+            }).collect()),
+            _ => Err(ResponseError("Server reply does not contain 'inprog'.".to_owned())),
+        }
+    }
+
+    fn kill_op(&self, opid: Bson) -> Result<()> {
+        let mut doc = doc! { "killOp" => 1 };
+        doc.insert("op".to_owned(), opid);
+
+        let db = self.db("admin");
+        try!(db.command(doc, CommandType::KillOp, None));
+        Ok(())
+    }
+
+    fn set_credential(&self, credential: Option<Credential>) -> Result<()> {
+        {
+            let mut locked = try!(self.credential.write());
+            *locked = credential;
+        }
+
+        // Forget keys derived from the previous secret.
+        self.scram_cache.clear();
+
+        let description = try!(self.topology.description.read());
+        for (_, server) in description.servers.iter() {
+            server.clear_pool();
+        }
+
+        Ok(())
+    }
+
     fn add_start_hook(&mut self, hook: fn(Client, &CommandStarted)) -> Result<()> {
         self.listener.add_start_hook(hook)
     }
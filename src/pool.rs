@@ -2,14 +2,22 @@
 use Error::{ArgumentError, OperationError};
 use Result;
 
+use auth::ConnectionAuth;
+use common::Deadline;
 use connstring::Host;
+use stream::{Stream, StreamConnector};
 
-use std::net::TcpStream;
-use std::sync::{Arc, Condvar, Mutex};
-use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 
 pub static DEFAULT_POOL_SIZE: usize = 5;
 
+/// A function run on each newly connected socket before it is used, such as authentication.
+/// It is passed the credential read by the operation before server selection, and runs after
+/// any TLS handshake.
+pub type Handshake = Box<Fn(&Stream, &ConnectionAuth) -> Result<()> + Send + Sync>;
+
 /// Handles threaded connections to a MongoDB server.
 #[derive(Clone)]
 pub struct ConnectionPool {
@@ -20,6 +28,12 @@ pub struct ConnectionPool {
     // A condition variable used for threads waiting for the pool
     // to be repopulated with available connections.
     wait_lock: Arc<Condvar>,
+    // The number of streams currently checked out of the pool for operations.
+    operation_count: Arc<AtomicUsize>,
+    // Prepares new connections before they are handed out.
+    handshake: Arc<RwLock<Option<Handshake>>>,
+    // Establishes TLS on new connections, if configured.
+    connector: StreamConnector,
 }
 
 struct Pool {
@@ -28,7 +42,7 @@ struct Pool {
     // The current number of open connections.
     pub len: Arc<AtomicUsize>,
     // The idle socket pool.
-    sockets: Vec<TcpStream>,
+    sockets: Vec<Stream>,
     // The pool iteration. When a server monitor fails to execute ismaster,
     // the connection pool is cleared and the iteration is incremented.
     iteration: usize,
@@ -39,29 +53,54 @@ struct Pool {
 pub struct PooledStream {
     // This socket option will always be Some(stream) until it is
     // returned to the pool using take().
-    socket: Option<TcpStream>,
+    socket: Option<Stream>,
     // A reference to the pool that the stream was taken from.
     pool: Arc<Mutex<Pool>>,
     // A reference to the waiting condvar associated with the pool.
     wait_lock: Arc<Condvar>,
     // The pool iteration at the moment of extraction.
     iteration: usize,
+    // The in-flight operation counter of the originating pool.
+    operation_count: Arc<AtomicUsize>,
+    // The host of the originating pool.
+    host: Host,
+    // If true, the socket was interrupted and will be closed instead of returned to the pool.
+    discarded: AtomicBool,
 }
 
 impl PooledStream {
     /// Returns a reference to the socket.
-    pub fn get_socket<'a>(&'a self) -> &'a TcpStream {
+    pub fn get_socket<'a>(&'a self) -> &'a Stream {
         self.socket.as_ref().unwrap()
     }
+
+    /// Returns the host that the socket is connected to.
+    pub fn host(&self) -> &Host {
+        &self.host
+    }
+
+    /// Marks the socket as unusable, so that it is closed rather than returned to the pool
+    /// when the stream is dropped. Used when an operation is interrupted mid-flight.
+    pub fn discard(&self) {
+        self.discarded.store(true, Ordering::SeqCst);
+    }
 }
 
 impl Drop for PooledStream {
     fn drop(&mut self) {
+        // The operation using this stream has completed.
+        self.operation_count.fetch_sub(1, Ordering::SeqCst);
+
         // Attempt to lock and return the socket to the pool,
         // or give up if the pool lock has been poisoned.
         if let Ok(mut locked) = self.pool.lock() {
             if self.iteration == locked.iteration {
-                locked.sockets.push(self.socket.take().unwrap());
+                if self.discarded.load(Ordering::SeqCst) {
+                    // Free the slot so that a replacement connection can be opened.
+                    let _ = locked.len.fetch_sub(1, Ordering::SeqCst);
+                } else {
+                    locked.sockets.push(self.socket.take().unwrap());
+                }
                 // Notify waiting threads that the pool has been repopulated.
                 self.wait_lock.notify_one();
             }
@@ -78,9 +117,18 @@ impl ConnectionPool {
 
     /// Returns a connection pool with a specified capped size.
     pub fn with_size(host: Host, size: usize) -> ConnectionPool {
+        ConnectionPool::with_connector(host, size, StreamConnector::Tcp)
+    }
+
+    /// Returns a connection pool with a specified capped size, whose connections
+    /// are established by the given connector.
+    pub fn with_connector(host: Host, size: usize, connector: StreamConnector) -> ConnectionPool {
         ConnectionPool {
             host: host,
             wait_lock: Arc::new(Condvar::new()),
+            operation_count: Arc::new(ATOMIC_USIZE_INIT),
+            handshake: Arc::new(RwLock::new(None)),
+            connector: connector,
             inner: Arc::new(Mutex::new(Pool {
                 len: Arc::new(ATOMIC_USIZE_INIT),
                 size: size,
@@ -101,6 +149,27 @@ impl ConnectionPool {
         }
     }
 
+    /// Returns the maximum number of open connections.
+    pub fn size(&self) -> usize {
+        match self.inner.lock() {
+            Ok(locked) => locked.size,
+            Err(_) => DEFAULT_POOL_SIZE,
+        }
+    }
+
+    /// Returns the number of streams currently in use by operations.
+    pub fn operation_count(&self) -> usize {
+        self.operation_count.load(Ordering::SeqCst)
+    }
+
+    /// Sets the handshake run on each new connection. Existing connections are unaffected
+    /// until the pool is cleared.
+    pub fn set_handshake(&self, handshake: Handshake) -> Result<()> {
+        let mut locked = try!(self.handshake.write());
+        *locked = Some(handshake);
+        Ok(())
+    }
+
     // Clear all open socket connections.
     pub fn clear(&self) {
         if let Ok(mut locked) = self.inner.lock() {
@@ -112,8 +181,17 @@ impl ConnectionPool {
     
     /// Attempts to acquire a connected socket. If none are available and
     /// the pool has not reached its maximum size, a new socket will connect.
-    /// Otherwise, the function will block until a socket is returned to the pool.
-    pub fn acquire_stream(&self) -> Result<PooledStream> {
+    /// Otherwise, the function will block until a socket is returned to the pool,
+    /// or until the deadline expires. The socket's timeouts are bounded by the deadline.
+    pub fn acquire_stream(&self, deadline: &Deadline) -> Result<PooledStream> {
+        self.acquire_authenticated_stream(deadline, &ConnectionAuth::none())
+    }
+
+    /// Acquires a socket as `acquire_stream` does, passing the credential to the handshake
+    /// of any new connection. The caller must not hold a topology lock, as the handshake
+    /// may take several round trips.
+    pub fn acquire_authenticated_stream(&self, deadline: &Deadline,
+                                        auth: &ConnectionAuth) -> Result<PooledStream> {
         let mut locked = try!(self.inner.lock());
         if locked.size == 0 {
             return Err(OperationError("The connection pool does not allow connections; \
@@ -123,37 +201,114 @@ impl ConnectionPool {
         loop {
             // Acquire available existing socket
             if let Some(stream) = locked.sockets.pop() {
-                return Ok(PooledStream {
-                    socket: Some(stream),
-                    pool: self.inner.clone(),
-                    wait_lock: self.wait_lock.clone(),
-                    iteration: locked.iteration,
-                });
+                return self.pooled_stream(stream, locked.iteration, deadline);
             }
 
-            // Attempt to make a new connection
+            // Reserve a slot for a new connection, then connect and run the handshake
+            // without holding the pool lock, so that other threads can take idle sockets.
             let len = locked.len.load(Ordering::SeqCst);
             if len < locked.size {
-                let socket = try!(self.connect());
                 let _ = locked.len.fetch_add(1, Ordering::SeqCst);
-                return Ok(PooledStream {
-                    socket: Some(socket),
-                    pool: self.inner.clone(),
-                    wait_lock: self.wait_lock.clone(),
-                    iteration: locked.iteration,
-                });
+                let iteration = locked.iteration;
+                drop(locked);
+
+                return match self.connect(deadline, auth) {
+                    Ok(socket) => self.pooled_stream(socket, iteration, deadline),
+                    Err(err) => {
+                        self.release_slot(iteration);
+                        Err(err)
+                    },
+                };
             }
 
             // Release lock and wait for pool to be repopulated
-            locked = try!(self.wait_lock.wait(locked));
+            locked = match try!(deadline.socket_timeout()) {
+                Some(timeout) => try!(self.wait_lock.wait_timeout(locked, timeout)).0,
+                None => try!(self.wait_lock.wait(locked)),
+            };
         }
     }
 
-    // Connects to a MongoDB server as defined by the initial configuration.
-    fn connect(&self) -> Result<TcpStream> {
+    // Frees a slot reserved for a connection that failed to open, and wakes a waiting thread
+    // so that it can try to connect instead.
+    fn release_slot(&self, iteration: usize) {
+        if let Ok(locked) = self.inner.lock() {
+            if locked.iteration == iteration {
+                let _ = locked.len.fetch_sub(1, Ordering::SeqCst);
+            }
+            self.wait_lock.notify_one();
+        }
+    }
+
+    // Wraps a socket for an operation, marking the operation as in-flight.
+    fn pooled_stream(&self, socket: Stream, iteration: usize,
+                     deadline: &Deadline) -> Result<PooledStream> {
+
+        let _ = self.operation_count.fetch_add(1, Ordering::SeqCst);
+        let stream = PooledStream {
+            socket: Some(socket),
+            pool: self.inner.clone(),
+            wait_lock: self.wait_lock.clone(),
+            iteration: iteration,
+            operation_count: self.operation_count.clone(),
+            host: self.host.clone(),
+            discarded: AtomicBool::new(false),
+        };
+
+        // Reset socket timeouts left over from previous operations.
+        let timeout = try!(deadline.socket_timeout());
+        try!(stream.get_socket().set_read_timeout(timeout));
+        try!(stream.get_socket().set_write_timeout(timeout));
+        Ok(stream)
+    }
+
+    // Connects to a MongoDB server as defined by the initial configuration,
+    // giving up on each resolved address once the deadline passes.
+    fn connect(&self, deadline: &Deadline, auth: &ConnectionAuth) -> Result<Stream> {
         let ref host_name = self.host.host_name;
         let port = self.host.port;
-        let stream = try!(TcpStream::connect((&host_name[..], port)));
+
+        let stream = match try!(deadline.socket_timeout()) {
+            None => try!(TcpStream::connect((&host_name[..], port))),
+            Some(_) => {
+                let mut last_err = None;
+                let mut connected = None;
+
+                for addr in try!((&host_name[..], port).to_socket_addrs()) {
+                    let timeout = match try!(deadline.socket_timeout()) {
+                        Some(timeout) => timeout,
+                        None => break,
+                    };
+
+                    match TcpStream::connect_timeout(&addr, timeout) {
+                        Ok(stream) => {
+                            connected = Some(stream);
+                            break;
+                        },
+                        Err(err) => last_err = Some(err),
+                    }
+                }
+
+                match (connected, last_err) {
+                    (Some(stream), _) => stream,
+                    (None, Some(err)) => return Err(deadline.map_err(From::from(err))),
+                    (None, None) => return Err(OperationError(
+                        format!("Failed to resolve host {}.", host_name))),
+                }
+            },
+        };
+
+        // Bound the TLS handshake by the deadline, then authenticate over the secured stream.
+        let timeout = try!(deadline.socket_timeout());
+        try!(stream.set_read_timeout(timeout));
+        try!(stream.set_write_timeout(timeout));
+        let stream = try!(self.connector.wrap(stream, host_name)
+                              .map_err(|err| deadline.map_err(err)));
+
+        if let Some(ref handshake) = *try!(self.handshake.read()) {
+            try!(handshake(&stream, auth));
+        }
+
         Ok(stream)
     }
 }
@@ -0,0 +1,288 @@
+//! Typed results for the dbStats, collStats, serverStatus and currentOp commands.
+//!
+//! The reported fields vary across server versions and storage engines. Counters that every
+//! supported server reports default to zero if missing, fields that only some servers report
+//! are optional, and the full reply is kept in `raw` for anything else.
+use bson::{self, Bson};
+
+use std::collections::BTreeMap;
+
+// Reads a number, whichever BSON number type the server used.
+fn to_i64(bson: &Bson) -> Option<i64> {
+    match bson {
+        &Bson::I32(v) => Some(v as i64),
+        &Bson::I64(v) => Some(v),
+        &Bson::FloatingPoint(v) => Some(v as i64),
+        _ => None,
+    }
+}
+
+fn get_i64(doc: &bson::Document, key: &str) -> Option<i64> {
+    doc.get(key).and_then(to_i64)
+}
+
+fn get_f64(doc: &bson::Document, key: &str) -> Option<f64> {
+    match doc.get(key) {
+        Some(&Bson::I32(v)) => Some(v as f64),
+        Some(&Bson::I64(v)) => Some(v as f64),
+        Some(&Bson::FloatingPoint(v)) => Some(v),
+        _ => None,
+    }
+}
+
+fn get_string(doc: &bson::Document, key: &str) -> String {
+    match doc.get(key) {
+        Some(&Bson::String(ref s)) => s.to_owned(),
+        _ => String::new(),
+    }
+}
+
+fn get_doc<'a>(doc: &'a bson::Document, key: &str) -> Option<&'a bson::Document> {
+    match doc.get(key) {
+        Some(&Bson::Document(ref doc)) => Some(doc),
+        _ => None,
+    }
+}
+
+/// Storage statistics for a database, as reported by dbStats.
+#[derive(Clone)]
+pub struct DatabaseStats {
+    pub db: String,
+    pub collections: i64,
+    /// The number of views, reported by MongoDB 3.4 and later.
+    pub views: Option<i64>,
+    pub objects: i64,
+    pub avg_obj_size: f64,
+    pub data_size: i64,
+    pub storage_size: i64,
+    pub indexes: i64,
+    pub index_size: i64,
+    /// Filesystem usage, reported by MongoDB 3.6 and later.
+    pub fs_used_size: Option<i64>,
+    pub fs_total_size: Option<i64>,
+    /// The full command reply.
+    pub raw: bson::Document,
+}
+
+impl DatabaseStats {
+    /// Reads the statistics from a dbStats reply.
+    pub fn with_doc(doc: bson::Document) -> DatabaseStats {
+        DatabaseStats {
+            db: get_string(&doc, "db"),
+            collections: get_i64(&doc, "collections").unwrap_or(0),
+            views: get_i64(&doc, "views"),
+            objects: get_i64(&doc, "objects").unwrap_or(0),
+            avg_obj_size: get_f64(&doc, "avgObjSize").unwrap_or(0.0),
+            data_size: get_i64(&doc, "dataSize").unwrap_or(0),
+            storage_size: get_i64(&doc, "storageSize").unwrap_or(0),
+            indexes: get_i64(&doc, "indexes").unwrap_or(0),
+            index_size: get_i64(&doc, "indexSize").unwrap_or(0),
+            fs_used_size: get_i64(&doc, "fsUsedSize"),
+            fs_total_size: get_i64(&doc, "fsTotalSize"),
+            raw: doc,
+        }
+    }
+}
+
+/// Storage statistics for a collection, as reported by collStats.
+#[derive(Clone)]
+pub struct CollectionStats {
+    pub ns: String,
+    pub count: i64,
+    pub size: i64,
+    /// The average document size, omitted for empty collections.
+    pub avg_obj_size: Option<f64>,
+    pub storage_size: i64,
+    pub nindexes: i64,
+    pub total_index_size: i64,
+    /// The size of each index, by index name.
+    pub index_sizes: BTreeMap<String, i64>,
+    pub capped: bool,
+    /// The maximum number of documents and size in bytes, for capped collections.
+    pub max: Option<i64>,
+    pub max_size: Option<i64>,
+    /// The full command reply.
+    pub raw: bson::Document,
+}
+
+impl CollectionStats {
+    /// Reads the statistics from a collStats reply.
+    pub fn with_doc(doc: bson::Document) -> CollectionStats {
+        let mut index_sizes = BTreeMap::new();
+
+        if let Some(sizes) = get_doc(&doc, "indexSizes") {
+            for (name, size) in sizes.iter() {
+                if let Some(size) = to_i64(size) {
+                    index_sizes.insert(name.to_owned(), size);
+                }
+            }
+        }
+
+        let capped = match doc.get("capped") {
+            Some(&Bson::Boolean(capped)) => capped,
+            _ => false,
+        };
+
+        CollectionStats {
+            ns: get_string(&doc, "ns"),
+            count: get_i64(&doc, "count").unwrap_or(0),
+            size: get_i64(&doc, "size").unwrap_or(0),
+            avg_obj_size: get_f64(&doc, "avgObjSize"),
+            storage_size: get_i64(&doc, "storageSize").unwrap_or(0),
+            nindexes: get_i64(&doc, "nindexes").unwrap_or(0),
+            total_index_size: get_i64(&doc, "totalIndexSize").unwrap_or(0),
+            index_sizes: index_sizes,
+            capped: capped,
+            max: get_i64(&doc, "max"),
+            max_size: get_i64(&doc, "maxSize"),
+            raw: doc,
+        }
+    }
+}
+
+/// Operation counts since the server started.
+#[derive(Clone)]
+pub struct OpCounters {
+    pub insert: i64,
+    pub query: i64,
+    pub update: i64,
+    pub delete: i64,
+    pub getmore: i64,
+    pub command: i64,
+}
+
+/// Client connection counts.
+#[derive(Clone)]
+pub struct Connections {
+    pub current: i64,
+    pub available: i64,
+    /// Reported by MongoDB 2.6 and later.
+    pub total_created: Option<i64>,
+}
+
+/// WiredTiger cache usage, in bytes and pages.
+#[derive(Clone)]
+pub struct WiredTigerCache {
+    pub bytes_in_cache: i64,
+    pub maximum_bytes_configured: i64,
+    pub tracked_dirty_bytes: i64,
+    pub pages_read_into_cache: i64,
+    pub pages_written_from_cache: i64,
+}
+
+/// Server state, as reported by serverStatus.
+#[derive(Clone)]
+pub struct ServerStatus {
+    pub host: String,
+    pub version: String,
+    pub process: String,
+    pub uptime_secs: f64,
+    pub opcounters: OpCounters,
+    pub connections: Connections,
+    /// Cache statistics, if the server uses the WiredTiger storage engine.
+    pub wired_tiger_cache: Option<WiredTigerCache>,
+    /// The full command reply.
+    pub raw: bson::Document,
+}
+
+impl ServerStatus {
+    /// Reads the server state from a serverStatus reply.
+    pub fn with_doc(doc: bson::Document) -> ServerStatus {
+        let empty = bson::Document::new();
+
+        let opcounters = {
+            let counters = get_doc(&doc, "opcounters").unwrap_or(&empty);
+            let count = |key: &str| get_i64(counters, key).unwrap_or(0);
+
+            OpCounters {
+                insert: count("insert"),
+                query: count("query"),
+                update: count("update"),
+                delete: count("delete"),
+                getmore: count("getmore"),
+                command: count("command"),
+            }
+        };
+
+        let connections = {
+            let connections = get_doc(&doc, "connections").unwrap_or(&empty);
+
+            Connections {
+                current: get_i64(connections, "current").unwrap_or(0),
+                available: get_i64(connections, "available").unwrap_or(0),
+                total_created: get_i64(connections, "totalCreated"),
+            }
+        };
+
+        let wired_tiger_cache = get_doc(&doc, "wiredTiger")
+            .and_then(|wired_tiger| get_doc(wired_tiger, "cache"))
+            .map(|cache| {
+                let count = |key: &str| get_i64(cache, key).unwrap_or(0);
+
+                WiredTigerCache {
+                    bytes_in_cache: count("bytes currently in the cache"),
+                    maximum_bytes_configured: count("maximum bytes configured"),
+                    tracked_dirty_bytes: count("tracked dirty bytes in the cache"),
+                    pages_read_into_cache: count("pages read into cache"),
+                    pages_written_from_cache: count("pages written from cache"),
+                }
+            });
+
+        ServerStatus {
+            host: get_string(&doc, "host"),
+            version: get_string(&doc, "version"),
+            process: get_string(&doc, "process"),
+            uptime_secs: get_f64(&doc, "uptime").unwrap_or(0.0),
+            opcounters: opcounters,
+            connections: connections,
+            wired_tiger_cache: wired_tiger_cache,
+            raw: doc,
+        }
+    }
+}
+
+/// An operation in progress on the server, as reported by currentOp.
+#[derive(Clone)]
+pub struct CurrentOperation {
+    /// The operation id to pass to killOp. This is a number on mongod, and a string prefixed
+    /// with the shard name on mongos.
+    pub opid: Bson,
+    pub ns: String,
+    /// How long the operation has been running, omitted for operations that have not started.
+    pub secs_running: Option<i64>,
+    /// The address of the client that issued the operation, omitted for internal operations.
+    pub client: Option<String>,
+    /// The command or query document being run.
+    pub command: Option<bson::Document>,
+    /// The comment attached to the operation, if any.
+    pub comment: Option<Bson>,
+    /// The full operation entry.
+    pub raw: bson::Document,
+}
+
+impl CurrentOperation {
+    /// Reads an operation from a currentOp `inprog` entry. Servers before MongoDB 3.2
+    /// report the operation under `query`, with any comment as `$comment`.
+    pub fn with_doc(doc: bson::Document) -> CurrentOperation {
+        let command = get_doc(&doc, "command").or_else(|| get_doc(&doc, "query")).cloned();
+
+        let comment = command.as_ref().and_then(|command| {
+            command.get("comment").or_else(|| command.get("$comment")).cloned()
+        });
+
+        let client = match doc.get("client").or_else(|| doc.get("client_s")) {
+            Some(&Bson::String(ref client)) => Some(client.to_owned()),
+            _ => None,
+        };
+
+        CurrentOperation {
+            opid: doc.get("opid").cloned().unwrap_or(Bson::Null),
+            ns: get_string(&doc, "ns"),
+            secs_running: get_i64(&doc, "secs_running"),
+            client: client,
+            command: command,
+            comment: comment,
+            raw: doc,
+        }
+    }
+}
@@ -0,0 +1,240 @@
+//! Network streams to MongoDB servers, optionally secured with TLS.
+//!
+//! TLS support requires the `ssl` feature, which links against OpenSSL.
+use Result;
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[cfg(feature = "ssl")]
+use Error::{self, IoError};
+#[cfg(feature = "ssl")]
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+#[cfg(feature = "ssl")]
+use openssl::x509::X509;
+#[cfg(feature = "ssl")]
+use std::fmt;
+#[cfg(feature = "ssl")]
+use std::fs::File;
+#[cfg(feature = "ssl")]
+use std::sync::{Arc, Mutex};
+
+/// Describes how new connections to servers are established.
+#[derive(Clone, Debug)]
+pub enum StreamConnector {
+    /// Plain TCP connections.
+    Tcp,
+    /// TLS connections, optionally presenting a client certificate.
+    #[cfg(feature = "ssl")]
+    Ssl {
+        /// A PEM file of the certificate authorities used to verify servers.
+        ca_file: Option<String>,
+        /// A PEM file of the client certificate presented to servers.
+        certificate_file: Option<String>,
+        /// A PEM file of the client certificate's private key.
+        key_file: Option<String>,
+        /// Whether to verify the server's certificate and host name.
+        verify_peer: bool,
+    },
+}
+
+/// A connection to a server, over plain TCP or TLS.
+pub enum Stream {
+    Tcp(TcpStream),
+    /// A TLS session, shared between handles to the same connection, along with
+    /// the underlying socket.
+    #[cfg(feature = "ssl")]
+    Ssl(Arc<Mutex<SslStream<TcpStream>>>, TcpStream),
+}
+
+impl StreamConnector {
+    /// Returns true if new connections are secured with TLS.
+    pub fn is_tls(&self) -> bool {
+        match *self {
+            StreamConnector::Tcp => false,
+            #[cfg(feature = "ssl")]
+            StreamConnector::Ssl { .. } => true,
+        }
+    }
+
+    /// Wraps a connected socket, performing the TLS handshake with the named host if
+    /// configured. The socket's timeouts bound the handshake.
+    pub fn wrap(&self, socket: TcpStream, host_name: &str) -> Result<Stream> {
+        match *self {
+            StreamConnector::Tcp => Ok(Stream::Tcp(socket)),
+            #[cfg(feature = "ssl")]
+            StreamConnector::Ssl { ref ca_file, ref certificate_file, ref key_file, verify_peer } => {
+                let mut builder = try!(SslConnector::builder(SslMethod::tls()).map_err(tls_error));
+
+                if let Some(ref file) = *ca_file {
+                    try!(builder.set_ca_file(file).map_err(tls_error));
+                }
+
+                if let Some(ref file) = *certificate_file {
+                    try!(builder.set_certificate_file(file, SslFiletype::PEM).map_err(tls_error));
+                }
+
+                if let Some(ref file) = *key_file {
+                    try!(builder.set_private_key_file(file, SslFiletype::PEM).map_err(tls_error));
+                }
+
+                if !verify_peer {
+                    builder.set_verify(SslVerifyMode::NONE);
+                }
+
+                let mut config = try!(builder.build().configure().map_err(tls_error));
+                config.set_verify_hostname(verify_peer);
+
+                let tcp = try!(socket.try_clone());
+                let ssl = try!(config.connect(host_name, socket).map_err(tls_error));
+                Ok(Stream::Ssl(Arc::new(Mutex::new(ssl)), tcp))
+            },
+        }
+    }
+
+    /// Returns the subject of the configured client certificate in RFC 2253 format,
+    /// as expected by the MONGODB-X509 mechanism, or None if no certificate is configured.
+    pub fn certificate_subject(&self) -> Result<Option<String>> {
+        match *self {
+            StreamConnector::Tcp => Ok(None),
+            #[cfg(feature = "ssl")]
+            StreamConnector::Ssl { ref certificate_file, .. } => {
+                let file = match *certificate_file {
+                    Some(ref file) => file,
+                    None => return Ok(None),
+                };
+
+                let mut pem = Vec::new();
+                try!(try!(File::open(file)).read_to_end(&mut pem));
+                let certificate = try!(X509::from_pem(&pem).map_err(tls_error));
+
+                let mut names = Vec::new();
+                for entry in certificate.subject_name().entries() {
+                    let key = try!(entry.object().nid().short_name().map_err(tls_error));
+                    let value = try!(entry.data().as_utf8().map_err(tls_error));
+                    names.push(format!("{}={}", key, escape_rdn_value(&value)));
+                }
+
+                // RFC 2253 lists the most specific name first, unlike the certificate encoding.
+                names.reverse();
+                Ok(Some(names.join(",")))
+            },
+        }
+    }
+}
+
+// Escapes the characters RFC 2253 reserves within an attribute value.
+#[cfg(feature = "ssl")]
+fn escape_rdn_value(value: &str) -> String {
+    let last = value.chars().count().saturating_sub(1);
+    let mut escaped = String::with_capacity(value.len());
+
+    for (i, c) in value.chars().enumerate() {
+        let reserved = match c {
+            ',' | '+' | '"' | '\\' | '<' | '>' | ';' => true,
+            '#' => i == 0,
+            ' ' => i == 0 || i == last,
+            _ => false,
+        };
+
+        if reserved {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+// Reports a TLS failure as a network error, so that server selection may try another server.
+#[cfg(feature = "ssl")]
+fn tls_error<E: fmt::Display>(err: E) -> Error {
+    IoError(io::Error::new(io::ErrorKind::Other, format!("TLS error: {}", err)))
+}
+
+// Reports a TLS session whose lock was poisoned by a panicking thread.
+#[cfg(feature = "ssl")]
+fn poisoned<T>(_: T) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "The TLS session lock was poisoned.")
+}
+
+impl Stream {
+    /// Returns another handle to the same connection.
+    pub fn try_clone(&self) -> Result<Stream> {
+        match *self {
+            Stream::Tcp(ref socket) => Ok(Stream::Tcp(try!(socket.try_clone()))),
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(ref ssl, ref socket) => {
+                Ok(Stream::Ssl(ssl.clone(), try!(socket.try_clone())))
+            },
+        }
+    }
+
+    /// Returns the underlying socket, used for socket options and to interrupt blocked
+    /// operations. Reading or writing it directly would bypass TLS.
+    pub fn tcp_stream(&self) -> &TcpStream {
+        match *self {
+            Stream::Tcp(ref socket) => socket,
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(_, ref socket) => socket,
+        }
+    }
+
+    /// Returns true if the connection is secured with TLS.
+    pub fn is_tls(&self) -> bool {
+        match *self {
+            Stream::Tcp(_) => false,
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(..) => true,
+        }
+    }
+
+    /// Returns the address of the server.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp_stream().peer_addr()
+    }
+
+    /// Sets the timeout of blocking reads.
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp_stream().set_read_timeout(timeout)
+    }
+
+    /// Sets the timeout of blocking writes.
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp_stream().set_write_timeout(timeout)
+    }
+
+    /// Shuts down the connection, interrupting blocked reads and writes on every handle.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        self.tcp_stream().shutdown(how)
+    }
+}
+
+impl<'a> Read for &'a Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match **self {
+            Stream::Tcp(ref socket) => (&*socket).read(buf),
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(ref ssl, _) => try!(ssl.lock().map_err(poisoned)).read(buf),
+        }
+    }
+}
+
+impl<'a> Write for &'a Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match **self {
+            Stream::Tcp(ref socket) => (&*socket).write(buf),
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(ref ssl, _) => try!(ssl.lock().map_err(poisoned)).write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match **self {
+            Stream::Tcp(ref socket) => (&*socket).flush(),
+            #[cfg(feature = "ssl")]
+            Stream::Ssl(ref ssl, _) => try!(ssl.lock().map_err(poisoned)).flush(),
+        }
+    }
+}
@@ -3,18 +3,21 @@ pub mod server;
 pub mod monitor;
 
 use {Client, Result};
-use Error::{self, ArgumentError, OperationError};
+use Error::{self, ArgumentError, AuthenticationError, OperationError};
 
 use bson::oid;
 
-use common::{ReadPreference, ReadMode};
+use auth::ConnectionAuth;
+use common::{Deadline, ReadPreference, ReadMode};
 use connstring::{ConnectionString, Host};
-use pool::PooledStream;
+use pool::{ConnectionPool, PooledStream};
 
 use rand::{thread_rng, Rng};
 
+use std::cmp;
 use std::collections::HashMap;
 use std::i64;
+use std::usize;
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 use std::thread;
@@ -25,6 +28,12 @@ use self::server::{Server, ServerDescription, ServerType};
 pub const DEFAULT_HEARTBEAT_FREQUENCY_MS: u32 = 10000;
 pub const DEFAULT_LOCAL_THRESHOLD_MS: i64 = 15;
 pub const DEFAULT_SERVER_SELECTION_TIMEOUT_MS: i64 = 30000;
+/// The smallest permitted maxStalenessSeconds value for a read preference.
+pub const SMALLEST_MAX_STALENESS_SECONDS: i64 = 90;
+/// The interval at which a primary writes a no-op when otherwise idle.
+pub const IDLE_WRITE_PERIOD_MS: i64 = 10000;
+/// The minimum wire version of servers that report the staleness of their reads.
+pub const MAX_STALENESS_WIRE_VERSION: i64 = 5;
 
 /// Describes the type of topology for a server set.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -100,64 +109,80 @@ impl TopologyDescription {
         }
     }
 
-    /// Returns the nearest server stream, calculated by round trip time.
-    fn get_nearest_from_vec(&self, servers: &mut Vec<Host>) -> Result<(PooledStream, ServerType)> {
-        servers.sort_by(|a, b| {
-            let mut a_rtt = i64::MAX;
-            let mut b_rtt = i64::MAX;
-            if let Some(server) = self.servers.get(a) {
-                if let Ok(a_description) = server.description.read() {
-                    a_rtt = a_description.round_trip_time.unwrap_or(i64::MAX);
-                }
-            }
-            if let Some(server) = self.servers.get(b) {
-                if let Ok(b_description) = server.description.read() {
-                    b_rtt = b_description.round_trip_time.unwrap_or(i64::MAX);
-                }
+    /// Orders the hosts by round trip time, dropping hosts whose round trip time is unknown.
+    fn order_by_latency(&self, mut servers: Vec<Host>) -> Vec<Host> {
+        let round_trip_time = |host: &Host| {
+            match self.servers.get(host) {
+                Some(server) => match server.description.read() {
+                    Ok(description) => description.round_trip_time,
+                    Err(_) => None,
+                },
+                None => None,
             }
+        };
 
-            a_rtt.cmp(&b_rtt)
-        });
+        servers.retain(|host| round_trip_time(host).is_some());
+        servers.sort_by(|a, b| round_trip_time(a).cmp(&round_trip_time(b)));
+        servers
+    }
 
-        // Iterate over each host until one's stream can be acquired.
-        for host in servers.iter() {
-            if let Some(server) = self.servers.get(host) {
-                if let Ok(description) = server.description.read() {
-                    if description.round_trip_time.is_none() {
-                        break;
-                    } else if let Ok(stream) = server.acquire_stream() {
-                        return Ok((stream, description.server_type));
-                    }
-                }
+    /// Returns the index of the less busy of two randomly chosen hosts, as measured
+    /// by the number of operations currently in flight on each server.
+    fn choose_less_busy(&self, servers: &Vec<Host>) -> usize {
+        let len = servers.len();
+        if len <= 1 {
+            return 0;
+        }
+
+        // Pick two distinct candidates.
+        let mut rng = thread_rng();
+        let first = rng.gen_range(0, len);
+        let mut second = rng.gen_range(0, len - 1);
+        if second >= first {
+            second += 1;
+        }
+
+        let operation_count = |index: usize| {
+            match self.servers.get(&servers[index]) {
+                Some(server) => server.operation_count(),
+                None => usize::MAX,
             }
+        };
+
+        if operation_count(second) < operation_count(first) {
+            second
+        } else {
+            first
         }
-        Err(OperationError("No servers available for the provided ReadPreference.".to_owned()))
     }
 
-    /// Returns a random server stream from the vector.
-    fn get_rand_from_vec(&self, servers: &mut Vec<Host>) -> Result<(PooledStream, ServerType)> {
+    /// Orders the hosts for connection attempts, each time preferring the less busy of two
+    /// random hosts among those remaining.
+    fn order_by_load(&self, mut servers: Vec<Host>) -> Vec<Host> {
+        let mut ordered = Vec::with_capacity(servers.len());
         while !servers.is_empty() {
-            let len = servers.len();
-            let index = thread_rng().gen_range(0, len);
-
-            if let Some(server) = self.servers.get(servers.get(index).unwrap()) {
-                if let Ok(stream) = server.acquire_stream() {
-                    if let Ok(description) = server.description.read() {
-                        return Ok((stream, description.server_type));
-                    }
-                }
-            }
-            servers.remove(index);
+            let index = self.choose_less_busy(&servers);
+            ordered.push(servers.remove(index));
         }
-        Err(OperationError("No servers available for the provided ReadPreference.".to_owned()))
+        ordered
     }
 
-    /// Returns a server stream for read operations.
-    pub fn acquire_stream(&self, read_preference: &ReadPreference) -> Result<(PooledStream, bool, bool)> {
+    /// Returns the connection pools of the hosts, in order.
+    fn pools(&self, hosts: Vec<Host>) -> Vec<Arc<ConnectionPool>> {
+        hosts.iter().filter_map(|host| self.servers.get(host).map(|server| server.pool())).collect()
+    }
+
+    /// Returns the connection pools of the servers suitable for a read operation, in the
+    /// order in which they should be tried, along with whether the query should set the
+    /// slave_ok flag and send the read preference to that server. Connecting is left to the
+    /// caller, so that no topology lock is held during a connection handshake.
+    pub fn select_servers(&self, read_preference: &ReadPreference)
+                          -> Vec<(Arc<ConnectionPool>, bool, bool)> {
         let (mut hosts, rand) = self.choose_hosts(&read_preference);
 
-        // Filter hosts by tagsets
+        // Filter hosts by staleness, then by tagsets
         if self.topology_type != TopologyType::Sharded && self.topology_type != TopologyType::Single {
+            self.filter_stale_hosts(&mut hosts, read_preference);
             self.filter_hosts(&mut hosts, read_preference);
         }
 
@@ -166,7 +191,7 @@ impl TopologyDescription {
         if hosts.is_empty() && read_preference.mode == ReadMode::SecondaryPreferred {
             let mut read_pref = read_preference.clone();
             read_pref.mode = ReadMode::PrimaryPreferred;
-            return self.acquire_stream(&read_pref);
+            return self.select_servers(&read_pref);
         }
 
         // If no servers are available, request an update from all monitors.
@@ -179,15 +204,33 @@ impl TopologyDescription {
         // Filter hosts by round trip times within the latency window.
         self.filter_latency_hosts(&mut hosts);
 
-        // Retrieve a server stream from the list of acceptable hosts.
-        let (pooled_stream, server_type) = if rand {
-            try!(self.get_rand_from_vec(&mut hosts))
+        // Order the acceptable hosts for connection attempts.
+        let hosts = if rand {
+            self.order_by_load(hosts)
         } else {
-            try!(self.get_nearest_from_vec(&mut hosts))
+            self.order_by_latency(hosts)
         };
 
-        // Determine how to handle server-side logic based on ReadMode and TopologyType.
-        let (slave_ok, send_read_pref) = match self.topology_type {
+        hosts.into_iter().filter_map(|host| {
+            let server = match self.servers.get(&host) {
+                Some(server) => server,
+                None => return None,
+            };
+
+            let server_type = match server.description.read() {
+                Ok(description) => description.server_type,
+                Err(_) => return None,
+            };
+
+            let (slave_ok, send_read_pref) = self.read_flags(read_preference, server_type);
+            Some((server.pool(), slave_ok, send_read_pref))
+        }).collect()
+    }
+
+    // Determines how to handle server-side logic based on ReadMode and TopologyType.
+    fn read_flags(&self, read_preference: &ReadPreference,
+                  server_type: ServerType) -> (bool, bool) {
+        match self.topology_type {
             TopologyType::Unknown => (false, false),
             TopologyType::Single => match server_type {
                 ServerType::Mongos => {
@@ -216,14 +259,13 @@ impl TopologyDescription {
                     ReadMode::Nearest => (true, true),
                 }
             }
-        };
-
-        Ok((pooled_stream, slave_ok, send_read_pref))
+        }
     }
 
-    /// Returns a server stream for write operations.
-    pub fn acquire_write_stream(&self) -> Result<PooledStream> {
-        let (mut hosts, rand) = self.choose_write_hosts();
+    /// Returns the connection pools of the servers suitable for a write operation, in the
+    /// order in which they should be tried.
+    pub fn select_write_servers(&self) -> Vec<Arc<ConnectionPool>> {
+        let (hosts, rand) = self.choose_write_hosts();
 
         // If no servers are available, request an update from all monitors.
         if hosts.is_empty() {
@@ -233,10 +275,96 @@ impl TopologyDescription {
         }
 
         if rand {
-            Ok(try!(self.get_rand_from_vec(&mut hosts)).0)
+            self.pools(self.order_by_load(hosts))
         } else {
-            Ok(try!(self.get_nearest_from_vec(&mut hosts)).0)
+            self.pools(self.order_by_latency(hosts))
+        }
+    }
+
+    /// Validates the read preference's max staleness against the topology's heartbeat frequency
+    /// and the wire versions of the known servers.
+    pub fn validate_max_staleness(&self, read_preference: &ReadPreference) -> Result<()> {
+        let max_staleness = match read_preference.max_staleness_seconds {
+            Some(max_staleness) => max_staleness,
+            None => return Ok(()),
+        };
+
+        if read_preference.mode == ReadMode::Primary {
+            return Err(ArgumentError("maxStalenessSeconds cannot be used with ReadMode::Primary.".to_owned()));
+        }
+
+        // Round up to the nearest second.
+        let heartbeat_seconds = (self.heartbeat_frequency_ms as i64 + IDLE_WRITE_PERIOD_MS + 999) / 1000;
+        let min_staleness = cmp::max(SMALLEST_MAX_STALENESS_SECONDS, heartbeat_seconds);
+
+        if max_staleness < min_staleness {
+            return Err(ArgumentError(format!("maxStalenessSeconds must be at least {} seconds.",
+                                             min_staleness)));
         }
+
+        for server in self.servers.values() {
+            let description = try!(server.description.read());
+            if description.server_type != ServerType::Unknown &&
+               description.max_wire_version < MAX_STALENESS_WIRE_VERSION {
+                return Err(ArgumentError(format!("Server {} does not support maxStalenessSeconds.",
+                                                 server.host.host_name)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Filters out secondaries whose estimated staleness exceeds the read preference's
+    /// max staleness. Secondaries that have not reported a last write date are retained.
+    pub fn filter_stale_hosts(&self, hosts: &mut Vec<Host>, read_preference: &ReadPreference) {
+        let max_staleness_ms = match read_preference.max_staleness_seconds {
+            Some(max_staleness) => max_staleness * 1000,
+            None => return,
+        };
+
+        let heartbeat_frequency_ms = self.heartbeat_frequency_ms as i64;
+
+        // Find the primary and the most recent secondary write date.
+        let mut primary = None;
+        let mut max_last_write_date = None;
+
+        for (_, server) in self.servers.iter() {
+            if let Ok(description) = server.description.read() {
+                match description.server_type {
+                    ServerType::RSPrimary => primary = Some(description.clone()),
+                    ServerType::RSSecondary => {
+                        if let Some(ref date) = description.last_write_date {
+                            let newer = match max_last_write_date {
+                                Some(ref max_date) => date > max_date,
+                                None => true,
+                            };
+                            if newer {
+                                max_last_write_date = Some(date.clone());
+                            }
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        }
+
+        hosts.retain(|host| {
+            if let Some(server) = self.servers.get(host) {
+                if let Ok(description) = server.description.read() {
+                    if description.server_type != ServerType::RSSecondary {
+                        return true;
+                    }
+
+                    return match description.staleness_ms(primary.as_ref(),
+                                                          max_last_write_date.as_ref(),
+                                                          heartbeat_frequency_ms) {
+                        Some(staleness) => staleness <= max_staleness_ms,
+                        None => true,
+                    };
+                }
+            }
+            false
+        });
     }
 
     /// Filters a given set of hosts based on the provided read preference tag sets.
@@ -715,46 +843,147 @@ impl Topology {
     }
 
     // Private server stream acquisition helper.
-    fn acquire_stream_private(&self, read_preference: Option<ReadPreference>, write: bool) -> Result<(PooledStream, bool, bool)> {
+    fn acquire_stream_private(&self, read_preference: Option<ReadPreference>, write: bool,
+                              deadline: &Deadline,
+                              auth: &ConnectionAuth) -> Result<(PooledStream, bool, bool)> {
         // Note start of server selection.
         let time = time::get_time();
         let start_ms = time.sec * 1000 + (time.nsec as i64) / 1000000;
 
         loop {
-            let description = try!(self.description.read());
-            let result = if write {
-                match description.acquire_write_stream() {
-                    Ok(stream) => Ok((stream, false, false)),
-                    Err(err) => Err(err),
-                }
-            } else {
-                description.acquire_stream(read_preference.as_ref().unwrap())
+            // Select servers under the topology lock, but connect to them after releasing it,
+            // as a new connection may be authenticated over several round trips.
+            let (candidates, server_selection_timeout_ms) = {
+                let description = try!(self.description.read());
+                let candidates = if write {
+                    description.select_write_servers().into_iter()
+                        .map(|pool| (pool, false, false))
+                        .collect::<Vec<_>>()
+                } else {
+                    description.select_servers(read_preference.as_ref().unwrap())
+                };
+                (candidates, description.server_selection_timeout_ms)
             };
 
-            match result {
-                Ok(stream) => return Ok(stream),
-                Err(err) => {
-                    // Check duration of current server selection and return an error if overdue.
-                    let end_time = time::get_time();
-                    let end_ms = end_time.sec * 1000 + (end_time.nsec as i64) / 1000000;
-                    if end_ms - start_ms >= description.server_selection_timeout_ms {
-                        return Err(err)
-                    }
-                    // Otherwise, sleep for a little while.
-                    thread::sleep_ms(500);
-                },
+            // Iterate over each server until one's stream can be acquired.
+            for (pool, slave_ok, send_read_pref) in candidates {
+                match pool.acquire_authenticated_stream(deadline, auth) {
+                    Ok(stream) => return Ok((stream, slave_ok, send_read_pref)),
+                    // Another server would reject the credential as well, and retrying
+                    // would fail to authenticate again until the selection timeout.
+                    Err(err @ AuthenticationError(_)) => return Err(err),
+                    Err(_) => (),
+                }
             }
-        }        
+
+            // An expired operation deadline takes precedence over the selection timeout.
+            try!(deadline.check());
+
+            // Check duration of current server selection and return an error if overdue.
+            let end_time = time::get_time();
+            let end_ms = end_time.sec * 1000 + (end_time.nsec as i64) / 1000000;
+            if end_ms - start_ms >= server_selection_timeout_ms {
+                return Err(OperationError("No servers available for the provided ReadPreference."
+                                              .to_owned()));
+            }
+            // Otherwise, sleep for a little while.
+            thread::sleep_ms(deadline.cap_ms(500) as u32);
+        }
     }
-    
-    /// Returns a server stream for read operations.
-    pub fn acquire_stream(&self, read_preference: ReadPreference) -> Result<(PooledStream, bool, bool)> {
-        self.acquire_stream_private(Some(read_preference), false)
+
+    /// Returns a server stream for read operations, selected before the deadline expires.
+    /// New connections are authenticated with the given credential.
+    pub fn acquire_stream(&self, read_preference: ReadPreference, deadline: &Deadline,
+                          auth: &ConnectionAuth) -> Result<(PooledStream, bool, bool)> {
+        {
+            let description = try!(self.description.read());
+            try!(description.validate_max_staleness(&read_preference));
+        }
+        self.acquire_stream_private(Some(read_preference), false, deadline, auth)
     }
 
-    /// Returns a server stream for write operations.
-    pub fn acquire_write_stream(&self) -> Result<PooledStream> {
-        let (stream, _, _) = try!(self.acquire_stream_private(None, true));
+    /// Returns a server stream for write operations, selected before the deadline expires.
+    /// New connections are authenticated with the given credential.
+    pub fn acquire_write_stream(&self, deadline: &Deadline,
+                                auth: &ConnectionAuth) -> Result<PooledStream> {
+        let (stream, _, _) = try!(self.acquire_stream_private(None, true, deadline, auth));
         Ok(stream)
     }
+
+    /// Returns the SASL mechanisms a server monitor reported for the '<source>.<user>'
+    /// namespace, if any monitor has received a list for it.
+    pub fn sasl_supported_mechs(&self, namespace: &str) -> Result<Option<Vec<String>>> {
+        let description = try!(self.description.read());
+        for server in description.servers.values() {
+            let server_description = try!(server.description.read());
+            match server_description.sasl_supported_mechs {
+                Some((ref requested, ref mechs)) if requested == namespace && !mechs.is_empty() => {
+                    return Ok(Some(mechs.clone()));
+                },
+                _ => (),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the current description of a server in the topology, or a default description
+    /// if the server is no longer part of the topology.
+    pub fn server_description(&self, host: &Host) -> Result<ServerDescription> {
+        let description = try!(self.description.read());
+        match description.servers.get(host) {
+            Some(server) => Ok(try!(server.description.read()).clone()),
+            None => Ok(ServerDescription::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Client, ThreadedClient};
+    use auth::ConnectionAuth;
+    use common::Deadline;
+    use connstring::Host;
+
+    use std::sync::{Arc, RwLock};
+
+    use super::TopologyDescription;
+    use super::server::Server;
+
+    #[test]
+    fn choose_less_busy() {
+        let client = Client::connect("localhost", 27017).unwrap();
+        let top_arc = Arc::new(RwLock::new(TopologyDescription::new()));
+
+        // Two addresses for the same server, each with its own connection pool.
+        let busy = Host { host_name: "localhost".to_owned(), port: 27017, ipc: String::new() };
+        let idle = Host { host_name: "127.0.0.1".to_owned(), port: 27017, ipc: String::new() };
+
+        let mut description = TopologyDescription::new();
+        for host in vec![busy.clone(), idle.clone()] {
+            let server = Server::new(client.clone(), host.clone(), top_arc.clone(), false);
+            description.servers.insert(host, server);
+        }
+
+        let hosts = vec![busy.clone(), idle.clone()];
+
+        let in_flight: Vec<_> = (0..2).map(|_| {
+            let server = description.servers.get(&busy).unwrap();
+            server.acquire_stream(&Deadline::none(), &ConnectionAuth::none()).unwrap()
+        }).collect();
+
+        assert_eq!(2, description.servers.get(&busy).unwrap().operation_count());
+        assert_eq!(0, description.servers.get(&idle).unwrap().operation_count());
+
+        // With two candidates, both are always compared.
+        for _ in 0..20 {
+            assert_eq!(1, description.choose_less_busy(&hosts));
+        }
+
+        drop(in_flight);
+        assert_eq!(0, description.servers.get(&busy).unwrap().operation_count());
+
+        // A single candidate is chosen without comparison.
+        assert_eq!(0, description.choose_less_busy(&vec![idle.clone()]));
+    }
 }
@@ -6,6 +6,7 @@ use bson::{self, Bson, oid};
 use chrono::{DateTime, UTC};
 
 use coll::options::FindOptions;
+use common::Deadline;
 use command_type::CommandType;
 use connstring::{self, Host};
 use cursor::Cursor;
@@ -21,8 +22,9 @@ use time;
 use super::server::{ServerDescription, ServerType};
 use super::{DEFAULT_HEARTBEAT_FREQUENCY_MS, TopologyDescription};
 
-const DEFAULT_MAX_BSON_OBJECT_SIZE: i64 = 16 * 1024 * 1024;
-const DEFAULT_MAX_MESSAGE_SIZE_BYTES: i64 = 48000000;
+pub const DEFAULT_MAX_BSON_OBJECT_SIZE: i64 = 16 * 1024 * 1024;
+pub const DEFAULT_MAX_MESSAGE_SIZE_BYTES: i64 = 48000000;
+pub const DEFAULT_MAX_WRITE_BATCH_SIZE: i64 = 1000;
 
 /// The result of an isMaster operation.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -31,6 +33,7 @@ pub struct IsMasterResult {
     pub is_master: bool,
     pub max_bson_object_size: i64,
     pub max_message_size_bytes: i64,
+    pub max_write_batch_size: i64,
     pub local_time: Option<DateTime<UTC>>,
     pub min_wire_version: i64,
     pub max_wire_version: i64,
@@ -52,6 +55,11 @@ pub struct IsMasterResult {
     pub election_id: Option<oid::ObjectId>,
     pub primary: Option<Host>,
     pub hidden: bool,
+    /// The date of the server's most recent write operation, used to estimate staleness.
+    pub last_write_date: Option<DateTime<UTC>>,
+    /// The SASL mechanisms available for the user named in the request's
+    /// saslSupportedMechs field, if one was provided and the user exists.
+    pub sasl_supported_mechs: Option<Vec<String>>,
 }
 
 /// Monitors and updates server and topology information.
@@ -95,6 +103,7 @@ impl IsMasterResult {
             is_master: false,
             max_bson_object_size: DEFAULT_MAX_BSON_OBJECT_SIZE,
             max_message_size_bytes: DEFAULT_MAX_MESSAGE_SIZE_BYTES,
+            max_write_batch_size: DEFAULT_MAX_WRITE_BATCH_SIZE,
             local_time: None,
             min_wire_version: -1,
             max_wire_version: -1,
@@ -111,22 +120,54 @@ impl IsMasterResult {
             election_id: None,
             primary: None,
             hidden: false,
+            last_write_date: None,
+            sasl_supported_mechs: None,
         };
 
         if let Some(&Bson::Boolean(b)) = doc.get("ismaster") {
             result.is_master = b;
         }
 
+        // Size limits are reported as 32-bit integers, but may be stored as doubles.
+        let limit = |name: &str| match doc.get(name) {
+            Some(&Bson::I32(v)) => Some(v as i64),
+            Some(&Bson::I64(v)) => Some(v),
+            Some(&Bson::FloatingPoint(v)) => Some(v as i64),
+            _ => None,
+        };
+
+        if let Some(size) = limit("maxBsonObjectSize") {
+            result.max_bson_object_size = size;
+        }
+
+        if let Some(size) = limit("maxMessageSizeBytes") {
+            result.max_message_size_bytes = size;
+        }
+
+        if let Some(size) = limit("maxWriteBatchSize") {
+            result.max_write_batch_size = size;
+        }
+
         if let Some(&Bson::UtcDatetime(ref datetime)) = doc.get("localTime") {
             result.local_time = Some(datetime.clone());
         }
 
-        if let Some(&Bson::I64(v)) = doc.get("minWireVersion") {
-            result.min_wire_version = v;
+        if let Some(&Bson::Document(ref last_write)) = doc.get("lastWrite") {
+            if let Some(&Bson::UtcDatetime(ref datetime)) = last_write.get("lastWriteDate") {
+                result.last_write_date = Some(datetime.clone());
+            }
+        }
+
+        match doc.get("minWireVersion") {
+            Some(&Bson::I32(v)) => result.min_wire_version = v as i64,
+            Some(&Bson::I64(v)) => result.min_wire_version = v,
+            _ => (),
         }
 
-        if let Some(&Bson::I64(v)) = doc.get("maxWireVersion") {
-            result.max_wire_version = v;
+        match doc.get("maxWireVersion") {
+            Some(&Bson::I32(v)) => result.max_wire_version = v as i64,
+            Some(&Bson::I64(v)) => result.max_wire_version = v,
+            _ => (),
         }
 
         if let Some(&Bson::String(ref s)) = doc.get("msg") {
@@ -190,6 +231,13 @@ impl IsMasterResult {
             }
         }
 
+        if let Some(&Bson::Array(ref mechs)) = doc.get("saslSupportedMechs") {
+            result.sasl_supported_mechs = Some(mechs.iter().filter_map(|mech| match mech {
+                &Bson::String(ref mech) => Some(mech.to_owned()),
+                _ => None,
+            }).collect());
+        }
+
         match doc.get("electionId") {
             Some(&Bson::ObjectId(ref id)) => result.election_id = Some(id.clone()),
             Some(&Bson::Document(ref doc)) => if let Some(&Bson::String(ref s)) = doc.get("$oid") {
@@ -208,11 +256,14 @@ impl Monitor {
                top_description: Arc<RwLock<TopologyDescription>>,
                server_description: Arc<RwLock<ServerDescription>>) -> Monitor {
 
+        let personal_pool = ConnectionPool::with_connector(host.clone(), 1,
+                                                           client.stream_connector.clone());
+
         Monitor {
             client: client,
-            host: host.clone(),
+            host: host,
             server_pool: pool,
-            personal_pool: Arc::new(ConnectionPool::with_size(host, 1)),
+            personal_pool: Arc::new(personal_pool),
             top_description: top_description,
             server_description: server_description,
             heartbeat_frequency_ms: AtomicUsize::new(DEFAULT_HEARTBEAT_FREQUENCY_MS as usize),
@@ -229,21 +280,30 @@ impl Monitor {
         self.update_top_description(server_description.clone());
     }
 
-    /// Returns an isMaster server response using an owned monitor socket.
-    pub fn is_master(&self) -> Result<(Cursor, i64)> {
+    /// Returns an isMaster server response using an owned monitor socket, along with the
+    /// '<source>.<user>' namespace whose SASL mechanisms were requested, if any.
+    pub fn is_master(&self) -> Result<(Cursor, i64, Option<String>)> {
         let options = FindOptions::new().with_limit(1);
         let flags = OpQueryFlags::with_find_options(&options);
         let mut filter = bson::Document::new();
         filter.insert("isMaster".to_owned(), Bson::I32(1));
 
-        let stream = try!(self.personal_pool.acquire_stream());
+        // Ask for the mechanisms available to the configured user, if any.
+        let namespace = try!(self.client.credential.read()).as_ref()
+            .map(|credential| credential.namespace());
+
+        if let Some(ref namespace) = namespace {
+            filter.insert("saslSupportedMechs".to_owned(), Bson::String(namespace.to_owned()));
+        }
+
+        let stream = try!(self.personal_pool.acquire_stream(&Deadline::none()));
 
         let time_start = time::get_time();
 
         let cursor = try!(Cursor::query_with_stream(
             stream, self.client.clone(), "local.$cmd".to_owned(), 1,
             flags, options.skip as i32, 1, filter.clone(), options.projection.clone(),
-            CommandType::IsMaster, false, None));
+            CommandType::IsMaster, false, None, Deadline::none(), None));
 
         let time_end = time::get_time();
 
@@ -254,7 +314,7 @@ impl Monitor {
         let end_ms = sec_end_ms + time_end.nsec as i64 / 1000000;
 
         let round_trip_time = end_ms - start_ms;
-        Ok((cursor, round_trip_time))
+        Ok((cursor, round_trip_time, namespace))
     }
 
     pub fn request_update(&self) {
@@ -262,13 +322,23 @@ impl Monitor {
     }
 
     // Updates the server description associated with this monitor using an isMaster server response.
-    fn update_server_description(&self, doc: bson::Document,
-                                 round_trip_time: i64) -> Result<ServerDescription> {
+    fn update_server_description(&self, doc: bson::Document, round_trip_time: i64,
+                                 namespace: Option<String>) -> Result<ServerDescription> {
 
         let ismaster_result = IsMasterResult::new(doc);
         let mut server_description = self.server_description.write().unwrap();
         match ismaster_result {
-            Ok(ismaster) => server_description.update(ismaster, round_trip_time),
+            Ok(ismaster) => {
+                // Key the reported mechanisms to the user they were requested for, so that
+                // they are not used for a credential set after the request was sent.
+                let mechs = match (namespace, ismaster.sasl_supported_mechs.clone()) {
+                    (Some(namespace), Some(mechs)) => Some((namespace, mechs)),
+                    _ => None,
+                };
+
+                server_description.update(ismaster, round_trip_time);
+                server_description.sasl_supported_mechs = mechs;
+            },
             Err(err) => {
                 server_description.set_err(err);
                 return Err(OperationError("Failed to parse ismaster result.".to_owned()))
@@ -286,10 +356,12 @@ impl Monitor {
     }
 
     // Updates server and topology descriptions using a successful isMaster cursor result.
-    fn update_with_is_master_cursor(&self, cursor: &mut Cursor, round_trip_time: i64) {
+    fn update_with_is_master_cursor(&self, cursor: &mut Cursor, round_trip_time: i64,
+                                    namespace: Option<String>) {
         match cursor.next() {
             Some(Ok(doc)) => {
-                if let Ok(description) = self.update_server_description(doc, round_trip_time) {
+                if let Ok(description) = self.update_server_description(doc, round_trip_time,
+                                                                        namespace) {
                     self.update_top_description(description);
                 }
             },
@@ -305,7 +377,9 @@ impl Monitor {
     /// Execute isMaster and update the server and topology.
     fn execute_update(&self) {
         match self.is_master() {
-            Ok((mut cursor, rtt)) => self.update_with_is_master_cursor(&mut cursor, rtt),
+            Ok((mut cursor, rtt, namespace)) => {
+                self.update_with_is_master_cursor(&mut cursor, rtt, namespace)
+            },
             Err(err) => {
                 // Refresh all connections
                 self.server_pool.clear();
@@ -318,7 +392,9 @@ impl Monitor {
                 } else {
                     // Retry once
                     match self.is_master() {
-                        Ok((mut cursor, rtt)) => self.update_with_is_master_cursor(&mut cursor, rtt),
+                        Ok((mut cursor, rtt, namespace)) => {
+                            self.update_with_is_master_cursor(&mut cursor, rtt, namespace)
+                        },
                         Err(err) => self.set_err(err),
                     }
                 }
@@ -1,10 +1,14 @@
 //! MongoDB server representation.
 use {Client, Result};
 use Error::{self, OperationError};
+use auth::{self, ConnectionAuth};
 
 use bson::oid;
+use chrono::{DateTime, UTC};
+use common::Deadline;
 use connstring::Host;
-use pool::{ConnectionPool, PooledStream};
+use pool::{ConnectionPool, PooledStream, DEFAULT_POOL_SIZE};
+use stream::Stream;
 
 use std::collections::BTreeMap;
 use std::str::FromStr;
@@ -12,7 +16,8 @@ use std::sync::{Arc, RwLock};
 use std::sync::atomic::Ordering;
 use std::thread;
 
-use super::monitor::{IsMasterResult, Monitor};
+use super::monitor::{IsMasterResult, Monitor, DEFAULT_MAX_BSON_OBJECT_SIZE,
+                     DEFAULT_MAX_MESSAGE_SIZE_BYTES, DEFAULT_MAX_WRITE_BATCH_SIZE};
 use super::TopologyDescription;
 
 /// Server round trip time is calculated as an exponentially-weighted moving
@@ -55,6 +60,12 @@ pub struct ServerDescription {
     pub min_wire_version: i64,
     /// The maximum wire version supported by this server.
     pub max_wire_version: i64,
+    /// The maximum size of a BSON document accepted by this server.
+    pub max_bson_object_size: i64,
+    /// The maximum size of a wire protocol message accepted by this server.
+    pub max_message_size_bytes: i64,
+    /// The maximum number of writes in a single write batch.
+    pub max_write_batch_size: i64,
     /// The server's host information, if it is part of a replica set.
     pub me: Option<Host>,
     /// All hosts in the replica set known by this server.
@@ -71,6 +82,13 @@ pub struct ServerDescription {
     pub election_id: Option<oid::ObjectId>,
     /// The server's opinion of who the primary is.
     pub primary: Option<Host>,
+    /// The date of the server's most recent write operation, as reported by the server.
+    pub last_write_date: Option<DateTime<UTC>>,
+    /// The local time at which this description was last updated by a heartbeat.
+    pub last_update_time: Option<DateTime<UTC>>,
+    /// The SASL mechanisms reported as available to a user, keyed by the '<source>.<user>'
+    /// namespace the monitor asked about.
+    pub sasl_supported_mechs: Option<(String, Vec<String>)>,
 }
 
 /// Holds status and connection information about a single server.
@@ -111,6 +129,9 @@ impl ServerDescription {
             round_trip_time: None,
             min_wire_version: 0,
             max_wire_version: 0,
+            max_bson_object_size: DEFAULT_MAX_BSON_OBJECT_SIZE,
+            max_message_size_bytes: DEFAULT_MAX_MESSAGE_SIZE_BYTES,
+            max_write_batch_size: DEFAULT_MAX_WRITE_BATCH_SIZE,
             me: None,
             hosts: Vec::new(),
             passives: Vec::new(),
@@ -119,6 +140,9 @@ impl ServerDescription {
             set_name: String::new(),
             election_id: None,
             primary: None,
+            last_write_date: None,
+            last_update_time: None,
+            sasl_supported_mechs: None,
         }
     }
 
@@ -131,6 +155,9 @@ impl ServerDescription {
 
         self.min_wire_version = ismaster.min_wire_version;
         self.max_wire_version = ismaster.max_wire_version;
+        self.max_bson_object_size = ismaster.max_bson_object_size;
+        self.max_message_size_bytes = ismaster.max_message_size_bytes;
+        self.max_write_batch_size = ismaster.max_write_batch_size;
         self.me = ismaster.me;
         self.hosts = ismaster.hosts;
         self.passives = ismaster.passives;
@@ -139,6 +166,8 @@ impl ServerDescription {
         self.set_name = ismaster.set_name;
         self.election_id = ismaster.election_id;
         self.primary = ismaster.primary;
+        self.last_write_date = ismaster.last_write_date;
+        self.last_update_time = Some(UTC::now());
         self.round_trip_time = match self.round_trip_time {
             Some(old_rtt) => {
                 // (rtt / div) + (old_rtt * (div-1)/div)
@@ -170,6 +199,36 @@ impl ServerDescription {
         }
     }
 
+    /// Estimates the replication lag of this server in milliseconds. The estimate is taken
+    /// relative to the primary's description if one is known, and otherwise relative to the
+    /// most recent write date among secondaries. Returns None if the server has not reported
+    /// its last write date.
+    pub fn staleness_ms(&self, primary: Option<&ServerDescription>,
+                        max_last_write_date: Option<&DateTime<UTC>>,
+                        heartbeat_frequency_ms: i64) -> Option<i64> {
+
+        let last_write_date = match self.last_write_date {
+            Some(ref date) => date,
+            None => return None,
+        };
+
+        match primary {
+            Some(primary) => {
+                match (&self.last_update_time, &primary.last_update_time, &primary.last_write_date) {
+                    (&Some(ref update_time), &Some(ref p_update_time), &Some(ref p_write_date)) => {
+                        let lag = (update_time.clone() - last_write_date.clone()).num_milliseconds();
+                        let p_lag = (p_update_time.clone() - p_write_date.clone()).num_milliseconds();
+                        Some(lag - p_lag + heartbeat_frequency_ms)
+                    },
+                    _ => None,
+                }
+            },
+            None => max_last_write_date.map(|max_date| {
+                (max_date.clone() - last_write_date.clone()).num_milliseconds() + heartbeat_frequency_ms
+            }),
+        }
+    }
+
     // Sets an encountered error and reverts the server type to Unknown.
     pub fn set_err(&mut self, err: Error) {
         self.err = Arc::new(Some(err));
@@ -202,7 +261,16 @@ impl Server {
         let host_clone = host.clone();
         let desc_clone = description.clone();
 
-        let pool = Arc::new(ConnectionPool::new(host.clone()));
+        let pool = Arc::new(ConnectionPool::with_connector(host.clone(), DEFAULT_POOL_SIZE,
+                                                           client.stream_connector.clone()));
+
+        // Authenticate new connections with the client's current credential,
+        // once any TLS handshake has completed.
+        let handshake_client = client.clone();
+        let _ = pool.set_handshake(Box::new(move |socket: &Stream,
+                                               credential: &ConnectionAuth| {
+            auth::authenticate_socket(&handshake_client, socket, credential)
+        }));
 
         // Fails silently
         let monitor = Arc::new(Monitor::new(client, host_clone, pool.clone(),
@@ -223,9 +291,33 @@ impl Server {
         }
     }
 
-    /// Returns a server stream from the connection pool.
-    pub fn acquire_stream(&self) -> Result<PooledStream> {
-        self.pool.acquire_stream()
+    /// Returns a server stream from the connection pool, waiting no later than the deadline.
+    /// New connections are authenticated with the given credential.
+    pub fn acquire_stream(&self, deadline: &Deadline,
+                          auth: &ConnectionAuth) -> Result<PooledStream> {
+        self.pool.acquire_authenticated_stream(deadline, auth)
+    }
+
+    /// Returns a handle to the server's connection pool, which remains usable after the
+    /// topology lock guarding the server is released.
+    pub fn pool(&self) -> Arc<ConnectionPool> {
+        self.pool.clone()
+    }
+
+    /// Closes idle connections and marks connections in use to be closed when returned,
+    /// so that subsequent operations reconnect.
+    pub fn clear_pool(&self) {
+        self.pool.clear();
+    }
+
+    /// Returns the maximum number of connections to this server.
+    pub fn pool_size(&self) -> usize {
+        self.pool.size()
+    }
+
+    /// Returns the number of operations currently in flight on this server.
+    pub fn operation_count(&self) -> usize {
+        self.pool.operation_count()
     }
 
     /// Request an update from the monitor on the server status.
@@ -14,6 +14,7 @@ pub enum OpCode {
     Insert = 2002,
     Query = 2004,
     GetMore = 2005,
+    Delete = 2006,
 }
 
 impl OpCode {
@@ -34,6 +35,7 @@ impl OpCode {
             2002 => Some(OpCode::Insert),
             2004 => Some(OpCode::Query),
             2005 => Some(OpCode::GetMore),
+            2006 => Some(OpCode::Delete),
             _ => None
         }
     }
@@ -47,6 +49,7 @@ impl fmt::Display for OpCode {
             &OpCode::Insert => write!(fmt, "OP_INSERT"),
             &OpCode::Query => write!(fmt, "OP_QUERY"),
             &OpCode::GetMore => write!(fmt, "OP_GET_MORE"),
+            &OpCode::Delete => write!(fmt, "OP_DELETE"),
         }
     }
 }
@@ -102,6 +105,12 @@ impl Header {
         Header::new_request(message_length, request_id, OpCode::GetMore)
     }
 
+    /// Constructs a new Header for an OP_DELETE, with `response_to` set to 0 and
+    /// `op_code` set to `Delete`.
+    pub fn new_delete(message_length: i32, request_id: i32) -> Header {
+        Header::new_request(message_length, request_id, OpCode::Delete)
+    }
+
     /// Writes the serialized Header to a buffer.
     ///
     /// # Arguments
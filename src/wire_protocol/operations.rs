@@ -4,7 +4,7 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use Error::{ArgumentError, ResponseError};
 use Result;
 use wire_protocol::header::{Header, OpCode};
-use wire_protocol::flags::{OpInsertFlags, OpQueryFlags,
+use wire_protocol::flags::{OpDeleteFlags, OpInsertFlags, OpQueryFlags,
                            OpReplyFlags, OpUpdateFlags};
 
 use std::io::{Read, Write};
@@ -101,6 +101,18 @@ pub enum Message {
         number_to_return: i32,
         /// Uniquely identifies the cursor being returned.
         cursor_id: i64,
+    },
+    OpDelete {
+        /// The message header.
+        header: Header,
+        // The wire protocol specifies that a 32-bit 0 field goes here
+        /// The full qualified name of the collection, beginning with the
+        /// database name and a dot separator.
+        namespace: String,
+        /// A bit vector of delete options.
+        flags: OpDeleteFlags,
+        /// Identifies the document(s) to be removed.
+        selector: bson::Document,
     }
 }
 
@@ -116,6 +128,18 @@ impl Message {
                            documents: documents }
     }
 
+    /// Returns the request id from the message header.
+    pub fn request_id(&self) -> i32 {
+        match self {
+            &Message::OpReply { ref header, .. } => header.request_id,
+            &Message::OpUpdate { ref header, .. } => header.request_id,
+            &Message::OpInsert { ref header, .. } => header.request_id,
+            &Message::OpQuery { ref header, .. } => header.request_id,
+            &Message::OpGetMore { ref header, .. } => header.request_id,
+            &Message::OpDelete { ref header, .. } => header.request_id,
+        }
+    }
+
     /// Constructs a new message for an update.
     pub fn new_update(request_id: i32, namespace: String, flags: OpUpdateFlags,
                       selector: bson::Document,
@@ -219,6 +243,28 @@ impl Message {
                              cursor_id: cursor_id }
     }
 
+    /// Constructs a new message request for a deletion.
+    pub fn new_delete(request_id: i32, namespace: String, flags: OpDeleteFlags,
+                      selector: bson::Document) -> Result<Message> {
+        let header_length = mem::size_of::<Header>() as i32;
+
+        // Add an extra byte after the string for null-termination.
+        let string_length = namespace.len() as i32 + 1;
+
+        // There are two i32 fields -- `flags` is represented in the struct as
+        // a bit vector, and the wire protocol-specified ZERO field.
+        let i32_length = mem::size_of::<i32>() as i32 * 2;
+
+        let selector_length = try!(selector.byte_length());
+
+        let total_length = header_length + string_length + i32_length + selector_length;
+
+        let header = Header::new_delete(total_length, request_id);
+
+        Ok(Message::OpDelete { header: header, namespace: namespace,
+                               flags: flags, selector: selector })
+    }
+
     /// Writes a serialized BSON document to a given buffer.
     ///
     /// # Arguments
@@ -400,6 +446,42 @@ impl Message {
         Ok(())
     }
 
+    /// Writes a serialized delete message to a given buffer.
+    ///
+    /// # Arguments
+    ///
+    /// `buffer` - The buffer to write to.
+    /// `header` - The header for the given message.
+    /// `namespace` - The full qualified name of the collection, beginning with
+    ///               the database name and a dot.
+    /// `flags` - Bit vector of delete options.
+    /// `selector` - Identifies the document(s) to be removed.
+    ///
+    /// # Return value
+    ///
+    /// Returns nothing on success, or an Error on failure.
+    pub fn write_delete(buffer: &mut Write, header: &Header, namespace: &str,
+                        flags: &OpDeleteFlags, selector: &bson::Document) -> Result<()> {
+
+        try!(header.write(buffer));
+
+        // Write ZERO field
+        try!(buffer.write_i32::<LittleEndian>(0));
+
+        for byte in namespace.bytes() {
+            try!(buffer.write_u8(byte));
+        }
+
+        // Writes the null terminator for the collection name string.
+        try!(buffer.write_u8(0));
+
+        try!(buffer.write_i32::<LittleEndian>(flags.to_i32()));
+        try!(Message::write_bson_document(buffer, selector));
+
+        let _ = buffer.flush();
+        Ok(())
+    }
+
     /// Attemps to write the serialized message to a buffer.
     ///
     /// # Arguments
@@ -432,7 +514,11 @@ impl Message {
             &Message::OpGetMore { ref header, ref namespace,
                                   number_to_return, cursor_id } =>
                 Message::write_get_more(buffer, &header, &namespace,
-                                        number_to_return, cursor_id)
+                                        number_to_return, cursor_id),
+            &Message::OpDelete { ref header, ref namespace,
+                                 ref flags, ref selector } =>
+                Message::write_delete(buffer, &header, &namespace,
+                                      &flags, &selector),
         }
     }
 
@@ -1,6 +1,7 @@
 use bson::Bson;
 use mongodb::coll::options::WriteModel;
 use mongodb::{Client, ThreadedClient};
+use mongodb::common::WriteConcern;
 use mongodb::db::ThreadedDatabase;
 
 #[test]
@@ -16,7 +17,7 @@ fn bulk_ordered_insert_only() {
         "x" => (i * 11)
     }}).collect();
 
-    coll.bulk_write(models, true);
+    coll.bulk_write(models, true, None);
 
     let cursor : Vec<_> = coll.find(None, None).unwrap().collect();
 
@@ -51,7 +52,7 @@ fn bulk_unordered_insert_only() {
         "x" => (i * 11)
     }}).collect();
 
-    coll.bulk_write(models, false);
+    coll.bulk_write(models, false, None);
 
     let cursor : Vec<_> = coll.find(None, None).unwrap().collect();
 
@@ -141,7 +142,7 @@ fn bulk_ordered_mix() {
 
     coll.drop().unwrap();
 
-    let result = coll.bulk_write(models, true);
+    let result = coll.bulk_write(models, true, None);
 
     assert_eq!(result.inserted_count, 9);
     assert_eq!(result.inserted_ids.len() as i32, result.inserted_count);
@@ -172,3 +173,23 @@ fn bulk_ordered_mix() {
     check_value_in_tree!(result.inserted_ids, 12, 104);
     check_value_in_tree!(result.upserted_ids, 8, 6);
 }
+
+#[test]
+fn bulk_write_concern() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("test");
+    let coll = db.collection_with_prefs("bulk_write_concern", false, None,
+                                        Some(WriteConcern::unacknowledged()));
+
+    coll.drop().unwrap();
+
+    let models = vec![WriteModel::InsertOne { document: doc! { "_id" => 1 } }];
+    let result = coll.bulk_write(models, true, Some(WriteConcern::new()));
+    assert!(result.acknowledged);
+    assert_eq!(result.inserted_count, 1);
+
+    let acknowledged = db.collection("bulk_write_concern");
+    let models = vec![WriteModel::InsertOne { document: doc! { "_id" => 2 } }];
+    let result = acknowledged.bulk_write(models, true, Some(WriteConcern::unacknowledged()));
+    assert!(!result.acknowledged);
+}
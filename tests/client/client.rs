@@ -1,6 +1,9 @@
-use bson;
-use mongodb::{Client, ThreadedClient};
+use bson::{self, Bson};
+use mongodb::{Client, ClientOptions, Error, ThreadedClient};
+use mongodb::coll::options::FindOptions;
+use mongodb::common::Deadline;
 use mongodb::db::ThreadedDatabase;
+use std::i64;
 use std::thread;
 
 #[test]
@@ -84,3 +87,127 @@ fn is_sync() {
     assert!(results.contains(&"concurrent_db".to_owned()));
     assert!(results.contains(&"concurrent_db_2".to_owned()));
 }
+
+#[test]
+fn deadline() {
+    let unbounded = Deadline::new(None);
+    assert!(!unbounded.is_bounded());
+    assert_eq!(None, unbounded.remaining_ms());
+    assert_eq!(500, unbounded.cap_ms(500));
+    assert!(unbounded.check().is_ok());
+
+    let disabled = Deadline::new(Some(0));
+    assert!(!disabled.is_bounded());
+
+    let overflowing = Deadline::new(Some(i64::MAX));
+    assert!(!overflowing.is_bounded());
+
+    let bounded = Deadline::new(Some(60000));
+    assert!(bounded.is_bounded());
+    assert!(bounded.remaining_ms().unwrap() <= 60000);
+    assert_eq!(500, bounded.cap_ms(500));
+    assert!(bounded.check().is_ok());
+
+    let expired = Deadline::new(Some(1));
+    thread::sleep_ms(5);
+    assert!(expired.is_expired());
+    assert_eq!(0, expired.cap_ms(500));
+    match expired.check() {
+        Err(Error::TimeoutError) => (),
+        _ => panic!("Expected an expired deadline to return a timeout error."),
+    }
+}
+
+#[test]
+fn timeout_bounds_server_selection() {
+    let mut options = ClientOptions::new();
+    options.timeout_ms = Some(200);
+
+    let client = Client::with_uri_and_options("mongodb://i-dont-exist:27017", options).unwrap();
+    assert_eq!(Some(200), client.db("test").timeout_ms);
+    assert_eq!(None, client.db("test").with_timeout(None).timeout_ms);
+
+    match client.db("test").collection("timeout").find_one(None, None) {
+        Err(Error::TimeoutError) => (),
+        Err(err) => panic!("Expected a timeout error, but received: {}", err),
+        Ok(_) => panic!("Expected a timeout error, but the operation succeeded."),
+    }
+}
+
+#[test]
+fn server_status() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let status = client.server_status().unwrap();
+
+    assert!(!status.version.is_empty());
+    assert!(status.uptime_secs >= 0.0);
+    assert!(status.connections.current >= 1);
+    assert!(status.opcounters.command >= 1);
+
+    if let Some(cache) = status.wired_tiger_cache {
+        assert!(cache.maximum_bytes_configured > 0);
+    }
+}
+
+#[test]
+fn list_databases() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("list_databases");
+    db.drop_database().unwrap();
+    db.collection("test").insert_one(doc! { "x" => 1 }, None).unwrap();
+
+    let specs = client.list_databases(None, false).unwrap();
+    let spec = specs.iter().find(|spec| spec.name == "list_databases").unwrap();
+    assert!(spec.size_on_disk.is_some());
+    assert_eq!(Some(false), spec.empty);
+
+    let specs = client.list_databases(Some(doc! { "name" => "list_databases" }), true).unwrap();
+    assert_eq!(1, specs.len());
+    assert_eq!("list_databases", specs[0].name);
+    assert!(specs[0].size_on_disk.is_none());
+}
+
+#[test]
+fn current_op_and_kill_op() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let coll = client.db("current_op_and_kill_op").collection("slow");
+    coll.drop().unwrap();
+
+    let docs = (0..20).map(|i| doc! { "_id" => i }).collect();
+    coll.insert_many(docs, None).unwrap();
+
+    let query = doc! {
+        "$where" => (Bson::JavaScriptCode("function() { sleep(200); return true; }".to_owned()))
+    };
+
+    let mut options = FindOptions::new();
+    options.comment = Some("current_op_and_kill_op".to_owned());
+
+    let slow = thread::spawn(move || {
+        coll.find(Some(query), Some(options)).and_then(|mut cursor| cursor.next_n(20))
+    });
+
+    // Servers before MongoDB 3.2 report the comment as part of the query.
+    let clauses = vec![Bson::Document(doc! { "command.comment" => "current_op_and_kill_op" }),
+                       Bson::Document(doc! { "query.$comment" => "current_op_and_kill_op" })];
+    let mut filter = bson::Document::new();
+    filter.insert("$or".to_owned(), Bson::Array(clauses));
+
+    let mut ops = vec![];
+    for _ in 0..50 {
+        ops = client.current_op(Some(filter.clone())).unwrap();
+        if !ops.is_empty() {
+            break;
+        }
+        thread::sleep_ms(20);
+    }
+
+    assert_eq!(1, ops.len());
+    assert_eq!("current_op_and_kill_op.slow", ops[0].ns);
+    assert!(ops[0].client.is_some());
+    assert!(ops[0].command.is_some());
+    assert_eq!(Some(Bson::String("current_op_and_kill_op".to_owned())), ops[0].comment);
+
+    client.kill_op(ops[0].opid.clone()).unwrap();
+    assert!(slow.join().unwrap().is_err());
+}
@@ -1,9 +1,13 @@
 use bson::Bson;
 
 use mongodb::{Client, ThreadedClient};
+use mongodb::common::{Acknowledgment, WriteConcern};
 use mongodb::db::ThreadedDatabase;
-use mongodb::coll::options::{FindOptions, FindOneAndUpdateOptions,
-                             IndexModel, IndexOptions, ReturnDocument};
+use mongodb::coll::options::{CollModOptions, FindOptions, FindOneAndUpdateOptions,
+                             IndexModel, IndexOptions, IndexSelector, ReturnDocument};
+use mongodb::db::options::{ValidationAction, ValidationLevel};
+
+use std::thread;
 
 #[test]
 fn find_sorted() {
@@ -460,6 +464,110 @@ fn delete_one() {
     assert!(cursor.next().is_none());
 }
 
+#[test]
+fn unacknowledged_writes() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("test");
+    let coll = db.collection_with_prefs("unacknowledged_writes", false, None,
+                                        Some(WriteConcern::unacknowledged()));
+
+    coll.drop().ok().expect("Failed to drop database");
+
+    let doc1 = doc! { "title" => "Jaws" };
+    let doc2 = doc! { "title" => "Back to the Future" };
+
+    let result = coll.insert_many(vec![doc1.clone(), doc2.clone()], None)
+        .ok().expect("Failed to insert documents.");
+    assert!(!result.acknowledged);
+    assert_eq!(2, result.inserted_ids.unwrap().len());
+
+    let result = coll.update_one(doc1.clone(), doc! { "$set" => { "director" => "Spielberg" } }, None)
+        .ok().expect("Failed to update document.");
+    assert!(!result.acknowledged);
+
+    let result = coll.delete_one(doc2.clone(), None).ok().expect("Failed to delete document.");
+    assert!(!result.acknowledged);
+
+    let result = coll.insert_one(doc! { "title" => "Jurassic Park" }, Some(WriteConcern::new()))
+        .ok().expect("Failed to insert document.");
+    assert!(result.acknowledged);
+
+    // The unacknowledged writes may have gone out over other pooled connections, so wait
+    // for the server to apply them.
+    let mut applied = false;
+    for _ in 0..50 {
+        if coll.count(None, None).unwrap() == 2 &&
+           coll.count(Some(doc! { "director" => "Spielberg" }), None).unwrap() == 1 {
+            applied = true;
+            break;
+        }
+        thread::sleep_ms(20);
+    }
+
+    assert!(applied);
+}
+
+#[test]
+fn unacknowledged_large_insert_many() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("test");
+    let coll = db.collection_with_prefs("unacknowledged_large_insert_many", false, None,
+                                        Some(WriteConcern::unacknowledged()));
+
+    coll.drop().ok().expect("Failed to drop database");
+
+    // About 50MB of documents, more than fits in a single 48MB message.
+    let padding: String = (0..1024 * 1024).map(|_| 'x').collect();
+    let docs = (0..50).map(|i| doc! { "_id" => (i), "padding" => (padding.clone()) }).collect();
+
+    let result = coll.insert_many(docs, None).ok().expect("Failed to insert documents.");
+    assert!(!result.acknowledged);
+
+    let mut applied = false;
+    for _ in 0..250 {
+        if coll.count(None, None).unwrap() == 50 {
+            applied = true;
+            break;
+        }
+        thread::sleep_ms(20);
+    }
+
+    assert!(applied);
+}
+
+#[test]
+fn write_concern_encoding() {
+    let mut wc = WriteConcern::majority();
+    wc.w_timeout = 500;
+    wc.j = true;
+
+    let doc = wc.to_bson();
+    assert_eq!(Some(&Bson::String("majority".to_owned())), doc.get("w"));
+    assert_eq!(Some(&Bson::I32(500)), doc.get("wtimeout"));
+    assert_eq!(Some(&Bson::Boolean(true)), doc.get("j"));
+    assert!(doc.get("fsync").is_none());
+    assert!(wc.validate().is_ok());
+
+    wc.fsync = true;
+    assert!(wc.validate().is_err());
+
+    let mut wc = WriteConcern::unacknowledged();
+    assert!(!wc.is_acknowledged());
+    assert_eq!(Some(&Bson::I32(0)), wc.to_bson().get("w"));
+    wc.j = true;
+    assert!(wc.validate().is_err());
+
+    let mut wc = WriteConcern::new();
+    wc.w = Acknowledgment::Tag("multiDC".to_owned());
+    wc.fsync = true;
+    assert_eq!(Some(&Bson::String("multiDC".to_owned())), wc.to_bson().get("w"));
+    assert_eq!(Some(&Bson::Boolean(true)), wc.to_bson().get("fsync"));
+    assert!(wc.validate().is_ok());
+
+    wc.w = Acknowledgment::Nodes(-1);
+    assert!(wc.validate().is_err());
+}
+
 #[test]
 fn delete_many() {
     let client = Client::connect("localhost", 27017).unwrap();
@@ -704,3 +812,50 @@ fn drop_all_indexes() {
 
     assert_eq!(1, results.len());
 }
+
+#[test]
+fn rename_coll_mod_convert_to_capped() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("test-client-coll-admin");
+    db.drop_database().unwrap();
+
+    let coll = db.collection("events");
+    coll.insert_one(doc! { "kind" => "start" }, None).unwrap();
+
+    let mut index_options = IndexOptions::new();
+    index_options.expire_after_seconds = Some(3600);
+    coll.create_index(doc! { "createdAt" => 1 }, Some(index_options)).unwrap();
+
+    // Rename, replacing an existing collection with the new name.
+    db.collection("logs").insert_one(doc! { "kind" => "stale" }, None).unwrap();
+    assert!(coll.rename("logs", false).is_err());
+    let logs = coll.rename("logs", true).unwrap();
+    assert_eq!("logs", logs.name());
+    assert_eq!(1, logs.count(None, None).unwrap());
+
+    let options = CollModOptions::new()
+        .with_validator(doc! { "kind" => { "$exists" => true } })
+        .with_validation_level(ValidationLevel::Strict)
+        .with_validation_action(ValidationAction::Error)
+        .with_index_ttl(IndexSelector::Keys(doc! { "createdAt" => 1 }), 60);
+    logs.coll_mod(options).unwrap();
+
+    assert!(logs.insert_one(doc! { "other" => 1 }, None).is_err());
+
+    let mut cursor = logs.list_indexes().unwrap();
+    let ttl = cursor.next_n(10).unwrap().into_iter().filter_map(|index| {
+        match index.get("expireAfterSeconds") {
+            Some(&Bson::I32(seconds)) => Some(seconds as i64),
+            Some(&Bson::I64(seconds)) => Some(seconds),
+            Some(&Bson::FloatingPoint(seconds)) => Some(seconds as i64),
+            _ => None,
+        }
+    }).next();
+    assert_eq!(Some(60), ttl);
+
+    assert!(logs.convert_to_capped(0).is_err());
+    logs.convert_to_capped(1 << 20).unwrap();
+
+    let stats = db.command(doc! { "collStats" => "logs" }, mongodb::CommandType::Suppressed, None).unwrap();
+    assert_eq!(Some(&Bson::Boolean(true)), stats.get("capped"));
+}
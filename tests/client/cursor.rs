@@ -1,11 +1,15 @@
 use bson::{Bson, Document};
 
-use mongodb::{Client, CommandType, ThreadedClient};
-use mongodb::common::{ReadMode, ReadPreference};
+use mongodb::{Client, CommandType, Error, ThreadedClient};
+use mongodb::cancel::CancellationToken;
+use mongodb::coll::options::FindOptions;
+use mongodb::common::{Deadline, ReadMode, ReadPreference};
 use mongodb::db::ThreadedDatabase;
 use mongodb::cursor::Cursor;
 use mongodb::wire_protocol::flags::OpQueryFlags;
 
+use std::thread;
+
 #[test]
 fn cursor_features() {
     let client = Client::connect("localhost", 27017).unwrap();
@@ -25,7 +29,8 @@ fn cursor_features() {
 
     let result = Cursor::query(client.clone(), "test.cursor_test".to_owned(),
                                3, flags, 0, 0, doc, None, CommandType::Find,
-                               false, ReadPreference::new(ReadMode::Primary, None));
+                               false, ReadPreference::new(ReadMode::Primary, None),
+                               Deadline::none(), None);
 
     let mut cursor = match result {
         Ok(c) => c,
@@ -67,3 +72,141 @@ fn cursor_features() {
         };
     }
 }
+
+#[test]
+fn cancellation_token() {
+    let token = CancellationToken::new();
+    assert!(!token.is_cancelled());
+    assert!(token.check().is_ok());
+    assert!(token.tag() != CancellationToken::new().tag());
+
+    let clone = token.clone();
+    clone.cancel();
+    assert!(token.is_cancelled());
+
+    match token.check() {
+        Err(Error::CancelledError) => (),
+        _ => panic!("Expected a cancelled token to return a cancellation error."),
+    }
+
+    // Operations using a cancelled token fail before selecting a server.
+    let client = Client::connect("localhost", 27017).unwrap();
+    let coll = client.db("test").collection("cursor_test");
+    let mut options = FindOptions::new();
+    options.cancellation_token = Some(token);
+
+    match coll.find(None, Some(options)) {
+        Err(Error::CancelledError) => (),
+        _ => panic!("Expected a cancelled find to return a cancellation error."),
+    }
+}
+
+// Returns the number of operations in progress that match the filter.
+fn tagged_operations(client: &Client, filter: Document) -> usize {
+    let mut current_op = doc! { "currentOp" => 1 };
+    for (key, value) in filter.into_iter() {
+        current_op.insert(key, value);
+    }
+
+    let reply = client.db("admin").command(current_op, CommandType::Suppressed, None).unwrap();
+    match reply.get("inprog") {
+        Some(&Bson::Array(ref ops)) => ops.len(),
+        _ => panic!("Expected currentOp to return an 'inprog' array."),
+    }
+}
+
+#[test]
+fn cancel_in_flight_operation() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let coll = client.db("test").collection("cancel_in_flight");
+    coll.drop().unwrap();
+
+    let docs = (0..20).map(|i| doc! { "_id" => i }).collect();
+    coll.insert_many(docs, None).unwrap();
+
+    let token = CancellationToken::new();
+    let mut options = FindOptions::new();
+    options.cancellation_token = Some(token.clone());
+
+    let query = doc! {
+        "$where" => (Bson::JavaScriptCode("function() { sleep(200); return true; }".to_owned()))
+    };
+
+    let slow = thread::spawn(move || {
+        coll.find(Some(query), Some(options)).and_then(|mut cursor| cursor.next_n(20))
+    });
+
+    // Wait for the query to start on the server.
+    let mut filter = Document::new();
+    filter.insert("command.comment".to_owned(), Bson::String(token.tag().to_owned()));
+    let mut legacy_filter = Document::new();
+    legacy_filter.insert("query.$comment".to_owned(), Bson::String(token.tag().to_owned()));
+    let mut or_filter = Document::new();
+    or_filter.insert("$or".to_owned(), Bson::Array(vec![Bson::Document(filter),
+                                                        Bson::Document(legacy_filter)]));
+
+    let mut started = false;
+    for _ in 0..50 {
+        if tagged_operations(&client, or_filter.clone()) > 0 {
+            started = true;
+            break;
+        }
+        thread::sleep_ms(20);
+    }
+    assert!(started);
+
+    token.cancel_and_kill(client.clone()).unwrap();
+
+    match slow.join().unwrap() {
+        Err(Error::CancelledError) => (),
+        _ => panic!("Expected the in-flight find to return a cancellation error."),
+    }
+
+    // The server-side operation was killed as well.
+    let mut killed = false;
+    for _ in 0..50 {
+        if tagged_operations(&client, or_filter.clone()) == 0 {
+            killed = true;
+            break;
+        }
+        thread::sleep_ms(20);
+    }
+    assert!(killed);
+}
+
+#[test]
+fn cancel_concurrent_operations() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let coll = client.db("test").collection("cancel_concurrent");
+    coll.drop().unwrap();
+
+    let docs = (0..20).map(|i| doc! { "_id" => (i) }).collect();
+    coll.insert_many(docs, None).unwrap();
+
+    let token = CancellationToken::new();
+
+    // Both operations share the token, so cancelling it must interrupt each of them.
+    let slow: Vec<_> = (0..2).map(|_| {
+        let coll = client.db("test").collection("cancel_concurrent");
+        let mut options = FindOptions::new();
+        options.cancellation_token = Some(token.clone());
+
+        let query = doc! {
+            "$where" => (Bson::JavaScriptCode("function() { sleep(200); return true; }".to_owned()))
+        };
+
+        thread::spawn(move || {
+            coll.find(Some(query), Some(options)).and_then(|mut cursor| cursor.next_n(20))
+        })
+    }).collect();
+
+    thread::sleep_ms(500);
+    token.cancel();
+
+    for handle in slow {
+        match handle.join().unwrap() {
+            Err(Error::CancelledError) => (),
+            _ => panic!("Expected each in-flight find to return a cancellation error."),
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use bson::{self, Bson};
-use mongodb::{Client, ThreadedClient};
+use mongodb::{Client, CommandType, ThreadedClient};
 use mongodb::db::ThreadedDatabase;
-use mongodb::db::options::CreateUserOptions;
-use mongodb::db::roles::{AllDatabaseRole, SingleDatabaseRole, Role};
+use mongodb::db::options::{CreateCollectionOptions, CreateRoleOptions, CreateUserOptions,
+                           ListCollectionsOptions, RoleInfoOptions, UpdateRoleOptions,
+                           UpdateUserOptions, ValidationAction, ValidationLevel};
+use mongodb::db::specs::CollectionType;
+use mongodb::error::Error::ArgumentError;
+use mongodb::db::roles::{AllDatabaseRole, Privilege, Resource, SingleDatabaseRole, Role};
 
 #[test]
 fn create_collection() {
@@ -148,3 +152,193 @@ fn create_and_get_users() {
         _ => panic!("User isn't named 'val' but should be")
     };
 }
+
+#[test]
+fn update_users_and_roles() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("update_users_and_roles");
+
+    db.drop_all_users(None).unwrap();
+    let _ = db.command(doc! { "dropAllRolesFromDatabase" => 1 }, CommandType::Suppressed, None);
+
+    // Create a custom role with a typed privilege, inheriting a built-in role.
+    let mut role_options = CreateRoleOptions::new();
+    role_options.privileges = vec![Privilege::new(Resource::Collection {
+        db: "update_users_and_roles".to_owned(),
+        collection: "logs".to_owned(),
+    }, vec!["find", "insert"])];
+    role_options.roles = vec![Role::All(AllDatabaseRole::Read)];
+
+    db.create_role("logWriter", Some(role_options)).unwrap();
+    db.grant_privileges_to_role("logWriter", vec![Privilege::new(Resource::Collection {
+        db: "update_users_and_roles".to_owned(),
+        collection: "logs".to_owned(),
+    }, vec!["remove"])], None).unwrap();
+
+    let mut info_options = RoleInfoOptions::new();
+    info_options.show_privileges = true;
+
+    let roles = db.roles_info(vec!["logWriter"], Some(info_options)).unwrap();
+    assert_eq!(1, roles.len());
+
+    match roles[0].get("privileges") {
+        Some(&Bson::Array(ref privileges)) => assert_eq!(1, privileges.len()),
+        _ => panic!("Expected privileges for role 'logWriter'")
+    };
+
+    let mut update = UpdateRoleOptions::new();
+    update.roles = Some(vec![]);
+    db.update_role("logWriter", update).unwrap();
+
+    // Grant the custom role to a user, then revoke it.
+    db.create_user("logger", "ilikelogs!", None).unwrap();
+    let custom = || Role::Custom { role: "logWriter".to_owned(),
+                                   db: "update_users_and_roles".to_owned() };
+
+    db.grant_roles_to_user("logger", vec![custom()], None).unwrap();
+    let user = db.get_user("logger", None).unwrap();
+
+    match user.get("roles") {
+        Some(&Bson::Array(ref roles)) => assert_eq!(1, roles.len()),
+        _ => panic!("Expected roles for user 'logger'")
+    };
+
+    db.revoke_roles_from_user("logger", vec![custom()], None).unwrap();
+
+    let mut user_options = UpdateUserOptions::new();
+    user_options.custom_data = Some(doc! { "team" => "ops" });
+    db.update_user("logger", user_options).unwrap();
+
+    let user = db.get_user("logger", None).unwrap();
+
+    match user.get("roles") {
+        Some(&Bson::Array(ref roles)) => assert!(roles.is_empty()),
+        _ => panic!("Expected roles for user 'logger'")
+    };
+
+    match user.get("customData") {
+        Some(&Bson::Document(ref data)) => assert_eq!(Some(&Bson::String("ops".to_owned())),
+                                                      data.get("team")),
+        _ => panic!("Expected custom data for user 'logger'")
+    };
+
+    db.drop_role("logWriter", None).unwrap();
+    assert!(db.roles_info(vec![], None).unwrap().is_empty());
+}
+
+#[test]
+fn create_collection_with_validator_and_view() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("create_collection_with_validator_and_view");
+    db.drop_database().unwrap();
+
+    let mut options = CreateCollectionOptions::new();
+    options.validator = Some(doc! { "age" => { "$gte" => 0 } });
+    options.validation_level = Some(ValidationLevel::Strict);
+    options.validation_action = Some(ValidationAction::Error);
+    options.collation = Some(doc! { "locale" => "en", "strength" => 2 });
+    db.create_collection("people", Some(options)).unwrap();
+
+    let coll = db.collection("people");
+    coll.insert_one(doc! { "name" => "Ann", "age" => 30 }, None).unwrap();
+    assert!(coll.insert_one(doc! { "name" => "Bob", "age" => (-1) }, None).is_err());
+
+    let pipeline = vec![doc! { "$project" => { "name" => 1 } }];
+    db.create_view("names", "people", pipeline, None).unwrap();
+
+    let view = db.collection("names");
+    let doc = view.find_one(None, None).unwrap().unwrap();
+    assert_eq!(Some(&Bson::String("Ann".to_owned())), doc.get("name"));
+    assert!(doc.get("age").is_none());
+
+    // A pipeline without a source is rejected before reaching the server.
+    let mut options = CreateCollectionOptions::new();
+    options.pipeline = Some(vec![]);
+    assert!(db.create_collection("invalid", Some(options)).is_err());
+
+    // Options that views do not accept are rejected rather than dropped.
+    let mut options = CreateCollectionOptions::new();
+    options.capped = true;
+    options.size = Some(1 << 20);
+    match db.create_view("invalid_view", "people", vec![], Some(options)) {
+        Err(ArgumentError(_)) => (),
+        _ => panic!("Expected ArgumentError for a capped view"),
+    };
+
+    let mut options = CreateCollectionOptions::new();
+    options.validator = Some(doc! { "age" => { "$gte" => 0 } });
+    match db.create_view("invalid_view", "people", vec![], Some(options)) {
+        Err(ArgumentError(_)) => (),
+        _ => panic!("Expected ArgumentError for a view with a validator"),
+    };
+
+    let mut options = CreateCollectionOptions::new();
+    options.collation = Some(doc! { "locale" => "en" });
+    db.create_view("collated_names", "people", vec![], Some(options)).unwrap();
+}
+
+#[test]
+fn database_and_collection_stats() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("database_and_collection_stats");
+    db.drop_database().unwrap();
+
+    let coll = db.collection("items");
+    for i in 0..10 {
+        coll.insert_one(doc! { "i" => i }, None).unwrap();
+    }
+    coll.create_index(doc! { "i" => 1 }, None).unwrap();
+
+    let stats = db.stats().unwrap();
+    assert_eq!("database_and_collection_stats", stats.db);
+    assert!(stats.collections >= 1);
+    assert!(stats.objects >= 10);
+    assert!(stats.data_size > 0);
+
+    let stats = coll.stats().unwrap();
+    assert_eq!("database_and_collection_stats.items", stats.ns);
+    assert_eq!(10, stats.count);
+    assert_eq!(2, stats.nindexes);
+    assert!(stats.index_sizes.contains_key("_id_"));
+    assert!(stats.index_sizes.contains_key("i_1"));
+    assert!(!stats.capped);
+}
+
+#[test]
+fn list_collection_specifications() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("list_collection_specifications");
+    db.drop_database().unwrap();
+
+    let mut options = CreateCollectionOptions::new();
+    options.capped = true;
+    options.size = Some(1 << 20);
+    db.create_collection("capped", Some(options)).unwrap();
+    db.create_view("capped_view", "capped", vec![], None).unwrap();
+
+    let specs = db.list_collections(Some(doc! { "name" => { "$in" => ["capped", "capped_view"] } }),
+                                    None).unwrap();
+    assert_eq!(2, specs.len());
+
+    let coll = specs.iter().find(|spec| spec.name == "capped").unwrap();
+    assert_eq!(CollectionType::Collection, coll.collection_type);
+    assert_eq!(Some(&Bson::Boolean(true)), coll.options.get("capped"));
+    assert!(!coll.info.read_only);
+    assert!(coll.id_index.is_some());
+
+    let view = specs.iter().find(|spec| spec.name == "capped_view").unwrap();
+    assert_eq!(CollectionType::View, view.collection_type);
+    assert_eq!(Some(&Bson::String("capped".to_owned())), view.options.get("viewOn"));
+    assert!(view.info.read_only);
+    assert!(view.id_index.is_none());
+
+    let mut options = ListCollectionsOptions::new();
+    options.name_only = true;
+    let names = db.list_collections(None, Some(options)).unwrap();
+    assert!(names.iter().any(|spec| spec.name == "capped"));
+
+    let mut names = db.collection_names(None).unwrap();
+    names.sort();
+    assert!(names.contains(&"capped".to_owned()));
+    assert!(names.contains(&"capped_view".to_owned()));
+}
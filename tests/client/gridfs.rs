@@ -1,14 +1,20 @@
-use bson::Bson;
+use bson::{Bson, oid};
+use bson::spec::BinarySubtype;
 
 use mongodb::{Client, ThreadedClient};
 use mongodb::coll::Collection;
 use mongodb::coll::options::{FindOptions, IndexOptions};
 use mongodb::db::ThreadedDatabase;
-use mongodb::gridfs::{Store, ThreadedStore};
+use mongodb::gridfs::{FileSelector, Progress, Store, ThreadedStore};
+use mongodb::gridfs::bucket::GridFsBucket;
 use mongodb::gridfs::file::DEFAULT_CHUNK_SIZE;
+use mongodb::gridfs::options::{GridFsBucketOptions, GridFsDownloadByNameOptions,
+                                GridFsStoreOptions, GridFsUploadOptions};
 
 use rand::{thread_rng, Rng};
-use std::io::{Read, Write};
+use std::i32;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::thread;
 
 fn init_gridfs(name: &str) -> (Store, Collection, Collection) {
     let client = Client::connect("localhost", 27017).unwrap();
@@ -37,7 +43,7 @@ fn put_get() {
     let src_len = (DEFAULT_CHUNK_SIZE as f32 * 2.5) as usize;
     let mut src = gen_rand_file(src_len);
 
-    let mut grid_file = fs.create(name.to_owned()).unwrap();
+    let mut grid_file = fs.create(name.to_owned(), None).unwrap();
     let id = grid_file.id.clone();
     let _ = grid_file.write(&mut src).unwrap();
     let _ = grid_file.close().unwrap();
@@ -106,7 +112,7 @@ fn remove() {
     let src_len = (DEFAULT_CHUNK_SIZE as f32 * 1.5) as usize;
     let mut src = gen_rand_file(src_len);
 
-    let mut grid_file = fs.create(name.to_owned()).unwrap();
+    let mut grid_file = fs.create(name.to_owned(), None).unwrap();
     let id = grid_file.id.clone();
     let _ = grid_file.write(&mut src).unwrap();
     let _ = grid_file.close().unwrap();
@@ -133,7 +139,7 @@ fn remove_id() {
     let src_len = (DEFAULT_CHUNK_SIZE as f32 * 1.5) as usize;
     let mut src = gen_rand_file(src_len);
 
-    let mut grid_file = fs.create(name.to_owned()).unwrap();
+    let mut grid_file = fs.create(name.to_owned(), None).unwrap();
     let id = grid_file.id.clone();
     let _ = grid_file.write(&mut src).unwrap();
     let _ = grid_file.close().unwrap();
@@ -160,12 +166,12 @@ fn find() {
     let src_len = (DEFAULT_CHUNK_SIZE as f32 * 1.5) as usize;
     let mut src = gen_rand_file(src_len);
 
-    let mut grid_file = fs.create(name.to_owned()).unwrap();
+    let mut grid_file = fs.create(name.to_owned(), None).unwrap();
     let id = grid_file.id.clone();
     let _ = grid_file.write(&mut src).unwrap();
     let _ = grid_file.close().unwrap();
 
-    let mut grid_file2 = fs.create(name2.to_owned()).unwrap();
+    let mut grid_file2 = fs.create(name2.to_owned(), None).unwrap();
     let id2 = grid_file2.id.clone();
     let _ = grid_file2.write(&mut src).unwrap();
     let _ = grid_file2.close().unwrap();
@@ -180,3 +186,337 @@ fn find() {
     assert_eq!(id, results[0].id);
     assert_eq!(id2, results[1].id);
 }
+
+fn init_bucket(name: &str, bucket_name: &str) -> GridFsBucket {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let mut options = GridFsBucketOptions::new();
+    options.bucket_name = bucket_name.to_owned();
+    options.chunk_size_bytes = 1024;
+
+    let bucket = GridFsBucket::new(client.db(name), Some(options));
+    bucket.drop().ok().expect("Failed to drop bucket.");
+    bucket
+}
+
+#[test]
+fn bucket_upload_download() {
+    let bucket = init_bucket("grid_bucket_upload", "images");
+
+    let src_len = 2500;
+    let src = gen_rand_file(src_len);
+    let id = Bson::ObjectId(bucket.upload_from_stream("image.png", &mut &src[..], None).unwrap());
+
+    let file = bucket.files().find_one(Some(doc!{ "_id" => (id.clone()) }), None).unwrap()
+        .expect("Expected file document in bucket.");
+    assert_eq!(Some(&Bson::I32(1024)), file.get("chunkSize"));
+    assert_eq!(Some(&Bson::I64(src_len as i64)), file.get("length"));
+
+    let mut cursor = bucket.chunks().find(Some(doc!{ "files_id" => (id.clone()) }), None).unwrap();
+    assert_eq!(3, cursor.next_batch().unwrap().len());
+
+    let mut dest = Vec::new();
+    bucket.download_to_stream(id, &mut dest).unwrap();
+    assert_eq!(src, dest);
+}
+
+#[test]
+fn bucket_revisions() {
+    let bucket = init_bucket("grid_bucket_revisions", "fs");
+
+    for i in 0..3 {
+        let src = vec![i as u8; 10];
+        bucket.upload_from_stream("notes.txt", &mut &src[..], None).unwrap();
+        // Upload dates have millisecond precision.
+        thread::sleep_ms(5);
+    }
+
+    let expected = [(0, 0u8), (1, 1), (2, 2), (-1, 2), (-2, 1), (-3, 0)];
+    for &(revision, byte) in expected.iter() {
+        let mut options = GridFsDownloadByNameOptions::new();
+        options.revision = revision;
+
+        let mut dest = Vec::new();
+        bucket.download_to_stream_by_name("notes.txt", &mut dest, Some(options)).unwrap();
+        assert_eq!(vec![byte; 10], dest);
+    }
+
+    let mut options = GridFsDownloadByNameOptions::new();
+    options.revision = 3;
+    assert!(bucket.open_download_stream_by_name("notes.txt", Some(options)).is_err());
+
+    let mut options = GridFsDownloadByNameOptions::new();
+    options.revision = i32::MIN;
+    assert!(bucket.open_download_stream_by_name("notes.txt", Some(options)).is_err());
+}
+
+#[test]
+fn bucket_existing_indexes() {
+    let bucket = init_bucket("grid_bucket_existing_indexes", "fs");
+
+    // An equivalent index created by another driver, with different options.
+    bucket.chunks().create_index(doc!{ "files_id" => 1.0, "n" => 1.0 }, None).unwrap();
+
+    let src = gen_rand_file(2048);
+    bucket.upload_from_stream("existing.bin", &mut &src[..], None).unwrap();
+
+    let indexes: Vec<_> = bucket.chunks().list_indexes().unwrap().collect();
+    assert_eq!(2, indexes.len());
+}
+
+#[test]
+fn bucket_rename_delete() {
+    let bucket = init_bucket("grid_bucket_rename_delete", "fs");
+
+    let src = gen_rand_file(2048);
+    let id = Bson::ObjectId(bucket.upload_from_stream("old.bin", &mut &src[..], None).unwrap());
+
+    bucket.rename(id.clone(), "new.bin").unwrap();
+    let mut cursor = bucket.find(Some(doc!{ "filename" => "new.bin" }), None).unwrap();
+    assert_eq!(id, cursor.next().expect("Expected renamed file.").id);
+
+    bucket.delete(id.clone()).unwrap();
+    assert!(bucket.open_download_stream(id.clone()).is_err());
+
+    let mut cursor = bucket.chunks().find(Some(doc!{ "files_id" => (id.clone()) }), None).unwrap();
+    assert_eq!(0, cursor.next_batch().unwrap().len());
+
+    assert!(bucket.delete(id.clone()).is_err());
+    assert!(bucket.rename(id, "other.bin").is_err());
+}
+
+#[test]
+fn seek_read_range() {
+    let bucket = init_bucket("grid_seek", "fs");
+
+    let src_len = 4000;
+    let src = gen_rand_file(src_len);
+    let id = Bson::ObjectId(bucket.upload_from_stream("video.mp4", &mut &src[..], None).unwrap());
+
+    let mut file = bucket.open_download_stream(id).unwrap();
+
+    // Within a single chunk, across chunk boundaries, and past the end of the file.
+    assert_eq!(&src[10..20], &file.read_range(10, 10).unwrap()[..]);
+    assert_eq!(&src[1000..3100], &file.read_range(1000, 2100).unwrap()[..]);
+    assert_eq!(&src[3990..], &file.read_range(3990, 100).unwrap()[..]);
+    assert_eq!(0, file.read_range(5000, 10).unwrap().len());
+
+    assert_eq!(3000, file.seek(SeekFrom::End(-1000)).unwrap());
+    let mut buf = [0u8; 24];
+    assert_eq!(24, file.read(&mut buf).unwrap());
+    assert_eq!(&src[3000..3024], &buf[..]);
+
+    assert_eq!(2048, file.seek(SeekFrom::Current(-976)).unwrap());
+    let mut rest = Vec::new();
+    file.read_to_end(&mut rest).unwrap();
+    assert_eq!(&src[2048..], &rest[..]);
+
+    assert!(file.seek(SeekFrom::Current(-5000)).is_err());
+}
+
+#[test]
+fn seek_invalid_chunk_size() {
+    let bucket = init_bucket("grid_seek_invalid", "fs");
+
+    let src = gen_rand_file(100);
+    let id = Bson::ObjectId(bucket.upload_from_stream("invalid.bin", &mut &src[..], None).unwrap());
+
+    let mut file = bucket.open_download_stream(id.clone()).unwrap();
+    file.chunk_size = 0;
+    match file.seek(SeekFrom::Start(10)) {
+        Err(ref err) if err.kind() == io::ErrorKind::InvalidData => (),
+        _ => panic!("Expected seeking without a chunk size to return invalid data."),
+    }
+
+    bucket.files().update_one(doc!{ "_id" => (id.clone()) },
+                              doc!{ "$set" => { "chunkSize" => 0 } }, None).unwrap();
+    assert!(bucket.open_download_stream(id).is_err());
+}
+
+#[test]
+fn upload_options_metadata() {
+    let (fs, fsfiles, _) = init_gridfs("grid_metadata");
+
+    let mut options = GridFsUploadOptions::new();
+    options.content_type = Some("video/mp4".to_owned());
+    options.aliases = vec!["clip.mp4".to_owned(), "trailer.mp4".to_owned()];
+    options.metadata = Some(doc!{ "owner" => "alice", "views" => 12 });
+
+    let mut grid_file = fs.create("movie.mp4".to_owned(), Some(options)).unwrap();
+    let id = grid_file.id.clone();
+    let _ = grid_file.write(&gen_rand_file(100)).unwrap();
+    let _ = grid_file.close().unwrap();
+
+    let doc = fsfiles.find_one(Some(doc!{ "_id" => (id.clone()) }), None).unwrap().unwrap();
+    assert_eq!(Some(&Bson::String("video/mp4".to_owned())), doc.get("contentType"));
+    match doc.get("metadata") {
+        Some(&Bson::Document(ref metadata)) => {
+            assert_eq!(Some(&Bson::String("alice".to_owned())), metadata.get("owner"));
+        },
+        _ => panic!("Expected metadata document."),
+    }
+
+    let file = fs.open_id(id.clone()).unwrap();
+    assert_eq!(Some("video/mp4".to_owned()), file.content_type);
+    assert_eq!(vec!["clip.mp4".to_owned(), "trailer.mp4".to_owned()], file.aliases);
+    assert_eq!(Some(&Bson::I32(12)), file.metadata.as_ref().unwrap().get("views"));
+
+    let mut cursor = fs.find_by_metadata(doc!{ "owner" => "alice" }, None).unwrap();
+    assert_eq!(id, cursor.next().expect("Expected file matching metadata.").id);
+
+    let mut cursor = fs.find_by_metadata(doc!{ "owner" => "bob" }, None).unwrap();
+    assert!(cursor.next().is_none());
+
+    let mut cursor = fs.find_by_content_type("video/mp4", None).unwrap();
+    assert_eq!(id, cursor.next().expect("Expected file matching content type.").id);
+}
+
+#[test]
+fn verify_and_clean_orphans() {
+    let (fs, fsfiles, fschunks) = init_gridfs("grid_verify");
+
+    let src_len = (DEFAULT_CHUNK_SIZE as f32 * 2.5) as usize;
+    let mut grid_file = fs.create("intact".to_owned(), None).unwrap();
+    let id = grid_file.id.clone();
+    let _ = grid_file.write(&gen_rand_file(src_len)).unwrap();
+    let _ = grid_file.close().unwrap();
+
+    let report = fs.verify(id.clone()).unwrap();
+    assert!(report.is_valid());
+    assert_eq!(3, report.expected_chunks);
+    assert_eq!(3, report.found_chunks);
+    assert_eq!(src_len as i64, report.bytes_read);
+
+    // Remove the middle chunk.
+    fschunks.delete_one(doc!{ "files_id" => (id.clone()), "n" => 1 }, None).unwrap();
+    let report = fs.verify(id.clone()).unwrap();
+    assert!(!report.is_valid());
+    assert_eq!(vec![1], report.missing_chunks);
+
+    // A duplicate of another chunk must not hide the missing one.
+    fschunks.drop_indexes().unwrap();
+    fschunks.insert_one(doc!{
+        "files_id" => (id.clone()),
+        "n" => 0,
+        "data" => (BinarySubtype::Generic, vec![0u8; 16])
+    }, None).unwrap();
+
+    // Leave chunks behind without a file document, as a crashed writer would.
+    let orphan_id = oid::ObjectId::new().unwrap();
+    for n in 0..3 {
+        fschunks.insert_one(doc!{
+            "files_id" => (orphan_id.clone()),
+            "n" => n,
+            "data" => (BinarySubtype::Generic, vec![0u8; 16])
+        }, None).unwrap();
+    }
+
+    let report = fs.clean_orphans(false).unwrap();
+    assert_eq!(vec![Bson::ObjectId(orphan_id.clone())], report.orphaned_files_ids);
+    assert_eq!(3, report.orphaned_chunks);
+    assert_eq!(vec![id.clone()], report.incomplete_files);
+
+    fs.clean_orphans(true).unwrap();
+    assert_eq!(0, fschunks.count(None, None).unwrap());
+    assert_eq!(0, fsfiles.count(None, None).unwrap());
+
+    let report = fs.clean_orphans(false).unwrap();
+    assert!(report.orphaned_files_ids.is_empty());
+    assert!(report.incomplete_files.is_empty());
+}
+
+#[test]
+fn put_reader_get_writer() {
+    let (fs, _, _) = init_gridfs("grid_stream");
+
+    let src_len = (DEFAULT_CHUNK_SIZE as f32 * 2.5) as usize;
+    let src = gen_rand_file(src_len);
+
+    let mut uploaded = Vec::new();
+    let id = {
+        let mut record = |progress: Progress| uploaded.push(progress);
+        fs.put_reader("stream".to_owned(), io::Cursor::new(src.clone()), None,
+                      Some(&mut record)).unwrap()
+    };
+
+    assert!(uploaded.len() >= 3);
+    assert_eq!(Progress { bytes_transferred: src_len as u64, total_bytes: None },
+               *uploaded.last().unwrap());
+
+    let mut downloaded = Vec::new();
+    let mut dest = Vec::new();
+    let n = {
+        let mut record = |progress: Progress| downloaded.push(progress);
+        fs.get_writer(FileSelector::Id(id), &mut dest, Some(&mut record)).unwrap()
+    };
+
+    assert_eq!(src_len as u64, n);
+    assert_eq!(src, dest);
+    assert_eq!(Progress { bytes_transferred: src_len as u64, total_bytes: Some(src_len as u64) },
+               *downloaded.last().unwrap());
+
+    let mut dest = Vec::new();
+    fs.get_writer(FileSelector::Name("stream".to_owned()), &mut dest, None).unwrap();
+    assert_eq!(src, dest);
+}
+
+#[test]
+fn store_options() {
+    let client = Client::connect("localhost", 27017).unwrap();
+    let db = client.db("grid_store_options");
+
+    let mut options = GridFsStoreOptions::new();
+    options.chunk_size_bytes = 1024;
+    options.max_concurrent_writes = 2;
+
+    let fs = Store::with_options(db.clone(), "backups".to_owned(), options).unwrap();
+    let fschunks = db.collection("backups.chunks");
+    db.collection("backups.files").drop().unwrap();
+    fschunks.drop().unwrap();
+
+    let src = gen_rand_file(10 * 1024 + 10);
+    let mut grid_file = fs.create("backup.tar".to_owned(), None).unwrap();
+    let id = grid_file.id.clone();
+    assert_eq!(1024, grid_file.chunk_size);
+    let _ = grid_file.write(&src).unwrap();
+    let _ = grid_file.close().unwrap();
+
+    assert_eq!(11, fschunks.count(Some(doc!{ "files_id" => (id.clone()) }), None).unwrap());
+    assert!(fs.verify(id.clone()).unwrap().is_valid());
+
+    // Per-file chunk sizes override the store chunk size.
+    let mut upload = GridFsUploadOptions::new();
+    upload.chunk_size_bytes = Some(4096);
+    let id = fs.put_reader("backup2.tar".to_owned(), io::Cursor::new(src.clone()),
+                           Some(upload), None).unwrap();
+    assert_eq!(3, fschunks.count(Some(doc!{ "files_id" => (id.clone()) }), None).unwrap());
+
+    let mut dest = Vec::new();
+    fs.get_writer(FileSelector::Id(id), &mut dest, None).unwrap();
+    assert_eq!(src, dest);
+
+    let mut invalid = GridFsStoreOptions::new();
+    invalid.chunk_size_bytes = 0;
+    assert!(Store::with_options(db.clone(), "invalid".to_owned(), invalid).is_err());
+}
+
+#[test]
+fn bucket_custom_ids() {
+    let bucket = init_bucket("grid_bucket_custom_ids", "fs");
+
+    // Other drivers may store files under ids of any type.
+    let id = Bson::String("report-2016".to_owned());
+    let src = gen_rand_file(1500);
+    bucket.upload_from_stream_with_id(id.clone(), "report.pdf", &mut &src[..], None).unwrap();
+
+    let mut cursor = bucket.find(Some(doc!{ "filename" => "report.pdf" }), None).unwrap();
+    let file = cursor.next().expect("Expected file with a custom id.");
+    assert_eq!(id, file.id);
+
+    let mut dest = Vec::new();
+    bucket.download_to_stream(id.clone(), &mut dest).unwrap();
+    assert_eq!(src, dest);
+
+    assert!(bucket.verify(id.clone()).unwrap().is_valid());
+    bucket.delete(id.clone()).unwrap();
+    assert!(bucket.open_download_stream(id).is_err());
+}
@@ -0,0 +1,30 @@
+use mongodb::common::Deadline;
+use mongodb::connstring::Host;
+use mongodb::pool::ConnectionPool;
+
+fn localhost() -> Host {
+    Host { host_name: "localhost".to_owned(), port: 27017, ipc: String::new() }
+}
+
+#[test]
+fn operation_count() {
+    let pool = ConnectionPool::new(localhost());
+    assert_eq!(0, pool.operation_count());
+
+    let first = pool.acquire_stream(&Deadline::none()).unwrap();
+    let second = pool.acquire_stream(&Deadline::none()).unwrap();
+    assert_eq!(2, pool.operation_count());
+
+    drop(first);
+    assert_eq!(1, pool.operation_count());
+
+    // Discarded streams are closed rather than returned, but still complete their operation.
+    second.discard();
+    drop(second);
+    assert_eq!(0, pool.operation_count());
+
+    let stream = pool.acquire_stream(&Deadline::none()).unwrap();
+    assert_eq!(1, pool.operation_count());
+    drop(stream);
+    assert_eq!(0, pool.operation_count());
+}
@@ -0,0 +1,118 @@
+use chrono::{Duration, UTC};
+
+use mongodb::{Client, ThreadedClient};
+use mongodb::common::{ReadMode, ReadPreference};
+use mongodb::connstring::{self, ConnectionString};
+use mongodb::topology::{TopologyDescription, TopologyType};
+use mongodb::topology::server::{Server, ServerType};
+
+use std::sync::{Arc, RwLock};
+
+// Builds a replica set topology from (host, server type, replication lag in seconds) triples.
+fn build_topology(ttype: TopologyType, members: Vec<(&str, ServerType, i64)>) -> TopologyDescription {
+    let dummy_config = ConnectionString::new("i-dont-exist", 27017);
+    let dummy_client = Client::with_config(dummy_config, None, None).unwrap();
+    let dummy_top_arc = Arc::new(RwLock::new(TopologyDescription::new()));
+
+    let mut topology_description = TopologyDescription::new();
+    topology_description.topology_type = ttype;
+
+    let now = UTC::now();
+
+    for (name, stype, lag) in members {
+        let host = connstring::parse_host(name).unwrap();
+        let server = Server::new(dummy_client.clone(), host.clone(), dummy_top_arc.clone(), false);
+
+        {
+            let mut description = server.description.write().unwrap();
+            description.round_trip_time = Some(5);
+            description.server_type = stype;
+            description.max_wire_version = 5;
+            description.last_update_time = Some(now.clone());
+            description.last_write_date = Some(now.clone() - Duration::seconds(lag));
+        }
+
+        topology_description.servers.insert(host, server);
+    }
+
+    topology_description
+}
+
+fn eligible_hosts(topology_description: &TopologyDescription,
+                  read_preference: &ReadPreference) -> Vec<String> {
+    let (mut hosts, _) = topology_description.choose_hosts(read_preference);
+    topology_description.filter_stale_hosts(&mut hosts, read_preference);
+    let mut names: Vec<_> = hosts.into_iter().map(|host| host.host_name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn max_staleness_with_primary() {
+    let topology_description = build_topology(TopologyType::ReplicaSetWithPrimary, vec![
+        ("a:27017", ServerType::RSPrimary, 0),
+        ("b:27017", ServerType::RSSecondary, 30),
+        ("c:27017", ServerType::RSSecondary, 200),
+    ]);
+
+    let mut read_preference = ReadPreference::new(ReadMode::Secondary, None);
+    read_preference.max_staleness_seconds = Some(120);
+
+    assert_eq!(vec!["b".to_owned()], eligible_hosts(&topology_description, &read_preference));
+}
+
+#[test]
+fn max_staleness_no_primary() {
+    let topology_description = build_topology(TopologyType::ReplicaSetNoPrimary, vec![
+        ("a:27017", ServerType::RSSecondary, 100),
+        ("b:27017", ServerType::RSSecondary, 150),
+        ("c:27017", ServerType::RSSecondary, 300),
+    ]);
+
+    let mut read_preference = ReadPreference::new(ReadMode::Nearest, None);
+    read_preference.max_staleness_seconds = Some(90);
+
+    assert_eq!(vec!["a".to_owned(), "b".to_owned()],
+               eligible_hosts(&topology_description, &read_preference));
+}
+
+#[test]
+fn max_staleness_validation() {
+    let topology_description = TopologyDescription::new();
+
+    let mut read_preference = ReadPreference::new(ReadMode::Primary, None);
+    read_preference.max_staleness_seconds = Some(120);
+    assert!(topology_description.validate_max_staleness(&read_preference).is_err());
+
+    read_preference.mode = ReadMode::Secondary;
+    assert!(topology_description.validate_max_staleness(&read_preference).is_ok());
+
+    read_preference.max_staleness_seconds = Some(89);
+    assert!(topology_description.validate_max_staleness(&read_preference).is_err());
+
+    read_preference.max_staleness_seconds = None;
+    assert!(topology_description.validate_max_staleness(&read_preference).is_ok());
+}
+
+#[test]
+fn max_staleness_validation_wire_version() {
+    let topology_description = build_topology(TopologyType::ReplicaSetWithPrimary, vec![
+        ("a:27017", ServerType::RSPrimary, 0),
+        ("b:27017", ServerType::RSSecondary, 30),
+    ]);
+
+    let mut read_preference = ReadPreference::new(ReadMode::Secondary, None);
+    read_preference.max_staleness_seconds = Some(120);
+    assert!(topology_description.validate_max_staleness(&read_preference).is_ok());
+
+    for server in topology_description.servers.values() {
+        if server.host.host_name == "b" {
+            server.description.write().unwrap().max_wire_version = 4;
+        }
+    }
+
+    assert!(topology_description.validate_max_staleness(&read_preference).is_err());
+
+    read_preference.max_staleness_seconds = None;
+    assert!(topology_description.validate_max_staleness(&read_preference).is_ok());
+}
@@ -4,3 +4,4 @@ pub mod replicasetwithprimary;
 pub mod sharded;
 pub mod single;
 pub mod unknown;
+pub mod max_staleness;